@@ -0,0 +1,220 @@
+//! Measures how the GI passes scale with the number of lights and occluders.
+//!
+//! ```text
+//! cargo run --release --example benchmark -- --lights 100 --occluders 1000
+//! ```
+//!
+//! Lights and occluders are spread over a grid covering the view. After
+//! `--warmup` frames, per-pass GPU timings and the frame time are averaged
+//! over `--frames` frames, printed, and the app exits. Pass timings need a
+//! backend with timestamp queries, otherwise only the frame time is shown.
+
+use bevy::camera::RenderTarget;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy::render::diagnostic::RenderDiagnosticsPlugin;
+use bevy::window::PresentMode;
+use bevy_magic_light_2d::gi::{MAX_LIGHTS, MAX_OCCLUDERS};
+use bevy_magic_light_2d::prelude::*;
+
+const VIEW_SIZE: f32 = 1024.0;
+
+#[derive(Resource, Clone, Copy, Debug)]
+struct BenchmarkArgs
+{
+    lights:    usize,
+    occluders: usize,
+    warmup:    u32,
+    frames:    u32,
+}
+
+impl Default for BenchmarkArgs
+{
+    fn default() -> Self
+    {
+        Self {
+            lights:    10,
+            occluders: 100,
+            warmup:    120,
+            frames:    300,
+        }
+    }
+}
+
+impl BenchmarkArgs
+{
+    fn from_env() -> Self
+    {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(flag) = iter.next() {
+            let value = iter.next().and_then(|value| value.parse::<usize>().ok());
+            match (flag.as_str(), value) {
+                ("--lights", Some(value)) => args.lights = value,
+                ("--occluders", Some(value)) => args.occluders = value,
+                ("--warmup", Some(value)) => args.warmup = value as u32,
+                ("--frames", Some(value)) => args.frames = value.max(1) as u32,
+                _ => {
+                    eprintln!(
+                        "usage: benchmark [--lights N] [--occluders N] [--warmup N] [--frames N]"
+                    );
+                    std::process::exit(2);
+                }
+            }
+        }
+
+        if args.lights > MAX_LIGHTS {
+            eprintln!("{} lights requested, only {} are shaded", args.lights, MAX_LIGHTS);
+        }
+        if args.occluders > MAX_OCCLUDERS {
+            eprintln!("{} occluders requested, only {} are used", args.occluders, MAX_OCCLUDERS);
+        }
+        args
+    }
+}
+
+/// Timings sampled after the warmup, in milliseconds.
+#[derive(Resource, Default)]
+struct BenchmarkSamples
+{
+    frame:    u32,
+    frame_ms: Vec<f64>,
+    pass_ms:  [Vec<f64>; GiPass::ALL.len()],
+}
+
+fn main()
+{
+    let args = BenchmarkArgs::from_env();
+
+    App::new()
+        .insert_resource(args)
+        .init_resource::<BenchmarkSamples>()
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: (VIEW_SIZE as u32, VIEW_SIZE as u32).into(),
+                    title: "Bevy Magic Light 2D: Benchmark".into(),
+                    resizable: false,
+                    // Uncapped, so the frame time isn't the refresh interval.
+                    present_mode: PresentMode::AutoNoVsync,
+                    ..default()
+                }),
+                ..default()
+            }),
+            FrameTimeDiagnosticsPlugin::default(),
+            RenderDiagnosticsPlugin,
+            BevyMagicLight2DPlugin,
+        ))
+        .add_systems(Startup, setup.after(setup_post_processing_camera))
+        .add_systems(Update, system_sample_timings)
+        .run();
+}
+
+/// Points of a square grid covering the view, one per item.
+fn grid_positions(count: usize) -> impl Iterator<Item = Vec2>
+{
+    let side = (count as f32).sqrt().ceil().max(1.0) as usize;
+    let spacing = VIEW_SIZE / side as f32;
+    (0..count).map(move |i| {
+        let cell = Vec2::new((i % side) as f32, (i / side) as f32);
+        (cell + 0.5) * spacing - VIEW_SIZE * 0.5
+    })
+}
+
+fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>, args: Res<BenchmarkArgs>)
+{
+    let occluder_spacing = VIEW_SIZE / (args.occluders as f32).sqrt().ceil().max(1.0);
+    for position in grid_positions(args.occluders) {
+        commands.spawn((
+            Transform::from_translation(position.extend(0.0)),
+            Visibility::default(),
+            LightOccluder2D {
+                h_size: Vec2::splat(occluder_spacing * 0.2),
+                ..default()
+            },
+        ));
+    }
+
+    // Offset by a quarter cell, so lights sit between the occluders.
+    let light_offset = Vec2::splat(VIEW_SIZE / (args.lights as f32).sqrt().ceil().max(1.0) * 0.25);
+    for (i, position) in grid_positions(args.lights).enumerate() {
+        commands.spawn((
+            Transform::from_translation((position + light_offset).extend(0.0)),
+            Visibility::default(),
+            OmniLightSource2D {
+                intensity: 1.0,
+                color: Color::hsl((i * 47 % 360) as f32, 0.8, 0.6),
+                falloff: Vec3::new(1.5, 10.0, 0.005),
+                ..default()
+            },
+        ));
+    }
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(camera_targets.floor_render_target()),
+            ..default()
+        },
+        Name::new("main_camera"),
+        FloorCamera,
+    ));
+}
+
+fn system_sample_timings(
+    args: Res<BenchmarkArgs>,
+    store: Res<DiagnosticsStore>,
+    gi_diagnostics: Res<GiDiagnostics>,
+    gi_pipeline_status: Res<GiPipelineStatus>,
+    mut samples: ResMut<BenchmarkSamples>,
+    mut app_exit: MessageWriter<AppExit>,
+)
+{
+    // Pipelines compile asynchronously, the warmup starts once they run.
+    if !gi_pipeline_status.is_ready() {
+        return;
+    }
+    samples.frame += 1;
+    if samples.frame <= args.warmup {
+        return;
+    }
+
+    if let Some(frame_ms) = store
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|diagnostic| diagnostic.value())
+    {
+        samples.frame_ms.push(frame_ms);
+    }
+    for pass in GiPass::ALL {
+        if let Some(ms) = gi_diagnostics.pass(pass) {
+            samples.pass_ms[pass as usize].push(ms);
+        }
+    }
+
+    if samples.frame < args.warmup + args.frames {
+        return;
+    }
+
+    let average = |values: &[f64]| {
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    };
+    let format = |ms: Option<f64>| ms.map_or_else(|| "n/a".to_string(), |ms| format!("{:.3} ms", ms));
+
+    println!(
+        "lights: {}, occluders: {}, frames: {}",
+        args.lights, args.occluders, args.frames
+    );
+    let mut total = None;
+    for pass in GiPass::ALL {
+        let ms = average(&samples.pass_ms[pass as usize]);
+        total = match (total, ms) {
+            (Some(total), Some(ms)) => Some(total + ms),
+            (total, ms) => total.or(ms),
+        };
+        println!("  {:<8} {}", pass.display_name(), format(ms));
+    }
+    println!("  {:<8} {}", "GI total", format(total));
+    println!("  {:<8} {}", "Frame", format(average(&samples.frame_ms)));
+
+    app_exit.write(AppExit::Success);
+}
@@ -3,9 +3,10 @@ use bevy::color::palettes;
 use bevy::image::{ImageFilterMode, ImageSamplerDescriptor};
 use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
+use bevy::render::diagnostic::RenderDiagnosticsPlugin;
 use bevy::window::PrimaryWindow;
 use bevy_inspector_egui::bevy_egui::EguiPlugin;
-use bevy_inspector_egui::quick::ResourceInspectorPlugin;
+use bevy_inspector_egui::quick::{ResourceInspectorPlugin, WorldInspectorPlugin};
 use bevy_magic_light_2d::prelude::*;
 use rand::prelude::*;
 
@@ -30,9 +31,13 @@ pub struct Movable;
 
 fn main()
 {
+    let mut app = App::new();
+    // Has to come before `DefaultPlugins`, see `HotReloadShadersPlugin`.
+    #[cfg(feature = "hot_reload_shaders")]
+    app.add_plugins(HotReloadShadersPlugin);
+
     // Basic setup.
-    App::new()
-        .insert_resource(ClearColor(Color::srgba_u8(0, 0, 0, 0)))
+    app.insert_resource(ClearColor(Color::srgba_u8(0, 0, 0, 0)))
         .add_plugins((
             DefaultPlugins
                 .set(AssetPlugin {
@@ -55,9 +60,12 @@ fn main()
                         ..default()
                     },
                 }),
+            RenderDiagnosticsPlugin,
             BevyMagicLight2DPlugin,
             CameraViewerPlugin,
             ResourceInspectorPlugin::<BevyMagicLight2DSettings>::new(),
+            WorldInspectorPlugin::new(),
+            LightInspectorPlugin,
         ))
         .insert_resource(BevyMagicLight2DSettings {
             light_pass_params: LightPassParams {
@@ -301,6 +309,7 @@ fn setup(
     // Add walls with occluder component.
     let occluder_data = LightOccluder2D {
         h_size: block_size / 2.0,
+        ..default()
     };
     for (i, row) in walls_info.iter().enumerate() {
         for (j, cell) in row.iter().enumerate() {
@@ -396,6 +405,7 @@ fn setup(
                     .insert(RenderLayers::layer(CAMERA_LAYER_OBJECTS))
                     .insert(LightOccluder2D {
                         h_size: Vec2::splat(2.0),
+                        ..default()
                     })
                     .insert(Name::new("candle_1"))
                     .id(),
@@ -429,6 +439,7 @@ fn setup(
                     .insert(RenderLayers::layer(CAMERA_LAYER_OBJECTS))
                     .insert(LightOccluder2D {
                         h_size: Vec2::splat(2.0),
+                        ..default()
                     })
                     .insert(Name::new("candle_2"))
                     .id(),
@@ -463,6 +474,7 @@ fn setup(
                     .insert(RenderLayers::layer(CAMERA_LAYER_OBJECTS))
                     .insert(LightOccluder2D {
                         h_size: Vec2::splat(2.0),
+                        ..default()
                     })
                     .insert(Name::new("candle_3"))
                     .id(),
@@ -496,6 +508,7 @@ fn setup(
                     .insert(RenderLayers::layer(CAMERA_LAYER_OBJECTS))
                     .insert(LightOccluder2D {
                         h_size: Vec2::splat(2.0),
+                        ..default()
                     })
                     .insert(Name::new("candle_4"))
                     .id(),
@@ -527,6 +540,7 @@ fn setup(
                     .insert(RenderLayers::layer(CAMERA_LAYER_OBJECTS))
                     .insert(LightOccluder2D {
                         h_size: Vec2::new(72.8, 31.0),
+                        ..default()
                     })
                     .insert(Name::new("tomb_1"))
                     .id(),
@@ -558,6 +572,7 @@ fn setup(
                     .insert(RenderLayers::layer(CAMERA_LAYER_OBJECTS))
                     .insert(LightOccluder2D {
                         h_size: Vec2::new(72.8, 31.0),
+                        ..default()
                     })
                     .insert(Name::new("tomb_1"))
                     .id(),
@@ -741,6 +756,7 @@ fn setup(
                 color:              Color::srgb_u8(0, 206, 94),
                 jitter_intensity:   0.7,
                 jitter_translation: 3.0,
+                ..default()
             },
         ));
 
@@ -755,6 +771,7 @@ fn setup(
                 color:              Color::srgb_u8(0, 206, 94),
                 jitter_intensity:   0.7,
                 jitter_translation: 3.0,
+                ..default()
             },
         ));
     }
@@ -772,6 +789,7 @@ fn setup(
         .insert(Name::new("skylight_mask_1"))
         .insert(SkylightMask2D {
             h_size: Vec2::new(430.0, 330.0),
+            ..default()
         });
     commands
         .spawn((
@@ -781,6 +799,7 @@ fn setup(
         .insert(Name::new("skylight_mask_2"))
         .insert(SkylightMask2D {
             h_size: Vec2::new(163.3, 156.1),
+            ..default()
         });
 
     // Add skylight light.
@@ -825,7 +844,7 @@ fn setup(
         .spawn((
             Camera2d,
             Camera {
-                target: RenderTarget::Image(camera_targets.floor_target.clone().unwrap().into()),
+                target: RenderTarget::Image(camera_targets.floor_render_target()),
                 ..default()
             },
             projection.clone(),
@@ -838,7 +857,7 @@ fn setup(
         .spawn((
             Camera2d,
             Camera {
-                target: RenderTarget::Image(camera_targets.walls_target.clone().unwrap().into()),
+                target: RenderTarget::Image(camera_targets.walls_render_target()),
                 ..default()
             },
             projection.clone(),
@@ -851,7 +870,7 @@ fn setup(
         .spawn((
             Camera2d,
             Camera {
-                target: RenderTarget::Image(camera_targets.objects_target.clone().unwrap().into()),
+                target: RenderTarget::Image(camera_targets.objects_render_target()),
                 ..default()
             },
             projection,
@@ -36,6 +36,7 @@ fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>)
             Visibility::default(),
             LightOccluder2D {
                 h_size: Vec2::new(40.0, 20.0),
+                ..default()
             },
         ))
         .id();
@@ -111,7 +112,7 @@ fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>)
         .spawn((
             Camera2d,
             Camera {
-                target: RenderTarget::Image(camera_targets.floor_target.clone().into()),
+                target: RenderTarget::Image(camera_targets.floor_render_target()),
                 ..Default::default()
             },
             Name::new("main_camera"),
@@ -54,6 +54,7 @@ fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>)
             Visibility::default(),
             LightOccluder2D {
                 h_size: Vec2::new(80.0, 40.0),
+                ..default()
             },
             Mover,
         ))
@@ -117,7 +118,7 @@ fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>)
     commands.spawn((
         Camera2d,
         Camera {
-            target: RenderTarget::Image(camera_targets.floor_target.clone().into()),
+            target: RenderTarget::Image(camera_targets.floor_render_target()),
             ..Default::default()
         },
         Name::new("main_camera"),
@@ -0,0 +1,95 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::camera::RenderTarget;
+use bevy::prelude::*;
+use bevy_magic_light_2d::prelude::*;
+
+#[derive(Component)]
+struct RotatingWalls;
+
+fn main()
+{
+    // Basic setup.
+    App::new()
+        .insert_resource(ClearColor(Color::srgba_u8(255, 255, 255, 0)))
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: (512u32, 512u32).into(),
+                    title: "Bevy Magic Light 2D: Rotated Occluder Example".into(),
+                    resizable: false,
+                    ..default()
+                }),
+                ..default()
+            }),
+            BevyMagicLight2DPlugin,
+        ))
+        .add_systems(Startup, setup.after(setup_post_processing_camera))
+        .add_systems(Update, system_rotate_walls)
+        .run();
+}
+
+fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>)
+{
+    // A wall rotated by 45° directly.
+    commands.spawn((
+        Name::new("wall_45"),
+        Transform::from_translation(Vec3::new(-100.0, 0.0, 0.0))
+            .with_rotation(Quat::from_rotation_z(FRAC_PI_4)),
+        Visibility::default(),
+        LightOccluder2D {
+            h_size: Vec2::new(60.0, 8.0),
+            ..default()
+        },
+    ));
+
+    // A wall that inherits its rotation from a rotating parent.
+    commands
+        .spawn((
+            Name::new("rotating_walls"),
+            Transform::from_translation(Vec3::new(100.0, 0.0, 0.0)),
+            Visibility::default(),
+            RotatingWalls,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Name::new("wall_child"),
+                Transform::default(),
+                Visibility::default(),
+                LightOccluder2D {
+                    h_size: Vec2::new(60.0, 8.0),
+                    ..default()
+                },
+            ));
+        });
+
+    commands.spawn((
+        Name::new("light"),
+        Transform::from_translation(Vec3::new(0.0, 150.0, 0.0)),
+        Visibility::default(),
+        OmniLightSource2D {
+            intensity: 1.0,
+            color: Color::srgb_u8(255, 230, 180),
+            falloff: Vec3::new(1.5, 10.0, 0.005),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(camera_targets.floor_render_target()),
+            ..default()
+        },
+        Name::new("main_camera"),
+        FloorCamera,
+        SpriteCamera,
+    ));
+}
+
+fn system_rotate_walls(mut query: Query<&mut Transform, With<RotatingWalls>>, time: Res<Time>)
+{
+    for mut transform in query.iter_mut() {
+        transform.rotate_z(time.delta_secs() * 0.5);
+    }
+}
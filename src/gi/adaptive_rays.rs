@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use crate::gi::resource::BevyMagicLight2DSettings;
+
+/// Frame time controller of the indirect ray count, see
+/// [`BevyMagicLight2DSettings::adaptive_rays`].
+///
+/// The frame time includes everything else the app does. With vsync it
+/// can't drop below the refresh interval, so a target at the refresh rate
+/// only ever lowers the ray count.
+#[derive(Copy, Clone, Debug, Reflect)]
+pub struct AdaptiveRayParams
+{
+    /// Frame rate the ray count is adjusted to hold.
+    pub target_fps: f32,
+    pub min_rays:   i32,
+    pub max_rays:   i32,
+    /// Adjustment rate, higher values react faster but may oscillate.
+    /// Roughly the inverse of the time in seconds to close most of the gap.
+    pub speed:      f32,
+}
+
+impl Default for AdaptiveRayParams
+{
+    fn default() -> Self
+    {
+        Self {
+            target_fps: 60.0,
+            min_rays:   4,
+            max_rays:   64,
+            speed:      2.0,
+        }
+    }
+}
+
+/// Indirect rays per sample currently traced by the bounce pass.
+///
+/// Follows `LightPassParams::indirect_rays_per_sample`, or is adjusted to
+/// the measured frame time when [`BevyMagicLight2DSettings::adaptive_rays`]
+/// is set.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct GiAdaptiveRays
+{
+    /// Kept fractional so small steps accumulate over frames.
+    rays:           f32,
+    /// Smoothed frame time in seconds, only measured while the controller is
+    /// enabled.
+    pub frame_time: Option<f32>,
+}
+
+impl GiAdaptiveRays
+{
+    pub fn indirect_rays_per_sample(&self) -> i32
+    {
+        self.rays.round() as i32
+    }
+}
+
+/// Smoothing of the measured frame time, so single hitches don't drop the
+/// ray count.
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+
+pub(crate) fn system_update_adaptive_rays(
+    settings: Res<BevyMagicLight2DSettings>,
+    time: Res<Time<Real>>,
+    mut adaptive_rays: ResMut<GiAdaptiveRays>,
+)
+{
+    let Some(params) = settings.adaptive_rays else {
+        let rays = settings.light_pass_params.indirect_rays_per_sample as f32;
+        if adaptive_rays.rays != rays || adaptive_rays.frame_time.is_some() {
+            *adaptive_rays = GiAdaptiveRays {
+                rays,
+                frame_time: None,
+            };
+        }
+        return;
+    };
+
+    let min = params.min_rays.max(0) as f32;
+    let max = (params.max_rays as f32).max(min);
+    // Adapts from the configured count, starting at the minimum would never
+    // be raised while vsync holds the frame time at the target.
+    if adaptive_rays.frame_time.is_none() {
+        adaptive_rays.rays = (settings.light_pass_params.indirect_rays_per_sample as f32).clamp(min, max);
+    }
+    let dt = time.delta_secs();
+    if dt <= 0.0 || params.target_fps <= 0.0 {
+        adaptive_rays.rays = adaptive_rays.rays.clamp(min, max);
+        return;
+    }
+
+    let frame_time = match adaptive_rays.frame_time {
+        Some(frame_time) => frame_time + (dt - frame_time) * FRAME_TIME_SMOOTHING,
+        None => dt,
+    };
+
+    // Assumes the frame time scales with the ray count, the step towards
+    // the matching count is damped by `speed`.
+    let target = (adaptive_rays.rays.max(1.0) * params.target_fps.recip() / frame_time).clamp(min, max);
+    let t = 1.0 - (-params.speed.max(0.0) * dt).exp();
+    let rays = adaptive_rays.rays + (target - adaptive_rays.rays) * t;
+
+    adaptive_rays.rays = rays.clamp(min, max);
+    adaptive_rays.frame_time = Some(frame_time);
+}
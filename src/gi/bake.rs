@@ -0,0 +1,183 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
+
+use crate::gi::pipeline::{create_baked_irradiance_image, GiTargetFormats, GiTargetsWrapper};
+use crate::gi::resource::{ComputedTargetSizes, GiUpdateClock};
+use crate::gi::types::{LightOccluder2D, OmniLightSource2D, RectLightSource2D, TileOccluderGrid};
+use crate::FloorCamera;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightBakeState
+{
+    /// GI is computed every frame.
+    #[default]
+    Live,
+    /// GI is computed until enough updates have accumulated, counted in GI
+    /// updates so `gi_update_hz` doesn't shorten the bake.
+    Converging
+    {
+        frames_left: u32,
+    },
+    /// The filtered irradiance is copied into the baked image this frame.
+    Copying,
+    /// The compute passes are idle and the baked image is composited.
+    Baked,
+}
+
+/// Freezes converged lighting of a static scene into an image.
+///
+/// Call [`LightBake::bake`] once lights and occluders are in place. After the
+/// given number of GI updates the filtered irradiance is copied into a
+/// persistent image and the GI compute passes stop running. Changing any light,
+/// occluder or the floor camera, or resizing the window, once baked
+/// invalidates the bake and switches back to live GI.
+///
+/// The bake reads the spatial filter output, so the filter pass has to be
+/// enabled while converging.
+#[derive(Resource, Default, Clone, ExtractResource)]
+pub struct LightBake
+{
+    state: LightBakeState,
+    image: Option<Handle<Image>>,
+}
+
+impl LightBake
+{
+    /// Starts baking, the bake completes after `frames` GI updates.
+    pub fn bake(&mut self, frames: u32)
+    {
+        self.state = LightBakeState::Converging { frames_left: frames };
+    }
+
+    /// Drops the bake and resumes live GI.
+    pub fn invalidate(&mut self)
+    {
+        self.state = LightBakeState::Live;
+    }
+
+    pub fn state(&self) -> LightBakeState
+    {
+        self.state
+    }
+
+    pub fn is_baked(&self) -> bool
+    {
+        self.state == LightBakeState::Baked
+    }
+
+    /// Baked irradiance, only valid once [`Self::is_baked`] returns `true`.
+    pub fn image(&self) -> Option<&Handle<Image>>
+    {
+        self.image.as_ref()
+    }
+
+    pub(crate) fn is_copying(&self) -> bool
+    {
+        self.state == LightBakeState::Copying
+    }
+}
+
+#[rustfmt::skip]
+pub(crate) fn system_update_light_bake(
+    mut light_bake:     ResMut<LightBake>,
+    mut images:         ResMut<Assets<Image>>,
+        target_sizes:   Res<ComputedTargetSizes>,
+        target_formats: Res<GiTargetFormats>,
+        gi_clock:       Res<GiUpdateClock>,
+) {
+    match light_bake.state {
+        LightBakeState::Live | LightBakeState::Baked => {}
+        LightBakeState::Converging { frames_left } => {
+            // Allocate the image early so that it is prepared in the render
+            // world by the time it is copied into.
+            let size = target_sizes.primary_target_usize;
+            let needs_image = match light_bake.image.as_ref().and_then(|h| images.get(h)) {
+                Some(image) => image.size() != size,
+                None => true,
+            };
+            if needs_image {
+                let image = create_baked_irradiance_image(size, &target_formats);
+                match light_bake.image {
+                    Some(ref handle) => {
+                        let _ = images.insert(handle.id(), image);
+                    }
+                    None => light_bake.image = Some(images.add(image)),
+                }
+            }
+
+            // Runs before the clock is advanced, so `update_due` is whether
+            // the previous frame computed GI.
+            if !gi_clock.update_due() {
+                return;
+            }
+            light_bake.state = match frames_left {
+                0 => LightBakeState::Copying,
+                n => LightBakeState::Converging { frames_left: n - 1 },
+            };
+        }
+        LightBakeState::Copying => {
+            light_bake.state = LightBakeState::Baked;
+            log::info!("GI bake complete");
+        }
+    }
+}
+
+/// Detects changes of anything the GI depends on: lights, occluders, the
+/// floor camera and the GI targets.
+#[rustfmt::skip]
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub(crate) struct GiSceneChanges<'w, 's>
+{
+    gi_targets_wrapper:   Res<'w, GiTargetsWrapper>,
+    query_lights:         Query<'w, 's, (), (With<OmniLightSource2D>, Or<(Changed<OmniLightSource2D>, Changed<GlobalTransform>)>)>,
+    query_rect_lights:    Query<'w, 's, (), (With<RectLightSource2D>, Or<(Changed<RectLightSource2D>, Changed<GlobalTransform>)>)>,
+    query_occluders:      Query<'w, 's, (), (With<LightOccluder2D>, Or<(Changed<LightOccluder2D>, Changed<GlobalTransform>)>)>,
+    query_tile_grids:     Query<'w, 's, (), Changed<TileOccluderGrid>>,
+    query_camera:         Query<'w, 's, (), (With<FloorCamera>, Changed<GlobalTransform>)>,
+    removed_lights:       RemovedComponents<'w, 's, OmniLightSource2D>,
+    removed_rect_lights:  RemovedComponents<'w, 's, RectLightSource2D>,
+    removed_occluders:    RemovedComponents<'w, 's, LightOccluder2D>,
+    removed_tile_grids:   RemovedComponents<'w, 's, TileOccluderGrid>,
+}
+
+impl GiSceneChanges<'_, '_>
+{
+    /// Whether anything changed since the system last ran. Has to be called
+    /// every run, removals are only reported once.
+    pub(crate) fn any(&mut self) -> bool
+    {
+        let removed = self.removed_lights.read().count()
+            + self.removed_rect_lights.read().count()
+            + self.removed_occluders.read().count()
+            + self.removed_tile_grids.read().count()
+            > 0;
+
+        removed
+            || self.gi_targets_wrapper.is_changed()
+            || !self.query_lights.is_empty()
+            || !self.query_rect_lights.is_empty()
+            || !self.query_occluders.is_empty()
+            || !self.query_tile_grids.is_empty()
+            || !self.query_camera.is_empty()
+    }
+}
+
+pub(crate) fn system_invalidate_light_bake(
+    mut light_bake: ResMut<LightBake>,
+    mut scene_changes: GiSceneChanges,
+)
+{
+    let changed = scene_changes.any();
+
+    // Changes while converging are simply accumulated over.
+    if !matches!(light_bake.state, LightBakeState::Copying | LightBakeState::Baked) {
+        return;
+    }
+
+    if changed {
+        log::info!("Scene changed, GI bake invalidated");
+        light_bake.invalidate();
+    }
+}
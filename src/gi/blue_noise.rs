@@ -0,0 +1,68 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+
+const SIGMA: f32 = 1.5;
+
+/// Generates a tileable blue-noise texture of `size * size` texels.
+///
+/// Texels are ranked with a simplified void-and-cluster pass: starting from a
+/// single point, the texel in the largest void (lowest gaussian energy on the
+/// torus) is picked next. The rank normalized to `0..=255` is stored in the
+/// red channel.
+pub(crate) fn create_blue_noise_image(size: u32) -> Image
+{
+    let n = size as usize;
+    let count = n * n;
+
+    // Gaussian falloff for every toroidal offset.
+    let mut kernel = vec![0.0_f32; count];
+    for y in 0..n {
+        for x in 0..n {
+            let dx = x.min(n - x) as f32;
+            let dy = y.min(n - y) as f32;
+            kernel[y * n + x] = (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp();
+        }
+    }
+
+    let mut energy = vec![0.0_f32; count];
+    let mut rank = vec![u32::MAX; count];
+
+    let mut next = 0;
+    for r in 0..count as u32 {
+        rank[next] = r;
+
+        let (px, py) = (next % n, next / n);
+        for y in 0..n {
+            let ky = (y + n - py) % n;
+            for x in 0..n {
+                let kx = (x + n - px) % n;
+                energy[y * n + x] += kernel[ky * n + kx];
+            }
+        }
+
+        next = (0..count)
+            .filter(|i| rank[*i] == u32::MAX)
+            .min_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+            .unwrap_or(0);
+    }
+
+    let data = rank
+        .iter()
+        .map(|r| (*r as u64 * 256 / count as u64) as u8)
+        .collect::<Vec<_>>();
+
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+    image
+}
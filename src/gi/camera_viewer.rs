@@ -1,17 +1,18 @@
 use bevy::camera::visibility::RenderLayers;
+use bevy::camera::RenderTarget;
 use bevy::prelude::*;
+use bevy::render::render_resource::Extent3d;
+use bevy::window::PrimaryWindow;
 use bevy_egui::EguiPrimaryContextPass;
 use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
 use log::info;
 
-use crate::gi::compositing::CameraTargets;
-use crate::gi::render_layer::{
-    ALL_LAYERS,
-    CAMERA_LAYER_FLOOR,
-    CAMERA_LAYER_OBJECTS,
-    CAMERA_LAYER_POST_PROCESSING,
-    CAMERA_LAYER_WALLS,
-};
+use crate::gi::compositing::{create_layer_image, upscale_source, CameraTargets};
+use crate::gi::diagnostics::{GiConvergence, GiDiagnostics, GiPass};
+use crate::gi::render_layer::MagicLightLayers;
+use crate::gi::resource::BevyMagicLight2DSettings;
+use crate::gi::types::OmniLightSource2D;
+use crate::FloorCamera;
 
 
 
@@ -21,6 +22,15 @@ pub struct CameraViewerState
 {
     pub selected_camera: CameraType,
     pub show_window:     bool,
+    /// Light shown in the falloff plot.
+    pub selected_light:  Option<Entity>,
+    /// Clicking the displayed render target spawns a copy of `light_template`
+    /// at the clicked world position.
+    pub place_lights:    bool,
+    pub light_template:  OmniLightSource2D,
+    /// Lights spawned by clicking, listed with their positions so they can
+    /// be copied into code.
+    pub placed_lights:   Vec<Entity>,
 }
 
 impl Default for CameraViewerState
@@ -30,6 +40,15 @@ impl Default for CameraViewerState
         Self {
             selected_camera: CameraType::Floor,
             show_window:     false,
+            selected_light:  None,
+            place_lights:    false,
+            light_template:  OmniLightSource2D {
+                intensity: 1.0,
+                color:     Color::WHITE,
+                falloff:   Vec3::new(1.5, 10.0, 0.005),
+                ..default()
+            },
+            placed_lights:   Vec::new(),
         }
     }
 }
@@ -68,18 +87,30 @@ impl CameraType
         }
     }
 
-    pub fn layers(&self) -> RenderLayers
+    pub fn layers(&self, layers: &MagicLightLayers) -> RenderLayers
     {
         match self {
-            Self::Floor => RenderLayers::layer(CAMERA_LAYER_FLOOR),
-            Self::Walls => RenderLayers::layer(CAMERA_LAYER_WALLS),
-            Self::Objects => RenderLayers::layer(CAMERA_LAYER_OBJECTS),
-            Self::PostProcessing => RenderLayers::layer(CAMERA_LAYER_POST_PROCESSING),
-            Self::Combined => RenderLayers::from_layers(ALL_LAYERS),
+            Self::Floor => layers.floor_layers(),
+            Self::Walls => layers.walls_layers(),
+            Self::Objects => layers.objects_layers(),
+            Self::PostProcessing => layers.post_processing_layers(),
+            Self::Combined => layers.content_layers(),
         }
     }
 }
 
+/// Copy of the composite shown by the viewer while the post-processing
+/// camera renders to the window.
+#[derive(Resource, Default)]
+struct CompositePreview
+{
+    image: Option<Handle<Image>>,
+}
+
+/// Renders the post-processing layers into [`CompositePreview`].
+#[derive(Component)]
+struct CompositePreviewCamera;
+
 pub struct CameraViewerPlugin;
 
 impl Plugin for CameraViewerPlugin
@@ -87,7 +118,9 @@ impl Plugin for CameraViewerPlugin
     fn build(&self, app: &mut App)
     {
         app.init_resource::<CameraViewerState>()
+            .init_resource::<CompositePreview>()
             .add_systems(EguiPrimaryContextPass, camera_viewer_ui_system)
+            .add_systems(Update, update_composite_preview)
             .add_systems(Update, register_render_target_textures.run_if(resource_changed::<CameraTargets>));
     }
 }
@@ -105,21 +138,139 @@ fn register_render_target_textures(
     egui_user_textures.add_image(bevy_egui::EguiTextureHandle::Strong(
         camera_targets.objects_target.clone().unwrap(),
     ));
+    if let Some(composite_target) = camera_targets.composite_target.clone() {
+        egui_user_textures.add_image(bevy_egui::EguiTextureHandle::Strong(composite_target));
+    }
+    if let Some(upscale_source) = camera_targets.upscale_source.clone() {
+        egui_user_textures.add_image(bevy_egui::EguiTextureHandle::Strong(upscale_source));
+    }
 
     info!("Done Render target textures registered with egui!");
 }
 
+/// Image the composite is rendered into by the plugin, `None` when it is
+/// rendered to the window.
+fn composite_output(settings: &BevyMagicLight2DSettings, camera_targets: &CameraTargets) -> Option<Handle<Image>>
+{
+    camera_targets
+        .composite_target
+        .clone()
+        .or_else(|| upscale_source(settings, camera_targets))
+}
+
+/// Keeps a camera mirroring the post-processing layers into
+/// [`CompositePreview`] while the composite is viewed and isn't rendered
+/// into an image already. The preview matches the window's logical size, so
+/// it is framed like the window.
+#[rustfmt::skip]
+#[allow(clippy::too_many_arguments)]
+fn update_composite_preview(
+    mut commands:       Commands,
+    mut images:         ResMut<Assets<Image>>,
+    mut preview:        ResMut<CompositePreview>,
+    mut egui_contexts:  EguiContexts,
+        viewer_state:   Res<CameraViewerState>,
+        camera_targets: Res<CameraTargets>,
+        settings:       Res<BevyMagicLight2DSettings>,
+        query_window:   Query<&Window, With<PrimaryWindow>>,
+    mut query_camera:   Query<(Entity, &mut Camera, &mut RenderLayers), With<CompositePreviewCamera>>,
+) {
+    let size = query_window
+        .single()
+        .ok()
+        .map(|window| UVec2::new(window.width() as u32, window.height() as u32).max(UVec2::ONE))
+        .filter(|_| {
+            viewer_state.show_window
+                && viewer_state.selected_camera == CameraType::PostProcessing
+                && composite_output(&settings, &camera_targets).is_none()
+        });
+    let Some(size) = size else {
+        for (entity, ..) in query_camera.iter() {
+            commands.entity(entity).despawn();
+        }
+        if let Some(image) = preview.image.take() {
+            egui_contexts.remove_image(&image);
+        }
+        return;
+    };
+
+    let stale = preview
+        .image
+        .as_ref()
+        .and_then(|image| images.get(image))
+        .is_none_or(|image| image.size() != size);
+    if stale {
+        if let Some(image) = preview.image.take() {
+            egui_contexts.remove_image(&image);
+        }
+        let extent = Extent3d {
+            width: size.x,
+            height: size.y,
+            ..default()
+        };
+        let image = images.add(create_layer_image("camera_viewer_composite_preview", extent, false));
+        egui_contexts.add_image(bevy_egui::EguiTextureHandle::Strong(image.clone()));
+        preview.image = Some(image);
+    }
+    let Some(image) = preview.image.clone() else {
+        return;
+    };
+
+    let target = RenderTarget::Image(image.into());
+    let layers = settings.render_layers.post_processing_layers();
+    let order = settings.post_processing_camera_params.order;
+    match query_camera.single_mut() {
+        Ok((_, mut camera, mut render_layers)) => {
+            if camera.target != target {
+                camera.target = target;
+            }
+            if camera.order != order {
+                camera.order = order;
+            }
+            if *render_layers != layers {
+                *render_layers = layers;
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                Name::new("camera_viewer_composite_preview"),
+                CompositePreviewCamera,
+                Camera2d,
+                Camera {
+                    order,
+                    target,
+                    ..default()
+                },
+                layers,
+            ));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn camera_viewer_ui_system(
+    mut commands: Commands,
     mut egui_contexts: EguiContexts,
     camera_targets: Res<CameraTargets>,
     mut viewer_state: ResMut<CameraViewerState>,
     images: Res<Assets<Image>>,
+    composite_preview: Res<CompositePreview>,
+    gi_diagnostics: Res<GiDiagnostics>,
+    gi_convergence: Res<GiConvergence>,
+    settings: Res<BevyMagicLight2DSettings>,
+    mut query_lights: Query<(Entity, Option<&Name>, &mut OmniLightSource2D)>,
+    query_transforms: Query<&GlobalTransform>,
+    query_floor_camera: Query<(&Camera, &GlobalTransform), With<FloorCamera>>,
 )
 {
     // Check texture IDs before the window to avoid borrowing issues
     let floor_texture_id = egui_contexts.image_id(camera_targets.floor_target.as_ref().unwrap());
     let walls_texture_id = egui_contexts.image_id(camera_targets.walls_target.as_ref().unwrap());
     let objects_texture_id = egui_contexts.image_id(camera_targets.objects_target.as_ref().unwrap());
+    let composite_image = composite_output(&settings, &camera_targets).or_else(|| composite_preview.image.clone());
+    let composite_texture_id = composite_image
+        .as_ref()
+        .and_then(|target| egui_contexts.image_id(target));
 
     let Ok(ctx) = egui_contexts.ctx_mut() else {
         return;
@@ -186,7 +337,7 @@ fn camera_viewer_ui_system(
             ui.separator();
 
             // Display the selected camera's render target
-            match viewer_state.selected_camera {
+            let clicked_uv = match viewer_state.selected_camera {
                 CameraType::Floor => display_render_target(
                     ui,
                     &camera_targets.floor_target,
@@ -209,9 +360,19 @@ fn camera_viewer_ui_system(
                     objects_texture_id,
                 ),
                 CameraType::PostProcessing => {
-                    ui.label("Post Processing View");
-                    ui.label("(This is what you see in the main window)");
-                    ui.label("The post processing combines all layers with lighting effects");
+                    if composite_image.is_some() {
+                        display_render_target(
+                            ui,
+                            &composite_image,
+                            &images,
+                            "Post Processing",
+                            composite_texture_id,
+                        )
+                    } else {
+                        // The preview camera is spawned on the next update.
+                        ui.label("Preparing the composite preview…");
+                        None
+                    }
                 }
                 CameraType::Combined => {
                     ui.label("Combined View");
@@ -246,11 +407,84 @@ fn camera_viewer_ui_system(
                             );
                             ui.end_row();
                         });
+                    None
+                }
+            };
+
+            // Every layer is rendered with the floor camera's view.
+            if let Some(uv) = clicked_uv.filter(|_| viewer_state.place_lights) {
+                let world_pose = query_floor_camera.single().ok().and_then(|(camera, transform)| {
+                    let viewport_size = camera.logical_viewport_size()?;
+                    camera.viewport_to_world_2d(transform, uv * viewport_size).ok()
+                });
+                if let Some(world_pose) = world_pose {
+                    let index = viewer_state.placed_lights.len();
+                    let light = commands
+                        .spawn((
+                            Name::new(format!("placed_light_{}", index)),
+                            viewer_state.light_template,
+                            Transform::from_translation(world_pose.extend(0.0)),
+                            Visibility::default(),
+                        ))
+                        .id();
+                    info!("Placed light at {:?}", world_pose);
+                    viewer_state.placed_lights.push(light);
                 }
             }
 
             ui.separator();
 
+            let convergence_text = if gi_convergence.is_converged() {
+                "GI converged".to_string()
+            } else {
+                format!(
+                    "GI converging… {}/{}",
+                    gi_convergence.frames, gi_convergence.frames_to_converge
+                )
+            };
+            ui.add(
+                egui::ProgressBar::new(gi_convergence.progress())
+                    .text(convergence_text)
+                    .desired_width(240.0),
+            );
+
+            // GPU timings of the GI compute passes.
+            ui.collapsing("GI Timings", |ui| {
+                if gi_diagnostics.total_ms().is_none() {
+                    ui.label("GPU timings unavailable");
+                    ui.label("(add RenderDiagnosticsPlugin, requires timestamp query support)");
+                    return;
+                }
+                egui::Grid::new("gi_timings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for pass in GiPass::ALL {
+                            ui.label(pass.display_name());
+                            match gi_diagnostics.pass(pass) {
+                                Some(ms) => ui.label(format!("{:.3} ms", ms)),
+                                None => ui.label("n/a"),
+                            };
+                            ui.end_row();
+                        }
+                        ui.label("Total");
+                        ui.label(format!("{:.3} ms", gi_diagnostics.total_ms().unwrap_or_default()));
+                        ui.end_row();
+                    });
+            });
+
+            ui.collapsing("Light Placement", |ui| {
+                light_placement_ui(ui, &mut viewer_state, &query_transforms);
+            });
+
+            ui.collapsing("Light Falloff", |ui| {
+                light_falloff_ui(
+                    ui,
+                    &mut viewer_state.selected_light,
+                    &mut query_lights,
+                    settings.light_pass_params.light_cull_epsilon,
+                );
+            });
+
             // Instructions
             ui.collapsing("Instructions", |ui| {
                 ui.label("• Select a camera to view its render target");
@@ -258,6 +492,7 @@ fn camera_viewer_ui_system(
                 ui.label("• Floor, Walls, and Objects are separate layers");
                 ui.label("• Post Processing combines all layers with lighting");
                 ui.label("• Combined view shows all layers side by side");
+                ui.label("• Enable light placement and click a layer to add lights");
             });
 
             // Close button
@@ -267,21 +502,180 @@ fn camera_viewer_ui_system(
         });
 }
 
+/// Template of lights placed by clicking the render target, and the positions
+/// of lights placed so far.
+fn light_placement_ui(
+    ui: &mut egui::Ui,
+    viewer_state: &mut CameraViewerState,
+    query_transforms: &Query<&GlobalTransform>,
+)
+{
+    ui.checkbox(&mut viewer_state.place_lights, "Click the render target to place lights");
+
+    let template = &mut viewer_state.light_template;
+    let mut color = template.color.to_srgba().to_f32_array_no_alpha();
+    egui::Grid::new("light_placement_grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Color");
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                template.color = Color::srgb(color[0], color[1], color[2]);
+            }
+            ui.end_row();
+            ui.label("Intensity");
+            ui.add(egui::DragValue::new(&mut template.intensity).speed(0.01));
+            ui.end_row();
+        });
+
+    // Despawned lights drop out of the list.
+    viewer_state
+        .placed_lights
+        .retain(|entity| query_transforms.contains(*entity));
+    for entity in &viewer_state.placed_lights {
+        if let Ok(transform) = query_transforms.get(*entity) {
+            let pose = transform.translation();
+            ui.label(format!("{}: ({:.1}, {:.1})", entity, pose.x, pose.y));
+        }
+    }
+}
+
+/// Plots the selected light's intensity over distance and marks the radius at
+/// which it is culled.
+fn light_falloff_ui(
+    ui: &mut egui::Ui,
+    selected_light: &mut Option<Entity>,
+    query_lights: &mut Query<(Entity, Option<&Name>, &mut OmniLightSource2D)>,
+    epsilon: f32,
+)
+{
+    const SAMPLES: usize = 64;
+
+    let light_label = |entity: Entity, name: Option<&Name>| {
+        name.map_or_else(|| format!("{}", entity), |name| name.to_string())
+    };
+
+    if !selected_light.is_some_and(|entity| query_lights.contains(entity)) {
+        *selected_light = query_lights.iter().next().map(|(entity, ..)| entity);
+    }
+    let Some(selected) = *selected_light else {
+        ui.label("No OmniLightSource2D in the scene");
+        return;
+    };
+
+    let selected_text = query_lights
+        .get(selected)
+        .map(|(entity, name, _)| light_label(entity, name))
+        .unwrap_or_default();
+    egui::ComboBox::from_id_salt("light_falloff_light")
+        .selected_text(selected_text)
+        .width(250.0)
+        .show_ui(ui, |ui| {
+            for (entity, name, _) in query_lights.iter() {
+                if ui
+                    .selectable_label(entity == selected, light_label(entity, name))
+                    .clicked()
+                {
+                    *selected_light = Some(entity);
+                }
+            }
+        });
+
+    let Ok((_, _, mut light)) = query_lights.get_mut(selected) else {
+        return;
+    };
+
+    // Edit a copy, so the light is only marked changed when a value changes.
+    let mut edited = *light;
+    let mut changed = false;
+    egui::Grid::new("light_falloff_grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Enabled");
+            changed |= ui.checkbox(&mut edited.enabled, "").changed();
+            ui.end_row();
+            ui.label("Intensity");
+            changed |= ui.add(egui::DragValue::new(&mut edited.intensity).speed(0.01)).changed();
+            ui.end_row();
+            ui.label("Falloff (a / (b + c·d²))");
+            ui.horizontal(|ui| {
+                changed |= ui.add(egui::DragValue::new(&mut edited.falloff.x).speed(0.01)).changed();
+                changed |= ui.add(egui::DragValue::new(&mut edited.falloff.y).speed(0.01)).changed();
+                changed |= ui.add(egui::DragValue::new(&mut edited.falloff.z).speed(0.0001)).changed();
+            });
+            ui.end_row();
+        });
+    if changed {
+        *light = edited;
+    }
+
+    let radius = edited.effective_radius(epsilon);
+    let max_distance = if radius.is_finite() {
+        (radius * 1.25).max(1.0)
+    } else {
+        1000.0
+    };
+    let max_intensity = edited.intensity_at(0.0).max(epsilon);
+
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(ui.available_width(), 160.0), egui::Sense::hover());
+    let rect = response.rect;
+    let to_screen = |distance: f32, intensity: f32| {
+        egui::pos2(
+            rect.left() + distance / max_distance * rect.width(),
+            rect.bottom() - (intensity / max_intensity).clamp(0.0, 1.0) * rect.height(),
+        )
+    };
+
+    painter.rect_stroke(
+        rect,
+        0.0,
+        egui::Stroke::new(1.0, egui::Color32::GRAY),
+        egui::StrokeKind::Inside,
+    );
+    let curve = (0..=SAMPLES)
+        .map(|i| {
+            let distance = max_distance * i as f32 / SAMPLES as f32;
+            to_screen(distance, edited.intensity_at(distance))
+        })
+        .collect::<Vec<_>>();
+    painter.add(egui::Shape::line(
+        curve,
+        egui::Stroke::new(2.0, egui::Color32::YELLOW),
+    ));
+    if radius.is_finite() {
+        painter.vline(
+            to_screen(radius, 0.0).x,
+            rect.y_range(),
+            egui::Stroke::new(1.0, egui::Color32::RED),
+        );
+    }
+
+    ui.label(format!("Peak: {:.3}", edited.intensity_at(0.0)));
+    if radius.is_finite() {
+        ui.label(format!("Cutoff radius: {:.1} (below {})", radius, epsilon));
+    } else {
+        ui.label("Cutoff radius: infinite (no quadratic falloff)");
+    }
+}
+
 fn display_render_target(
     ui: &mut egui::Ui,
     target: &Option<Handle<Image>>,
     images: &Assets<Image>,
     label: &str,
     texture_id: Option<egui::TextureId>,
-)
+) -> Option<Vec2>
 {
     let Some(target) = target else {
         error!("display_render_target called with no target");
-        return;
+        return None;
     };
 
     ui.label(label);
 
+    // Position of a click on the image, in uv of the render target.
+    let mut clicked_uv = None;
+
     if let Some(image) = images.get(target) {
         let size = image.size();
 
@@ -295,10 +689,19 @@ fn display_render_target(
         match texture_id {
             Some(texture_id) => {
                 // Successfully got egui texture ID, display the actual image
-                ui.image(egui::load::SizedTexture::new(
-                    texture_id,
-                    egui::Vec2::new(display_width, display_height),
-                ));
+                let response = ui.add(
+                    egui::Image::new(egui::load::SizedTexture::new(
+                        texture_id,
+                        egui::Vec2::new(display_width, display_height),
+                    ))
+                    .sense(egui::Sense::click()),
+                );
+                if response.clicked() {
+                    clicked_uv = response.interact_pointer_pos().map(|pos| {
+                        let uv = (pos - response.rect.min) / response.rect.size();
+                        Vec2::new(uv.x, uv.y)
+                    });
+                }
 
                 // Show some debug info
                 if let Some(data) = &image.data {
@@ -358,6 +761,8 @@ fn display_render_target(
     } else {
         ui.label("Render target not available");
     }
+
+    clicked_uv
 }
 
 fn display_render_target_in_grid(
@@ -1,13 +1,19 @@
-use bevy::camera::visibility::RenderLayers;
+use bevy::camera::visibility::{Layer, RenderLayers};
+use bevy::camera::{ImageRenderTarget, RenderTarget};
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::image::ImageSampler;
 use bevy::mesh::MeshVertexBufferLayoutRef;
 use bevy::pbr::{MAX_CASCADES_PER_LIGHT, MAX_DIRECTIONAL_LIGHTS};
 use bevy::post_process::bloom::Bloom;
 use bevy::prelude::*;
 use bevy::reflect::TypePath;
+use bevy::render::extract_resource::ExtractResource;
+use bevy::render::view::Hdr;
 use bevy::render::render_resource::{
     AsBindGroup,
     Extent3d,
     RenderPipelineDescriptor,
+    ShaderType,
     SpecializedMeshPipelineError,
     TextureDescriptor,
     TextureDimension,
@@ -15,18 +21,40 @@ use bevy::render::render_resource::{
     TextureUsages,
 };
 use bevy::shader::{ShaderDefVal, ShaderRef};
-use bevy::sprite_render::{Material2d, Material2dKey};
+use bevy::sprite_render::{AlphaMode2d, Material2d, Material2dKey};
 
-use crate::gi::constants::{POST_PROCESSING_MATERIAL, POST_PROCESSING_RECT};
+use crate::gi::bake::LightBake;
 use crate::gi::pipeline::GiTargetsWrapper;
-use crate::gi::render_layer::CAMERA_LAYER_POST_PROCESSING;
-use crate::gi::resource::ComputedTargetSizes;
+use crate::gi::pipeline_assets::shader_path;
+use crate::gi::render_layer::MAX_EXTRA_LIT_LAYERS;
+use crate::gi::resource::{
+    BevyMagicLight2DSettings,
+    ComputedTargetSizes,
+    GiWarmup,
+    OccluderOutline,
+    Vignette,
+};
+use crate::gi::types::Unlit;
+use crate::ObjectsCamera;
 
 #[derive(Component)]
 pub struct PostProcessingQuad;
 
+#[derive(Component)]
+pub struct PostProcessingCamera;
+
+/// Mesh and material of the post-processing quad, created by
+/// [`setup_post_processing_camera`]. Regular asset handles rather than fixed
+/// ids, so they can't collide with assets of other plugins.
+#[derive(Resource, Clone)]
+pub struct PostProcessingHandles
+{
+    pub rect:     Handle<Mesh>,
+    pub material: Handle<PostProcessingMaterial>,
+}
+
 #[rustfmt::skip]
-#[derive(AsBindGroup, Clone, TypePath, Asset)]
+#[derive(AsBindGroup, Clone, PartialEq, TypePath, Asset)]
 pub struct PostProcessingMaterial {
     #[texture(0)]
     #[sampler(1)]
@@ -43,11 +71,146 @@ pub struct PostProcessingMaterial {
     #[texture(6)]
     #[sampler(7)]
     irradiance_image:  Handle<Image>,
+
+    #[texture(8)]
+    #[sampler(9)]
+    sdf_image:         Handle<Image>,
+
+    #[texture(10)]
+    #[sampler(11)]
+    probes_image:      Handle<Image>,
+
+    #[uniform(12)]
+    debug_view:        u32,
+
+    #[texture(13)]
+    #[sampler(14)]
+    extra_image_0:     Handle<Image>,
+
+    #[texture(15)]
+    #[sampler(16)]
+    extra_image_1:     Handle<Image>,
+
+    #[texture(17)]
+    #[sampler(18)]
+    extra_image_2:     Handle<Image>,
+
+    #[texture(19)]
+    #[sampler(20)]
+    extra_image_3:     Handle<Image>,
+
+    #[uniform(21)]
+    extra_layer_count: u32,
+
+    #[uniform(22)]
+    irradiance_filter: u32,
+
+    #[uniform(23)]
+    layer_alpha:       u32,
+
+    #[uniform(24)]
+    gi_camera_margin:  f32,
+
+    #[uniform(25)]
+    contrast:          f32,
+
+    #[uniform(26)]
+    saturation:        f32,
+
+    #[uniform(27)]
+    exposure:          f32,
+
+    #[uniform(28)]
+    vignette:          GpuVignette,
+
+    #[uniform(29)]
+    reflection_strength: f32,
+
+    #[uniform(30)]
+    color_bleed:         f32,
+
+    /// Indirect multipliers of the floor, walls and objects layers.
+    #[uniform(31)]
+    layer_indirect:      Vec3,
+
+    #[uniform(32)]
+    occluder_outline:    GpuOccluderOutline,
+
+    #[texture(33)]
+    #[sampler(34)]
+    unlit_image:         Handle<Image>,
+
+    /// Irradiance faded out after the GI targets were rebuilt, see
+    /// [`GiTargetTransition`].
+    #[texture(35)]
+    #[sampler(36)]
+    previous_irradiance_image: Handle<Image>,
+
+    #[uniform(37)]
+    target_transition:   f32,
+
+    #[uniform(38)]
+    transparent_background: u32,
+
+    alpha_mode:          AlphaMode2d,
+}
+
+#[rustfmt::skip]
+#[derive(ShaderType, Clone, Copy, Default, PartialEq)]
+struct GpuVignette {
+    color:    Vec3,
+    radius:   f32,
+    softness: f32,
+    enabled:  u32,
+}
+
+#[rustfmt::skip]
+#[derive(ShaderType, Clone, Copy, Default, PartialEq)]
+struct GpuOccluderOutline {
+    color:     Vec3,
+    thickness: f32,
+    enabled:   u32,
+}
+
+impl From<Option<OccluderOutline>> for GpuOccluderOutline
+{
+    fn from(outline: Option<OccluderOutline>) -> Self
+    {
+        let Some(outline) = outline else {
+            return Self::default();
+        };
+        Self {
+            color:     outline.color.to_srgba().to_vec3(),
+            thickness: outline.thickness.max(1e-4),
+            enabled:   1,
+        }
+    }
+}
+
+impl From<Option<Vignette>> for GpuVignette
+{
+    fn from(vignette: Option<Vignette>) -> Self
+    {
+        let Some(vignette) = vignette else {
+            return Self::default();
+        };
+        Self {
+            color:    vignette.color.to_srgba().to_vec3(),
+            radius:   vignette.radius.max(0.0),
+            softness: vignette.softness.max(1e-4),
+            enabled:  1,
+        }
+    }
 }
 
 impl PostProcessingMaterial
 {
-    pub fn create(camera_targets: &CameraTargets, gi_targets_wrapper: &GiTargetsWrapper) -> Self
+    pub fn create(
+        camera_targets: &CameraTargets,
+        gi_targets_wrapper: &GiTargetsWrapper,
+        settings: &BevyMagicLight2DSettings,
+        light_bake: &LightBake,
+    ) -> Self
     {
         // Log texture handle information for debugging
         log::debug!("Creating PostProcessingMaterial with texture handles:");
@@ -61,6 +224,25 @@ impl PostProcessingMaterial
             log::error!("GI targets not initialized when creating PostProcessingMaterial!");
         }
 
+        let extra_image = |index: usize| {
+            camera_targets
+                .extra_targets
+                .get(index)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let gi_targets = gi_targets_wrapper
+            .targets
+            .as_ref()
+            .expect("GI targets must be initialized");
+
+        let irradiance_image = match light_bake.image() {
+            Some(baked) if light_bake.is_baked() => baked.clone(),
+            _ if settings.enable_filter => gi_targets.ss_filter_target.clone(),
+            _ => gi_targets.ss_blend_target.clone(),
+        };
+
         Self {
             floor_image:      camera_targets.floor_target.clone()
                 .expect("Floor target must be initialized"),
@@ -68,85 +250,277 @@ impl PostProcessingMaterial
                 .expect("Walls target must be initialized"),
             objects_image:    camera_targets.objects_target.clone()
                 .expect("Objects target must be initialized"),
-            irradiance_image: gi_targets_wrapper
-                .targets
-                .as_ref()
-                .expect("GI targets must be initialized")
-                .ss_filter_target
-                .clone(),
+            irradiance_image: irradiance_image.clone(),
+            sdf_image:        gi_targets.sdf_target.clone(),
+            probes_image:     gi_targets.ss_blend_target.clone(),
+            debug_view:       settings.debug_output as u32,
+
+            // Unused slots are bound to the default image and skipped by
+            // the shader.
+            extra_image_0:     extra_image(0),
+            extra_image_1:     extra_image(1),
+            extra_image_2:     extra_image(2),
+            extra_image_3:     extra_image(3),
+            extra_layer_count: camera_targets.extra_targets.len() as u32,
+
+            irradiance_filter: settings.effective_irradiance_filter() as u32,
+            layer_alpha:       settings.layer_alpha as u32,
+            gi_camera_margin:  settings.gi_camera_margin.max(0.0),
+            contrast:          settings.contrast.max(0.0),
+            saturation:        settings.saturation.max(0.0),
+            exposure:          settings.exposure.max(0.0),
+            vignette:          settings.vignette.into(),
+
+            reflection_strength: settings.reflection_strength.clamp(0.0, 1.0),
+            color_bleed:         settings.color_bleed.clamp(0.0, 1.0),
+            layer_indirect:      settings.layer_indirect.into(),
+            occluder_outline:    settings.occluder_outline.into(),
+            unlit_image:         camera_targets.unlit_target.clone()
+                .expect("Unlit target must be initialized"),
+            target_transition:   0.0,
+            transparent_background: settings.transparent_background as u32,
+            alpha_mode:          composite_alpha_mode(settings),
+
+            previous_irradiance_image: irradiance_image,
+        }
+    }
+
+    /// Points the material at the current targets and settings. The exposure
+    /// and the target transition are kept, their systems update them.
+    pub(crate) fn update(
+        &mut self,
+        camera_targets: &CameraTargets,
+        gi_targets_wrapper: &GiTargetsWrapper,
+        settings: &BevyMagicLight2DSettings,
+        light_bake: &LightBake,
+    )
+    {
+        *self = Self {
+            exposure:                  self.exposure,
+            target_transition:         self.target_transition,
+            previous_irradiance_image: self.previous_irradiance_image.clone(),
+            ..Self::create(camera_targets, gi_targets_wrapper, settings, light_bake)
+        };
+    }
+
+    pub(crate) fn exposure(&self) -> f32
+    {
+        self.exposure
+    }
+
+    pub(crate) fn set_exposure(&mut self, exposure: f32)
+    {
+        self.exposure = exposure;
+    }
+
+    pub(crate) fn irradiance_image(&self) -> &Handle<Image>
+    {
+        &self.irradiance_image
+    }
+
+    fn set_target_transition(&mut self, previous_irradiance: Option<&Handle<Image>>, weight: f32)
+    {
+        self.previous_irradiance_image = previous_irradiance.unwrap_or(&self.irradiance_image).clone();
+        self.target_transition = weight;
+    }
+}
+
+/// Updates the post-processing material in place, so its handle stays the
+/// same. Only creates it if the asset is missing, and leaves it untouched if
+/// nothing changed, e.g. while a light bake is counting frames.
+pub(crate) fn update_post_processing_material_asset(
+    materials: &mut Assets<PostProcessingMaterial>,
+    post_handles: &PostProcessingHandles,
+    camera_targets: &CameraTargets,
+    gi_targets_wrapper: &GiTargetsWrapper,
+    settings: &BevyMagicLight2DSettings,
+    light_bake: &LightBake,
+)
+{
+    let Some(material) = materials.get(post_handles.material.id()) else {
+        let material = PostProcessingMaterial::create(camera_targets, gi_targets_wrapper, settings, light_bake);
+        let _ = materials.insert(post_handles.material.id(), material);
+        return;
+    };
+
+    let mut updated = material.clone();
+    updated.update(camera_targets, gi_targets_wrapper, settings, light_bake);
+    if updated != *material {
+        if let Some(material) = materials.get_mut(post_handles.material.id()) {
+            *material = updated;
         }
     }
 }
 
+/// Cross-fade from the irradiance shown before the GI targets were rebuilt
+/// for new [`TargetScalingParams`](crate::gi::resource::TargetScalingParams).
+///
+/// The new targets start out empty and take a few frames to converge, the
+/// previous irradiance is kept and faded out over that time instead of
+/// flashing black.
 #[derive(Resource, Default)]
+pub struct GiTargetTransition
+{
+    previous_irradiance: Option<Handle<Image>>,
+    frames:              u32,
+    remaining:           u32,
+}
+
+impl GiTargetTransition
+{
+    pub fn is_active(&self) -> bool
+    {
+        self.remaining > 0
+    }
+
+    pub(crate) fn start(&mut self, previous_irradiance: Handle<Image>, frames: u32)
+    {
+        let frames = frames.max(1);
+        *self = Self {
+            previous_irradiance: Some(previous_irradiance),
+            frames,
+            remaining: frames,
+        };
+    }
+
+    /// Weight of the previous irradiance, `1.0` right after the rebuild.
+    fn weight(&self) -> f32
+    {
+        self.remaining as f32 / self.frames.max(1) as f32
+    }
+}
+
+pub(crate) fn system_update_target_transition(
+    mut transition: ResMut<GiTargetTransition>,
+    mut materials: ResMut<Assets<PostProcessingMaterial>>,
+    post_handles: Res<PostProcessingHandles>,
+)
+{
+    let weight = transition.weight();
+    let previous = transition.previous_irradiance.as_ref().filter(|_| transition.is_active());
+
+    // Compared like the exposure, so the material is only touched when the
+    // transition moves.
+    let outdated = materials.get(post_handles.material.id()).is_some_and(|material| {
+        material.target_transition != weight
+            || material.previous_irradiance_image != *previous.unwrap_or(&material.irradiance_image)
+    });
+    if outdated {
+        if let Some(material) = materials.get_mut(post_handles.material.id()) {
+            material.set_target_transition(previous, weight);
+        }
+    }
+
+    if transition.is_active() {
+        transition.remaining -= 1;
+        if !transition.is_active() {
+            transition.previous_irradiance = None;
+        }
+    }
+}
+
+#[derive(Resource, Default, Clone, ExtractResource)]
 pub struct CameraTargets
 {
-    pub floor_target:   Option<Handle<Image>>,
-    pub walls_target:   Option<Handle<Image>>,
-    pub objects_target: Option<Handle<Image>>,
+    pub floor_target:     Option<Handle<Image>>,
+    pub walls_target:     Option<Handle<Image>>,
+    pub objects_target:   Option<Handle<Image>>,
+    /// Image the final composite is rendered into, `None` when rendering
+    /// to the window.
+    pub composite_target: Option<Handle<Image>>,
+    /// Targets of [`BevyMagicLight2DSettings::extra_lit_layers`], in the
+    /// same order.
+    pub extra_targets:    Vec<Handle<Image>>,
+    /// Target of [`Unlit`] sprites, composited without lighting.
+    pub unlit_target:     Option<Handle<Image>>,
+    /// Composite at the primary target size, presented to the window by
+    /// the [full resolution output](BevyMagicLight2DSettings::full_resolution_output).
+    pub upscale_source:   Option<Handle<Image>>,
+    /// Whether the layer targets are `Rgba16Float`, see
+    /// [`BevyMagicLight2DSettings::hdr_layers`].
+    pub hdr:              bool,
 }
 
 impl CameraTargets
 {
-    pub fn update_handles(&mut self, images: &mut Assets<Image>, sizes: &ComputedTargetSizes)
+    /// Render target for the floor camera.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called before the targets are created in `PreStartup`.
+    pub fn floor_render_target(&self) -> ImageRenderTarget
     {
+        self.floor_target
+            .clone()
+            .expect("Floor target must be initialized")
+            .into()
+    }
+
+    /// Render target for the walls camera, see [`Self::floor_render_target`].
+    pub fn walls_render_target(&self) -> ImageRenderTarget
+    {
+        self.walls_target
+            .clone()
+            .expect("Walls target must be initialized")
+            .into()
+    }
+
+    /// Render target for the objects camera, see
+    /// [`Self::floor_render_target`].
+    pub fn objects_render_target(&self) -> ImageRenderTarget
+    {
+        self.objects_target
+            .clone()
+            .expect("Objects target must be initialized")
+            .into()
+    }
+
+    /// Target of a layer registered in
+    /// [`BevyMagicLight2DSettings::extra_lit_layers`].
+    pub fn extra_layer_target(
+        &self,
+        settings: &BevyMagicLight2DSettings,
+        layer: Layer,
+    ) -> Option<Handle<Image>>
+    {
+        let index = settings.extra_lit_layers.iter().position(|l| *l == layer)?;
+        self.extra_targets.get(index).cloned()
+    }
+
+    /// Whether `image` is one of the floor, walls, objects, unlit or extra
+    /// layer targets.
+    pub fn is_layer_target(&self, image: &Handle<Image>) -> bool
+    {
+        [&self.floor_target, &self.walls_target, &self.objects_target, &self.unlit_target]
+            .into_iter()
+            .flatten()
+            .chain(self.extra_targets.iter())
+            .any(|target| target.id() == image.id())
+    }
+
+    pub fn update_handles(
+        &mut self,
+        images: &mut Assets<Image>,
+        sizes: &ComputedTargetSizes,
+        extra_layer_count: usize,
+        hdr: bool,
+    )
+    {
+        self.hdr = hdr;
         let target_size = Extent3d {
             width: sizes.primary_target_usize.x,
             height: sizes.primary_target_usize.y,
             ..default()
         };
 
-        let mut floor_image = Image {
-            texture_descriptor: TextureDescriptor {
-                label:           Some("target_floor"),
-                size:            target_size,
-                dimension:       TextureDimension::D2,
-                format:          TextureFormat::bevy_default(),
-                mip_level_count: 1,
-                sample_count:    1,
-                usage:           TextureUsages::TEXTURE_BINDING
-                    | TextureUsages::COPY_DST
-                    | TextureUsages::RENDER_ATTACHMENT,
-                view_formats:    &[],
-            },
-            ..default()
-        };
-        let mut walls_image = Image {
-            texture_descriptor: TextureDescriptor {
-                label:           Some("target_walls"),
-                size:            target_size,
-                dimension:       TextureDimension::D2,
-                format:          TextureFormat::bevy_default(),
-                mip_level_count: 1,
-                sample_count:    1,
-                usage:           TextureUsages::TEXTURE_BINDING
-                    | TextureUsages::COPY_DST
-                    | TextureUsages::RENDER_ATTACHMENT,
-                view_formats:    &[],
-            },
-            ..default()
-        };
-
-        let mut objects_image = Image {
-            texture_descriptor: TextureDescriptor {
-                label:           Some("target_objects"),
-                size:            target_size,
-                dimension:       TextureDimension::D2,
-                format:          TextureFormat::bevy_default(),
-                mip_level_count: 1,
-                sample_count:    1,
-                usage:           TextureUsages::TEXTURE_BINDING
-                    | TextureUsages::COPY_DST
-                    | TextureUsages::RENDER_ATTACHMENT,
-                view_formats:    &[],
-            },
-            ..default()
-        };
-
-        // Fill images data with zeroes.
-        floor_image.resize(target_size);
-        walls_image.resize(target_size);
-        objects_image.resize(target_size);
+        let floor_image = create_layer_image("target_floor", target_size, hdr);
+        let walls_image = create_layer_image("target_walls", target_size, hdr);
+        let objects_image = create_layer_image("target_objects", target_size, hdr);
+        let unlit_image = create_layer_image("target_unlit", target_size, hdr);
+        // Sampled bilinearly by the upscale pass, nearest filtering loads
+        // texels directly.
+        let mut upscale_image = create_layer_image("target_upscale_source", target_size, false);
+        upscale_image.sampler = ImageSampler::linear();
 
         if let Some(ref floor_target) = self.floor_target {
             images
@@ -169,6 +543,41 @@ impl CameraTargets
         } else {
             self.objects_target = Some(images.add(objects_image));
         }
+        if let Some(ref unlit_target) = self.unlit_target {
+            images
+                .insert(unlit_target, unlit_image)
+                .expect("unlit image handle updating should work everytime");
+        } else {
+            self.unlit_target = Some(images.add(unlit_image));
+        }
+        if let Some(ref upscale_source) = self.upscale_source {
+            images
+                .insert(upscale_source, upscale_image)
+                .expect("upscale image handle updating should work everytime");
+        } else {
+            self.upscale_source = Some(images.add(upscale_image));
+        }
+
+        let extra_layer_count = if extra_layer_count > MAX_EXTRA_LIT_LAYERS {
+            log::warn!(
+                "{} extra lit layers requested, only the first {} are composited",
+                extra_layer_count,
+                MAX_EXTRA_LIT_LAYERS,
+            );
+            MAX_EXTRA_LIT_LAYERS
+        } else {
+            extra_layer_count
+        };
+        self.extra_targets.truncate(extra_layer_count);
+        for extra_target in self.extra_targets.iter() {
+            images
+                .insert(extra_target, create_layer_image("target_extra_layer", target_size, hdr))
+                .expect("extra layer image handle updating should work everytime");
+        }
+        while self.extra_targets.len() < extra_layer_count {
+            self.extra_targets
+                .push(images.add(create_layer_image("target_extra_layer", target_size, hdr)));
+        }
         
         // Validate that all targets are properly initialized
         if let (Some(floor), Some(walls), Some(objects)) = (&self.floor_target, &self.walls_target, &self.objects_target) {
@@ -179,11 +588,46 @@ impl CameraTargets
     }
 }
 
+/// Layer targets are `Rgba16Float` in HDR so values above `1.0` reach the
+/// composite.
+pub(crate) fn create_layer_image(label: &'static str, size: Extent3d, hdr: bool) -> Image
+{
+    let format = if hdr {
+        TextureFormat::Rgba16Float
+    } else {
+        TextureFormat::bevy_default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label:           Some(label),
+            size,
+            dimension:       TextureDimension::D2,
+            format,
+            mip_level_count: 1,
+            sample_count:    1,
+            usage:           TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats:    &[],
+        },
+        ..default()
+    };
+
+    // Fill image data with zeroes.
+    image.resize(size);
+    image
+}
+
 impl Material2d for PostProcessingMaterial
 {
     fn fragment_shader() -> ShaderRef
     {
-        "embedded://bevy_magic_light_2d/gi/shaders/gi_post_processing.wgsl".into()
+        ShaderRef::Path(shader_path("gi_post_processing.wgsl").into())
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d
+    {
+        self.alpha_mode
     }
 
     fn specialize(
@@ -219,6 +663,8 @@ pub fn setup_post_processing_camera(
 
     target_sizes:                 Res<ComputedTargetSizes>,
     gi_targets_wrapper:           Res<GiTargetsWrapper>,
+    settings:                     Res<BevyMagicLight2DSettings>,
+    light_bake:                   Res<LightBake>,
 ) {
 
     let quad =  Mesh::from(bevy::math::primitives::Rectangle::new(
@@ -226,42 +672,507 @@ pub fn setup_post_processing_camera(
         target_sizes.primary_target_size.y,
     ));
 
-    let _ = meshes.insert(POST_PROCESSING_RECT.id(), quad);
+    let rect = meshes.add(quad);
 
-    camera_targets.update_handles(&mut images, &target_sizes);
+    camera_targets.update_handles(
+        &mut images,
+        &target_sizes,
+        settings.extra_lit_layers.len(),
+        settings.hdr_layers,
+    );
 
-    let material = PostProcessingMaterial::create(&camera_targets, &gi_targets_wrapper);
-    let _ = materials.insert(POST_PROCESSING_MATERIAL.id(), material);
+    let material = PostProcessingMaterial::create(
+        &camera_targets,
+        &gi_targets_wrapper,
+        &settings,
+        &light_bake,
+    );
+    let handles = PostProcessingHandles {
+        rect,
+        material: materials.add(material),
+    };
 
     // This specifies the layer used for the post processing camera, which
     // will be attached to the post processing camera and 2d quad.
-    let layer = RenderLayers::layer(CAMERA_LAYER_POST_PROCESSING);
+    let layer = settings.render_layers.post_processing_layers();
 
     commands.spawn((
         PostProcessingQuad,
-        Mesh2d(POST_PROCESSING_RECT.clone()),
-        MeshMaterial2d(POST_PROCESSING_MATERIAL.clone()),
+        Mesh2d(handles.rect.clone()),
+        MeshMaterial2d(handles.material.clone()),
         Transform::from_translation(Vec3::new(0.0, 0.0, 1.5)),
         layer.clone(),
     ));
 
-    commands.spawn((
+    let camera_params = &settings.post_processing_camera_params;
+    camera_targets.composite_target = settings.composite_target.clone();
+
+    let mut camera = commands.spawn((
         Name::new("post_processing_camera"),
+        PostProcessingCamera,
         Camera2d,
         Camera{
-            order: 1,
-            ..default()
-        },
-        Bloom {
-            intensity: 0.1,
+            order:       camera_params.order,
+            clear_color: composite_clear_color(&settings, &camera_targets),
+            target:      composite_render_target(&settings, &camera_targets),
             ..default()
         },
+        default_bloom(),
+        settings.msaa,
         layer
-    ))
-    .insert((
+    ));
+    if !camera_params.hdr {
+        camera.remove::<Hdr>();
+    }
+    if !post_processing_bloom(&settings) {
+        camera.remove::<Bloom>();
+    }
+    camera.insert((
         PostProcessingQuad,
-        Mesh2d(POST_PROCESSING_RECT.clone()),
-        MeshMaterial2d(POST_PROCESSING_MATERIAL.clone()),
+        Mesh2d(handles.rect.clone()),
+        MeshMaterial2d(handles.material.clone()),
         Transform::from_translation(Vec3::new(0.0, 0.0, 1.5)),
     ));
+
+    commands.insert_resource(handles);
+}
+
+/// Bloom needs an HDR camera and is off in pixel-art mode.
+fn post_processing_bloom(settings: &BevyMagicLight2DSettings) -> bool
+{
+    settings.post_processing_camera_params.hdr && !settings.pixel_art_mode
+}
+
+fn composite_render_target(settings: &BevyMagicLight2DSettings, camera_targets: &CameraTargets) -> RenderTarget
+{
+    match (&settings.composite_target, upscale_source(settings, camera_targets)) {
+        (Some(image), _) => RenderTarget::Image(image.clone().into()),
+        (None, Some(image)) => RenderTarget::Image(image.into()),
+        (None, None) => RenderTarget::default(),
+    }
+}
+
+fn default_bloom() -> Bloom
+{
+    Bloom {
+        intensity: 0.1,
+        ..default()
+    }
+}
+
+/// With a transparent background the composite is blended over what the
+/// target already shows.
+fn composite_alpha_mode(settings: &BevyMagicLight2DSettings) -> AlphaMode2d
+{
+    if settings.transparent_background {
+        AlphaMode2d::Blend
+    } else {
+        AlphaMode2d::Opaque
+    }
+}
+
+/// The upscale camera presents with the configured clear color, the
+/// upscale source itself is cleared to transparent so it only holds the
+/// composite.
+fn composite_clear_color(settings: &BevyMagicLight2DSettings, camera_targets: &CameraTargets) -> ClearColorConfig
+{
+    if settings.transparent_background && upscale_source(settings, camera_targets).is_some() {
+        ClearColorConfig::Custom(Color::NONE)
+    } else {
+        settings.post_processing_camera_params.clear_color
+    }
+}
+
+/// Image the composite is rendered into for the full resolution output,
+/// `None` when it is disabled.
+pub(crate) fn upscale_source(settings: &BevyMagicLight2DSettings, camera_targets: &CameraTargets) -> Option<Handle<Image>>
+{
+    settings
+        .full_resolution_output
+        .filter(|_| settings.composite_target.is_none())
+        .and(camera_targets.upscale_source.clone())
+}
+
+/// Applies [`PostProcessingCameraParams`](crate::gi::resource::PostProcessingCameraParams)
+/// and the composite target to the post-processing camera whenever the
+/// settings change.
+#[rustfmt::skip]
+#[allow(clippy::type_complexity)]
+pub fn update_post_processing_camera(
+    mut commands:          Commands,
+    mut query_post_camera: Query<(Entity, &mut Camera, Has<Hdr>, Has<Bloom>), With<PostProcessingCamera>>,
+    mut query_post_layers: Query<&mut RenderLayers, Or<(With<PostProcessingQuad>, With<PostProcessingCamera>)>>,
+    mut camera_targets:    ResMut<CameraTargets>,
+        settings:          Res<BevyMagicLight2DSettings>,
+    // Bloom is only restored after it was removed here, otherwise bloom the
+    // app removed itself stays off.
+    mut bloom_removed:     Local<bool>,
+) {
+    let layers = settings.render_layers.post_processing_layers();
+    for mut render_layers in query_post_layers.iter_mut() {
+        if *render_layers != layers {
+            *render_layers = layers.clone();
+        }
+    }
+
+    if camera_targets.composite_target != settings.composite_target {
+        camera_targets.composite_target = settings.composite_target.clone();
+    }
+
+    let camera_params = &settings.post_processing_camera_params;
+    for (entity, mut camera, has_hdr, has_bloom) in query_post_camera.iter_mut() {
+        camera.target = composite_render_target(&settings, &camera_targets);
+        if camera.order != camera_params.order {
+            camera.order = camera_params.order;
+        }
+        camera.clear_color = composite_clear_color(&settings, &camera_targets);
+
+        if camera_params.hdr && !has_hdr {
+            commands.entity(entity).insert(Hdr);
+        } else if !camera_params.hdr && has_hdr {
+            commands.entity(entity).remove::<Hdr>();
+        }
+
+        if !post_processing_bloom(&settings) {
+            if has_bloom {
+                commands.entity(entity).remove::<Bloom>();
+            }
+            *bloom_removed = true;
+        } else if *bloom_removed {
+            commands.entity(entity).insert(default_bloom());
+            *bloom_removed = false;
+        }
+    }
+}
+
+/// Keeps `Hdr` on the cameras rendering into layer targets in sync with
+/// [`BevyMagicLight2DSettings::hdr_layers`], so their output isn't clamped
+/// before it reaches the `Rgba16Float` targets.
+#[rustfmt::skip]
+pub fn update_layer_cameras_hdr(
+    mut commands:       Commands,
+        query_cameras:  Query<(Entity, &Camera, Has<Hdr>), Without<PostProcessingCamera>>,
+        camera_targets: Res<CameraTargets>,
+) {
+    for (entity, camera, has_hdr) in query_cameras.iter() {
+        let RenderTarget::Image(ref target) = camera.target else {
+            continue;
+        };
+        if !camera_targets.is_layer_target(&target.handle) {
+            continue;
+        }
+
+        if camera_targets.hdr && !has_hdr {
+            commands.entity(entity).insert(Hdr);
+        } else if !camera_targets.hdr && has_hdr {
+            commands.entity(entity).remove::<Hdr>();
+        }
+    }
+}
+
+/// Applies [`BevyMagicLight2DSettings::msaa`] to the cameras rendering into
+/// layer targets and to the cameras spawned by the plugin.
+#[rustfmt::skip]
+#[allow(clippy::type_complexity)]
+pub fn update_camera_msaa(
+    mut query_cameras:  Query<(&Camera, &mut Msaa, Has<PostProcessingCamera>, Has<UpscaleCamera>)>,
+        camera_targets: Res<CameraTargets>,
+        settings:       Res<BevyMagicLight2DSettings>,
+) {
+    for (camera, mut msaa, is_post_camera, is_upscale_camera) in query_cameras.iter_mut() {
+        let renders_layer = match camera.target {
+            RenderTarget::Image(ref target) => camera_targets.is_layer_target(&target.handle),
+            _ => false,
+        };
+        if renders_layer || is_post_camera || is_upscale_camera {
+            msaa.set_if_neq(settings.msaa);
+        }
+    }
+}
+
+/// Deactivates the post-processing and upscale cameras while a [`GiWarmup`]
+/// runs and reactivates them once it finishes. Cameras deactivated by the
+/// game are left alone.
+#[rustfmt::skip]
+pub fn system_hide_composite_during_warmup(
+    mut query_post_camera: Query<(Entity, &mut Camera), Or<(With<PostProcessingCamera>, With<UpscaleCamera>)>>,
+        warmup:            Res<GiWarmup>,
+    mut hidden:            Local<Option<Vec<Entity>>>,
+) {
+    match (warmup.is_warming_up(), hidden.is_some()) {
+        (true, false) => {
+            let mut deactivated = Vec::new();
+            for (entity, mut camera) in query_post_camera.iter_mut() {
+                if camera.is_active {
+                    camera.is_active = false;
+                    deactivated.push(entity);
+                }
+            }
+            *hidden = Some(deactivated);
+        }
+        (false, true) => {
+            for entity in hidden.take().unwrap_or_default() {
+                if let Ok((_, mut camera)) = query_post_camera.get_mut(entity) {
+                    camera.is_active = true;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Camera rendering [`Unlit`] sprites into `CameraTargets::unlit_target`.
+/// Spawned by the plugin and kept in sync with the `ObjectsCamera`.
+#[derive(Component)]
+pub struct UnlitCamera;
+
+/// Moves [`Unlit`] entities to the unlit render layer, also when the layers
+/// are remapped.
+pub fn system_assign_unlit_layers(
+    mut commands: Commands,
+    query_added: Query<Entity, Added<Unlit>>,
+    query_unlit: Query<Entity, With<Unlit>>,
+    settings: Res<BevyMagicLight2DSettings>,
+)
+{
+    let layers = settings.render_layers.unlit_layers();
+    let entities = if settings.is_changed() {
+        query_unlit.iter().collect::<Vec<_>>()
+    } else {
+        query_added.iter().collect()
+    };
+    for entity in entities {
+        commands.entity(entity).insert(layers.clone());
+    }
+}
+
+/// Spawns the [`UnlitCamera`] as a child of the `ObjectsCamera`, so both share
+/// one global transform even when the `ObjectsCamera` is parented, and copies
+/// its projection every frame, so unlit sprites line up with the objects layer.
+#[rustfmt::skip]
+#[allow(clippy::type_complexity)]
+pub fn system_sync_unlit_camera(
+    mut commands:             Commands,
+        query_objects_camera: Query<(Entity, &Camera, Ref<Projection>), (With<ObjectsCamera>, Without<UnlitCamera>)>,
+    mut query_unlit_camera:   Query<(Entity, &mut Camera, &mut Projection, &mut RenderLayers, Option<&ChildOf>), With<UnlitCamera>>,
+        camera_targets:       Res<CameraTargets>,
+        settings:             Res<BevyMagicLight2DSettings>,
+) {
+    let Ok((objects_entity, objects_camera, objects_projection)) = query_objects_camera.single() else {
+        return;
+    };
+    let Some(unlit_target) = camera_targets.unlit_target.clone() else {
+        return;
+    };
+    let layers = settings.render_layers.unlit_layers();
+
+    let Ok((unlit_entity, mut camera, mut projection, mut render_layers, child_of)) = query_unlit_camera.single_mut() else {
+        commands.spawn((
+            Name::new("unlit_camera"),
+            UnlitCamera,
+            Camera2d,
+            Camera {
+                order:       objects_camera.order,
+                target:      RenderTarget::Image(unlit_target.into()),
+                clear_color: ClearColorConfig::Custom(Color::NONE),
+                ..default()
+            },
+            Transform::IDENTITY,
+            ChildOf(objects_entity),
+            (*objects_projection).clone(),
+            settings.msaa,
+            layers,
+        ));
+        return;
+    };
+
+    if child_of.map(ChildOf::parent) != Some(objects_entity) {
+        commands.entity(unlit_entity).insert(ChildOf(objects_entity));
+    }
+    if camera.order != objects_camera.order {
+        camera.order = objects_camera.order;
+    }
+    if objects_projection.is_changed() {
+        *projection = (*objects_projection).clone();
+    }
+    if *render_layers != layers {
+        *render_layers = layers;
+    }
+}
+
+/// Material of the quad presenting the full resolution output.
+#[rustfmt::skip]
+#[derive(AsBindGroup, Clone, TypePath, Asset)]
+pub struct UpscaleMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    source_image: Handle<Image>,
+
+    #[uniform(2)]
+    filter:       u32,
+
+    /// The source is premultiplied with a transparent background.
+    #[uniform(3)]
+    transparent:  u32,
+
+    alpha_mode:   AlphaMode2d,
+}
+
+impl Material2d for UpscaleMaterial
+{
+    fn fragment_shader() -> ShaderRef
+    {
+        ShaderRef::Path(shader_path("gi_upscale.wgsl").into())
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d
+    {
+        self.alpha_mode
+    }
+}
+
+/// Camera presenting the composite to the window when
+/// [`BevyMagicLight2DSettings::full_resolution_output`] is set.
+#[derive(Component)]
+pub struct UpscaleCamera;
+
+#[derive(Component)]
+pub struct UpscaleQuad;
+
+/// Spawns the [`UpscaleCamera`] and its quad while the full resolution
+/// output is enabled, and despawns them once it is disabled.
+#[rustfmt::skip]
+#[allow(clippy::too_many_arguments)]
+pub fn system_sync_upscale_output(
+    mut commands:             Commands,
+    mut meshes:               ResMut<Assets<Mesh>>,
+    mut materials:            ResMut<Assets<UpscaleMaterial>>,
+    mut query_upscale_camera: Query<(Entity, &mut Camera, &mut RenderLayers), With<UpscaleCamera>>,
+    mut query_upscale_quad:   Query<(Entity, &mut Transform, &mut RenderLayers, &MeshMaterial2d<UpscaleMaterial>), (With<UpscaleQuad>, Without<UpscaleCamera>)>,
+        camera_targets:       Res<CameraTargets>,
+        target_sizes:         Res<ComputedTargetSizes>,
+        settings:             Res<BevyMagicLight2DSettings>,
+) {
+    let (Some(filter), Some(source)) = (
+        settings.effective_upscale_filter(),
+        upscale_source(&settings, &camera_targets),
+    ) else {
+        for (entity, ..) in query_upscale_camera.iter() {
+            commands.entity(entity).despawn();
+        }
+        for (entity, ..) in query_upscale_quad.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+
+    let order = settings.post_processing_camera_params.order + 1;
+    let clear_color = settings.post_processing_camera_params.clear_color;
+    let transparent = settings.transparent_background as u32;
+    let layers = settings.render_layers.upscale_layers();
+    // The quad covers the window in logical pixels, like the
+    // post-processing quad.
+    let scale = target_sizes.primary_target_size.extend(1.0);
+
+    match query_upscale_camera.single_mut() {
+        Ok((_, mut camera, mut render_layers)) => {
+            if camera.order != order {
+                camera.order = order;
+            }
+            if camera.clear_color != clear_color {
+                camera.clear_color = clear_color;
+            }
+            if *render_layers != layers {
+                *render_layers = layers.clone();
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                Name::new("upscale_camera"),
+                UpscaleCamera,
+                Camera2d,
+                // Presents in place of the post-processing camera, so it
+                // clears like it.
+                Camera {
+                    order,
+                    clear_color,
+                    ..default()
+                },
+                // The composite is already tone mapped.
+                Tonemapping::None,
+                settings.msaa,
+                layers.clone(),
+            ));
+        }
+    }
+
+    match query_upscale_quad.single_mut() {
+        Ok((_, mut transform, mut render_layers, material)) => {
+            if transform.scale != scale {
+                transform.scale = scale;
+            }
+            if *render_layers != layers {
+                *render_layers = layers;
+            }
+            let outdated = materials
+                .get(material.id())
+                .is_some_and(|material| {
+                    material.filter != filter as u32 || material.transparent != transparent
+                });
+            if outdated {
+                if let Some(material) = materials.get_mut(material.id()) {
+                    material.filter = filter as u32;
+                    material.transparent = transparent;
+                    material.alpha_mode = composite_alpha_mode(&settings);
+                }
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                UpscaleQuad,
+                Mesh2d(meshes.add(bevy::math::primitives::Rectangle::new(1.0, 1.0))),
+                MeshMaterial2d(materials.add(UpscaleMaterial {
+                    source_image: source,
+                    filter:       filter as u32,
+                    transparent,
+                    alpha_mode:   composite_alpha_mode(&settings),
+                })),
+                Transform::from_scale(scale),
+                layers,
+            ));
+        }
+    }
+}
+
+/// Warns when another active camera rendering to a window shares the
+/// post-processing camera's order.
+#[rustfmt::skip]
+pub fn warn_post_processing_camera_order(
+    query_post_camera: Query<&Camera, With<PostProcessingCamera>>,
+    query_cameras:     Query<(&Camera, Option<&Name>), Without<PostProcessingCamera>>,
+    query_changed:     Query<(), Changed<Camera>>,
+) {
+    if query_changed.is_empty() {
+        return;
+    }
+    let Ok(post_camera) = query_post_camera.single() else {
+        return;
+    };
+    if matches!(post_camera.target, RenderTarget::Image(_)) {
+        return;
+    }
+
+    for (camera, name) in query_cameras.iter() {
+        if !camera.is_active || matches!(camera.target, RenderTarget::Image(_)) {
+            continue;
+        }
+        if camera.order == post_camera.order {
+            log::warn!(
+                "Camera {:?} has the same order ({}) as the post-processing camera, \
+                 set `post_processing_camera_params.order` to a unique value",
+                name.map(|n| n.as_str()).unwrap_or("<unnamed>"),
+                camera.order,
+            );
+        }
+    }
 }
@@ -1,9 +1,18 @@
-use bevy::asset::uuid_handle;
-use bevy::prelude::*;
+pub const GI_SCREEN_PROBE_SIZE: i32 = 8;
 
-use crate::gi::compositing::PostProcessingMaterial;
+/// Maximum number of lights sent to the GPU per frame, extra lights are
+/// dropped with a warning.
+pub const MAX_LIGHTS: usize = 1024;
 
-pub const GI_SCREEN_PROBE_SIZE: i32 = 8;
+/// Maximum number of occluders sent to the GPU per frame, extra occluders
+/// are dropped with a warning.
+pub const MAX_OCCLUDERS: usize = 2048;
+
+/// Area covered by the SDF map relative to the view, per axis, so that
+/// occluders just offscreen still cast shadows. Independent of the SDF
+/// resolution, see `TargetScalingParams::sdf_scale`. Mirrored in
+/// `gi_post_processing.wgsl`.
+pub const SDF_VIEW_COVERAGE: f32 = 2.0;
 
-pub const POST_PROCESSING_RECT: Handle<Mesh> = uuid_handle!("9999c9b9-c46a-48e7-b7b8-023a354b7cac");
-pub const POST_PROCESSING_MATERIAL: Handle<PostProcessingMaterial> = uuid_handle!("9999c9b9-c46a-48e7-b7b8-023a354b9cac");
+/// Side length of the tiled blue-noise texture used to jitter indirect rays.
+pub const BLUE_NOISE_SIZE: u32 = 32;
@@ -0,0 +1,87 @@
+//! Helpers to look up and tweak lights by [`Name`] from exclusive systems,
+//! commands or scripted sequences.
+//!
+//! Both [`OmniLightSource2D`] and [`RectLightSource2D`] are handled. Lights
+//! are mutated through change detection, which is all the GI passes need to
+//! pick up the new values and to invalidate a [`LightBake`](crate::gi::bake::LightBake).
+//! Each setter returns the number of lights it updated, names don't have to
+//! be unique.
+
+use bevy::prelude::*;
+
+use crate::gi::types::{OmniLightSource2D, RectLightSource2D};
+
+/// Entities of all lights named `name`.
+pub fn find_lights(world: &mut World, name: &str) -> Vec<Entity>
+{
+    let mut query = world.query_filtered::<(Entity, &Name), Or<(
+        With<OmniLightSource2D>,
+        With<RectLightSource2D>,
+    )>>();
+    query
+        .iter(world)
+        .filter(|(_, light_name)| light_name.as_str() == name)
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// First light named `name`, if any.
+pub fn find_light(world: &mut World, name: &str) -> Option<Entity>
+{
+    find_lights(world, name).into_iter().next()
+}
+
+pub fn set_light_intensity(world: &mut World, name: &str, intensity: f32) -> usize
+{
+    for_each_light(
+        world,
+        name,
+        |light| light.intensity = intensity,
+        |light| light.intensity = intensity,
+    )
+}
+
+pub fn set_light_color(world: &mut World, name: &str, color: Color) -> usize
+{
+    for_each_light(
+        world,
+        name,
+        |light| light.color = color,
+        |light| light.color = color,
+    )
+}
+
+/// Moves the lights in the xy plane, their depth is kept.
+pub fn set_light_position(world: &mut World, name: &str, position: Vec2) -> usize
+{
+    let mut updated = 0;
+    for entity in find_lights(world, name) {
+        if let Some(mut transform) = world.get_mut::<Transform>(entity) {
+            transform.translation = position.extend(transform.translation.z);
+            updated += 1;
+        }
+    }
+    updated
+}
+
+fn for_each_light(
+    world: &mut World,
+    name: &str,
+    mut update_omni: impl FnMut(&mut OmniLightSource2D),
+    mut update_rect: impl FnMut(&mut RectLightSource2D),
+) -> usize
+{
+    let mut updated = 0;
+    for entity in find_lights(world, name) {
+        let mut entity = world.entity_mut(entity);
+        if let Some(mut light) = entity.get_mut::<OmniLightSource2D>() {
+            update_omni(&mut light);
+            updated += 1;
+        }
+        if let Some(mut light) = entity.get_mut::<RectLightSource2D>() {
+            update_rect(&mut light);
+            updated += 1;
+        }
+    }
+    updated
+}
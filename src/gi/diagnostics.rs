@@ -0,0 +1,154 @@
+use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore};
+use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
+
+use crate::gi::bake::GiSceneChanges;
+use crate::gi::resource::{
+    BevyMagicLight2DSettings,
+    ComputedTargetSizes,
+    FrameStepControl,
+    GiUpdateClock,
+};
+
+/// Compute passes dispatched by the light pass node, in dispatch order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GiPass
+{
+    Sdf,
+    SsProbe,
+    SsBounce,
+    SsBlend,
+    SsFilter,
+}
+
+impl GiPass
+{
+    pub const ALL: [GiPass; 5] = [
+        GiPass::Sdf,
+        GiPass::SsProbe,
+        GiPass::SsBounce,
+        GiPass::SsBlend,
+        GiPass::SsFilter,
+    ];
+
+    /// Name of the render diagnostics span recorded around the pass.
+    pub fn span_name(&self) -> &'static str
+    {
+        match self {
+            GiPass::Sdf => "gi_sdf",
+            GiPass::SsProbe => "gi_ss_probe",
+            GiPass::SsBounce => "gi_ss_bounce",
+            GiPass::SsBlend => "gi_ss_blend",
+            GiPass::SsFilter => "gi_ss_filter",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str
+    {
+        match self {
+            GiPass::Sdf => "SDF",
+            GiPass::SsProbe => "Probe",
+            GiPass::SsBounce => "Bounce",
+            GiPass::SsBlend => "Blend",
+            GiPass::SsFilter => "Filter",
+        }
+    }
+
+    fn elapsed_gpu_path(&self) -> DiagnosticPath
+    {
+        DiagnosticPath::from_components(["render", self.span_name(), "elapsed_gpu"])
+    }
+}
+
+/// GPU time spent in each GI compute pass, in milliseconds.
+///
+/// Timings are recorded through Bevy's render diagnostics, so they are only
+/// available when `RenderDiagnosticsPlugin` is added and the backend supports
+/// timestamp queries. Otherwise every pass reports `None`.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct GiDiagnostics
+{
+    pub pass_ms: [Option<f64>; 5],
+}
+
+impl GiDiagnostics
+{
+    pub fn pass(&self, pass: GiPass) -> Option<f64>
+    {
+        self.pass_ms[pass as usize]
+    }
+
+    /// Sum of all passes, or `None` if no pass has been timed.
+    pub fn total_ms(&self) -> Option<f64>
+    {
+        self.pass_ms
+            .iter()
+            .flatten()
+            .copied()
+            .reduce(|a, b| a + b)
+    }
+}
+
+pub(crate) fn system_update_gi_diagnostics(
+    store: Option<Res<DiagnosticsStore>>,
+    mut gi_diagnostics: ResMut<GiDiagnostics>,
+)
+{
+    let Some(store) = store else {
+        return;
+    };
+
+    for pass in GiPass::ALL {
+        gi_diagnostics.pass_ms[pass as usize] = store
+            .get(&pass.elapsed_gpu_path())
+            .and_then(|diagnostic| diagnostic.smoothed());
+    }
+}
+
+/// Temporal accumulation progress, e.g. for a "converging" indicator.
+///
+/// `frames` counts GI updates since the last change of lights, occluders,
+/// the floor camera or the targets. Once it reaches `frames_to_converge`
+/// every probe atlas tile holds a sample taken after the change.
+#[derive(Resource, ExtractResource, Default, Debug, Clone, Copy)]
+pub struct GiConvergence
+{
+    pub frames:             u32,
+    pub frames_to_converge: u32,
+}
+
+impl GiConvergence
+{
+    /// Accumulation progress in `[0, 1]`.
+    pub fn progress(&self) -> f32
+    {
+        if self.frames_to_converge == 0 {
+            return 1.0;
+        }
+        (self.frames as f32 / self.frames_to_converge as f32).min(1.0)
+    }
+
+    pub fn is_converged(&self) -> bool
+    {
+        self.frames >= self.frames_to_converge
+    }
+}
+
+pub(crate) fn system_update_gi_convergence(
+    mut convergence: ResMut<GiConvergence>,
+    mut scene_changes: GiSceneChanges,
+    gi_clock: Res<GiUpdateClock>,
+    frame_step: Res<FrameStepControl>,
+    target_sizes: Res<ComputedTargetSizes>,
+    settings: Res<BevyMagicLight2DSettings>,
+)
+{
+    convergence.frames_to_converge =
+        target_sizes.frame_cycle(settings.light_pass_params.reservoir_size);
+
+    if scene_changes.any() {
+        convergence.frames = 0;
+    } else if gi_clock.update_due() && frame_step.advances() {
+        convergence.frames = convergence.frames.saturating_add(1);
+    }
+}
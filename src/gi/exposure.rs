@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_resource::TextureFormat;
+
+use crate::gi::compositing::{PostProcessingHandles, PostProcessingMaterial};
+use crate::gi::pipeline::GiTargetsWrapper;
+use crate::gi::resource::BevyMagicLight2DSettings;
+
+/// Eye adaptation, see [`BevyMagicLight2DSettings::auto_exposure`].
+#[derive(Copy, Clone, Debug, Reflect)]
+pub struct AutoExposureParams
+{
+    /// Average irradiance luminance the exposure adapts towards.
+    pub target_luminance: f32,
+    /// Adaptation rate, higher values adapt faster. Roughly the inverse of
+    /// the time in seconds to close most of the gap.
+    pub speed:            f32,
+    pub min_exposure:     f32,
+    pub max_exposure:     f32,
+}
+
+impl Default for AutoExposureParams
+{
+    fn default() -> Self
+    {
+        Self {
+            target_luminance: 0.5,
+            speed:            1.5,
+            min_exposure:     0.1,
+            max_exposure:     10.0,
+        }
+    }
+}
+
+/// Exposure currently applied to irradiance in the composite.
+///
+/// Follows [`BevyMagicLight2DSettings::exposure`], or adapts to the measured
+/// scene luminance when [`BevyMagicLight2DSettings::auto_exposure`] is set.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct GiExposure
+{
+    pub exposure:          f32,
+    /// Log-average irradiance luminance of the last GPU readback. Only
+    /// measured while auto exposure is enabled.
+    pub average_luminance: Option<f32>,
+}
+
+impl Default for GiExposure
+{
+    fn default() -> Self
+    {
+        Self {
+            exposure:          1.0,
+            average_luminance: None,
+        }
+    }
+}
+
+/// Reads back the temporally blended probe grid, it is already downsampled
+/// to one texel per probe.
+#[derive(Component)]
+struct ExposureReadback(Handle<Image>);
+
+pub(crate) fn system_update_exposure_readback(
+    mut commands: Commands,
+    settings: Res<BevyMagicLight2DSettings>,
+    gi_targets_wrapper: Res<GiTargetsWrapper>,
+    query: Query<(Entity, &ExposureReadback)>,
+    mut exposure: ResMut<GiExposure>,
+)
+{
+    let target = gi_targets_wrapper
+        .targets
+        .as_ref()
+        .filter(|_| settings.auto_exposure.is_some())
+        .map(|targets| targets.ss_blend_target.clone());
+
+    let Some(target) = target else {
+        for (entity, _) in query.iter() {
+            commands.entity(entity).despawn();
+        }
+        if exposure.average_luminance.is_some() {
+            exposure.average_luminance = None;
+        }
+        return;
+    };
+
+    match query.single() {
+        Ok((_, readback)) if readback.0 == target => {}
+        Ok((entity, _)) => {
+            commands
+                .entity(entity)
+                .insert((ExposureReadback(target.clone()), Readback::texture(target)));
+        }
+        Err(_) => {
+            for (entity, _) in query.iter() {
+                commands.entity(entity).despawn();
+            }
+            commands
+                .spawn((ExposureReadback(target.clone()), Readback::texture(target)))
+                .observe(on_exposure_readback);
+        }
+    }
+}
+
+fn on_exposure_readback(
+    readback: On<ReadbackComplete>,
+    query: Query<&ExposureReadback>,
+    images: Res<Assets<Image>>,
+    mut exposure: ResMut<GiExposure>,
+)
+{
+    let Ok(ExposureReadback(handle)) = query.get(readback.entity) else {
+        return;
+    };
+    let Some(image) = images.get(handle) else {
+        return;
+    };
+
+    let size = image.size();
+    let format = image.texture_descriptor.format;
+    if let Some(luminance) = average_luminance(&readback.data, size, format) {
+        exposure.average_luminance = Some(luminance);
+    }
+}
+
+/// Log-average luminance of an rgba float texture, rows may be padded.
+fn average_luminance(data: &[u8], size: UVec2, format: TextureFormat) -> Option<f32>
+{
+    let texel_size = match format {
+        TextureFormat::Rgba32Float => 16,
+        TextureFormat::Rgba16Float => 8,
+        _ => return None,
+    };
+    if size.x == 0 || size.y == 0 {
+        return None;
+    }
+
+    let row_size = size.x as usize * texel_size;
+    let stride = data.len() / size.y as usize;
+    if stride < row_size {
+        return None;
+    }
+
+    let channel = |texel: &[u8], i: usize| match format {
+        TextureFormat::Rgba32Float => {
+            f32::from_le_bytes([texel[i * 4], texel[i * 4 + 1], texel[i * 4 + 2], texel[i * 4 + 3]])
+        }
+        _ => f16_to_f32(u16::from_le_bytes([texel[i * 2], texel[i * 2 + 1]])),
+    };
+
+    let mut log_sum = 0.0;
+    for row in data.chunks_exact(stride).take(size.y as usize) {
+        for texel in row[..row_size].chunks_exact(texel_size) {
+            let rgb = Vec3::new(channel(texel, 0), channel(texel, 1), channel(texel, 2));
+            let luminance = rgb.dot(Vec3::new(0.2126, 0.7152, 0.0722));
+            if luminance.is_finite() {
+                log_sum += (luminance.max(0.0) + 1e-4).ln();
+            }
+        }
+    }
+
+    Some((log_sum / (size.x * size.y) as f32).exp())
+}
+
+fn f16_to_f32(bits: u16) -> f32
+{
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+pub(crate) fn system_update_exposure(
+    settings: Res<BevyMagicLight2DSettings>,
+    time: Res<Time>,
+    mut exposure: ResMut<GiExposure>,
+    mut materials: ResMut<Assets<PostProcessingMaterial>>,
+    post_handles: Res<PostProcessingHandles>,
+)
+{
+    let next = match (settings.auto_exposure, exposure.average_luminance) {
+        (Some(params), Some(luminance)) => {
+            let min = params.min_exposure.max(0.0);
+            let target = (params.target_luminance / luminance.max(1e-4))
+                .clamp(min, params.max_exposure.max(min));
+            let t = 1.0 - (-params.speed.max(0.0) * time.delta_secs()).exp();
+            exposure.exposure + (target - exposure.exposure) * t
+        }
+        // Hold the current exposure until the first readback arrives.
+        (Some(_), None) => exposure.exposure,
+        (None, _) => settings.exposure.max(0.0),
+    };
+    if exposure.exposure != next {
+        exposure.exposure = next;
+    }
+
+    // Target and settings changes keep the exposure, so it is compared and
+    // the material only touched when it moves.
+    let outdated = materials
+        .get(post_handles.material.id())
+        .is_some_and(|material| material.exposure() != exposure.exposure);
+    if outdated {
+        if let Some(material) = materials.get_mut(post_handles.material.id()) {
+            material.set_exposure(exposure.exposure);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Inspector widgets for the light components.
+//!
+//! [`LightInspectorPlugin`] replaces the inspector UI of
+//! [`OmniLightSource2D`] with its fields plus a swatch of the light's radial
+//! falloff. Swatches share a fixed world scale, so the reach of lights can be
+//! compared side by side in the entity list.
+
+use std::any::{Any, TypeId};
+
+use bevy::prelude::*;
+use bevy::reflect::{PartialReflect, ReflectMut, ReflectRef, Struct};
+use bevy_inspector_egui::bevy_egui::egui;
+use bevy_inspector_egui::inspector_egui_impls::InspectorEguiImpl;
+use bevy_inspector_egui::reflect_inspector::InspectorUi;
+
+use crate::gi::resource::LightPassParams;
+use crate::gi::types::OmniLightSource2D;
+
+/// World distance covered by the radius of a swatch.
+const PREVIEW_DISTANCE: f32 = 400.0;
+/// Rings the swatch gradient is drawn with.
+const PREVIEW_RINGS: usize = 32;
+const PREVIEW_SIZE: f32 = 48.0;
+
+pub struct LightInspectorPlugin;
+
+impl Plugin for LightInspectorPlugin
+{
+    fn build(&self, app: &mut App)
+    {
+        let mut registry = app.world().resource::<AppTypeRegistry>().write();
+        registry.register::<OmniLightSource2D>();
+        registry
+            .get_mut(TypeId::of::<OmniLightSource2D>())
+            .expect("OmniLightSource2D was just registered")
+            .insert(InspectorEguiImpl::new(
+                omni_light_ui,
+                omni_light_ui_readonly,
+                omni_light_ui_many,
+            ));
+    }
+}
+
+fn omni_light_ui(
+    value: &mut dyn Any,
+    ui: &mut egui::Ui,
+    _options: &dyn Any,
+    id: egui::Id,
+    mut env: InspectorUi<'_, '_>,
+) -> bool
+{
+    let light = value
+        .downcast_mut::<OmniLightSource2D>()
+        .expect("inspector impl registered for OmniLightSource2D");
+
+    falloff_swatch(ui, light);
+
+    // The fields are drawn one by one, going through the inspector for the
+    // whole struct would end up back in this function.
+    let ReflectMut::Struct(fields) = light.reflect_mut() else {
+        unreachable!("OmniLightSource2D is a struct");
+    };
+    let mut changed = false;
+    egui::Grid::new(id).num_columns(2).show(ui, |ui| {
+        for i in 0..fields.field_len() {
+            let name = fields.name_at(i).unwrap_or_default().to_owned();
+            let Some(field) = fields.field_at_mut(i) else {
+                continue;
+            };
+            ui.label(name);
+            changed |= env.ui_for_reflect_with_options(field, ui, id.with(i), &());
+            ui.end_row();
+        }
+    });
+    changed
+}
+
+fn omni_light_ui_readonly(
+    value: &dyn Any,
+    ui: &mut egui::Ui,
+    _options: &dyn Any,
+    id: egui::Id,
+    mut env: InspectorUi<'_, '_>,
+)
+{
+    let light = value
+        .downcast_ref::<OmniLightSource2D>()
+        .expect("inspector impl registered for OmniLightSource2D");
+
+    falloff_swatch(ui, light);
+
+    let ReflectRef::Struct(fields) = light.reflect_ref() else {
+        unreachable!("OmniLightSource2D is a struct");
+    };
+    egui::Grid::new(id).num_columns(2).show(ui, |ui| {
+        for i in 0..fields.field_len() {
+            let Some(field) = fields.field_at(i) else {
+                continue;
+            };
+            ui.label(fields.name_at(i).unwrap_or_default());
+            env.ui_for_reflect_readonly_with_options(field, ui, id.with(i), &());
+            ui.end_row();
+        }
+    });
+}
+
+fn omni_light_ui_many(
+    ui: &mut egui::Ui,
+    _options: &dyn Any,
+    _id: egui::Id,
+    _env: InspectorUi<'_, '_>,
+    _values: &mut [&mut dyn PartialReflect],
+    _projector: &dyn Fn(&mut dyn PartialReflect) -> &mut dyn PartialReflect,
+) -> bool
+{
+    ui.label("Editing several lights at once is not supported");
+    false
+}
+
+/// Draws the light as seen from above, brightness tone mapped so lights of
+/// different intensities stay comparable.
+fn falloff_swatch(ui: &mut egui::Ui, light: &OmniLightSource2D)
+{
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(PREVIEW_SIZE, PREVIEW_SIZE), egui::Sense::hover());
+    let rect = response.rect;
+
+    painter.rect_filled(rect, 2.0, egui::Color32::BLACK);
+
+    // `intensity_at` already includes the brightest channel, the colors are
+    // normalized so it isn't applied twice.
+    let normalize = |color: LinearRgba| color / color.red.max(color.green).max(color.blue).max(1e-4);
+    let color = normalize(light.color.to_linear());
+    let edge_color = light.edge_color.map_or(color, normalize);
+    let radius = light.effective_radius(LightPassParams::default().light_cull_epsilon);
+
+    // Drawn from the outside in, each ring covering the previous one.
+    for ring in (0..PREVIEW_RINGS).rev() {
+        let distance = (ring as f32 + 0.5) / PREVIEW_RINGS as f32 * PREVIEW_DISTANCE;
+        let intensity = if light.enabled { light.intensity_at(distance) } else { 0.0 };
+        let exposed = intensity / (1.0 + intensity);
+        // Same color ramp as the probe pass.
+        let ring_color = color.mix(&edge_color, (distance / radius.max(1e-4)).clamp(0.0, 1.0)) * exposed;
+        painter.circle_filled(
+            rect.center(),
+            (ring + 1) as f32 / PREVIEW_RINGS as f32 * rect.width() * 0.5,
+            egui::Rgba::from_rgb(ring_color.red, ring_color.green, ring_color.blue),
+        );
+    }
+
+    response.on_hover_text(format!(
+        "Falloff up to {} world units from the light",
+        PREVIEW_DISTANCE
+    ));
+}
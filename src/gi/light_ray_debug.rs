@@ -0,0 +1,144 @@
+//! Gizmo overlay of the shadow rays cast from a single point to every light.
+//!
+//! Visibility is tested on the CPU with [`LightVisibility`], so it shows the
+//! same occluders the lighting uses. Occluder height and the height map are
+//! not taken into account, partially transparent occluders show as blocking.
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::gi::compositing::PostProcessingCamera;
+use crate::gi::line_of_sight::{LightVisibility, LineOfSightOccluder};
+use crate::gi::resource::BevyMagicLight2DSettings;
+use crate::gi::types::{OmniLightSource2D, RectLightSource2D};
+use crate::FloorCamera;
+
+/// Point the shadow rays are drawn from.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct LightRayDebug
+{
+    /// World position to test, `None` hides the overlay.
+    pub point:       Option<Vec2>,
+    /// Clicking with this button moves `point` to the cursor.
+    pub pick_button: Option<MouseButton>,
+}
+
+/// Gizmos of the overlay, drawn by the post-processing camera so they are
+/// unlit and on top of the composite.
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct LightRayGizmos;
+
+const VISIBLE_COLOR: Color = Color::srgb(0.2, 1.0, 0.2);
+const BLOCKED_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+
+pub(crate) fn system_setup_light_ray_gizmos(
+    mut config_store: ResMut<GizmoConfigStore>,
+    settings: Res<BevyMagicLight2DSettings>,
+)
+{
+    let (config, _) = config_store.config_mut::<LightRayGizmos>();
+    config.render_layers = settings.render_layers.post_processing_layers();
+}
+
+#[rustfmt::skip]
+pub(crate) fn system_pick_light_ray_point(
+    mut light_ray_debug: ResMut<LightRayDebug>,
+        mouse_buttons:   Res<ButtonInput<MouseButton>>,
+        query_window:    Query<&Window, With<PrimaryWindow>>,
+        query_camera:    Query<(&Camera, &GlobalTransform), With<FloorCamera>>,
+) {
+    let Some(button) = light_ray_debug.pick_button else {
+        return;
+    };
+    if !mouse_buttons.just_pressed(button) {
+        return;
+    }
+    let (Ok(window), Ok((camera, transform))) = (query_window.single(), query_camera.single()) else {
+        return;
+    };
+    let point = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(transform, cursor).ok());
+    if point.is_some() {
+        light_ray_debug.point = point;
+    }
+}
+
+#[rustfmt::skip]
+pub(crate) fn system_draw_light_rays(
+    mut gizmos:            Gizmos<LightRayGizmos>,
+        light_ray_debug:   Res<LightRayDebug>,
+        light_visibility:  Res<LightVisibility>,
+        query_lights:      Query<(&GlobalTransform, &OmniLightSource2D, &InheritedVisibility)>,
+        query_rect_lights: Query<(&GlobalTransform, &RectLightSource2D, &InheritedVisibility)>,
+        query_camera:      Query<(&Camera, &GlobalTransform), With<FloorCamera>>,
+        query_post_camera: Query<(&Camera, &GlobalTransform), With<PostProcessingCamera>>,
+) {
+    let Some(point) = light_ray_debug.point else {
+        return;
+    };
+    let (Ok((camera, transform)), Ok((post_camera, post_transform))) =
+        (query_camera.single(), query_post_camera.single())
+    else {
+        return;
+    };
+
+    // Scene positions are drawn where the composite shows them.
+    let to_overlay = |world_pose: Vec2| {
+        camera
+            .world_to_viewport(transform, world_pose.extend(0.0))
+            .and_then(|viewport| post_camera.viewport_to_world_2d(post_transform, viewport))
+            .ok()
+    };
+
+    let lights = query_lights
+        .iter()
+        .filter(|(_, light, hviz)| light.enabled && hviz.get())
+        .map(|(t, light, _)| (t.translation().xy(), light.occluder_mask, light.shadow_bias))
+        .chain(
+            query_rect_lights
+                .iter()
+                .filter(|(_, light, hviz)| light.enabled && hviz.get())
+                .map(|(t, light, _)| (t.translation().xy(), light.occluder_mask, 1.0)),
+        );
+
+    let Some(overlay_point) = to_overlay(point) else {
+        return;
+    };
+    gizmos.circle_2d(overlay_point, 4.0, Color::WHITE);
+
+    for (light_pose, occluder_mask, shadow_bias) in lights {
+        let Some(overlay_light) = to_overlay(light_pose) else {
+            continue;
+        };
+
+        // Rays stop short of the light like in the probe pass.
+        let to_light = light_pose - point;
+        let ray_end = light_pose - to_light.normalize_or_zero() * shadow_bias.min(to_light.length());
+
+        match light_visibility.first_blocker_masked(point, ray_end, occluder_mask) {
+            None => gizmos.line_2d(overlay_point, overlay_light, VISIBLE_COLOR),
+            Some((occluder, hit)) => {
+                gizmos.line_2d(overlay_point, overlay_light, BLOCKED_COLOR);
+                if let Some(overlay_hit) = to_overlay(hit) {
+                    gizmos.circle_2d(overlay_hit, 3.0, BLOCKED_COLOR);
+                }
+                let outline = occluder_outline(&occluder).map(&to_overlay);
+                if outline.iter().all(Option::is_some) {
+                    gizmos.linestrip_2d(outline.into_iter().flatten(), BLOCKED_COLOR);
+                }
+            }
+        }
+    }
+}
+
+/// Closed outline of the occluder's box in world space.
+fn occluder_outline(occluder: &LineOfSightOccluder) -> [Vec2; 5]
+{
+    let rotation = occluder.inv_rotation.inverse();
+    let corner = |x: f32, y: f32| {
+        occluder.center + (rotation * (occluder.h_extent * Vec2::new(x, y)).extend(0.0)).truncate()
+    };
+    let first = corner(-1.0, -1.0);
+    [first, corner(1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0), first]
+}
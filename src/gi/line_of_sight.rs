@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+
+use crate::gi::types::{LightOccluder2D, TileOccluderGrid, OCCLUDER_MASK_ALL};
+
+/// CPU copy of a single occluder, in the same form it is sent to the GPU.
+#[derive(Debug, Clone, Copy)]
+pub struct LineOfSightOccluder
+{
+    pub center:       Vec2,
+    pub inv_rotation: Quat,
+    pub h_extent:     Vec2,
+    pub group:        u32,
+}
+
+impl LineOfSightOccluder
+{
+    fn to_local(&self, p: Vec2) -> Vec2
+    {
+        (self.inv_rotation * (p - self.center).extend(0.0)).truncate()
+    }
+
+    /// Segment vs oriented box test (slab method in the occluder's frame).
+    pub fn intersects_segment(&self, a: Vec2, b: Vec2) -> bool
+    {
+        self.segment_entry(a, b).is_some()
+    }
+
+    /// Fraction of the segment from `a` to `b` at which it enters the box,
+    /// `0.0` if `a` is inside it.
+    pub fn segment_entry(&self, a: Vec2, b: Vec2) -> Option<f32>
+    {
+        let origin = self.to_local(a);
+        let delta = self.to_local(b) - origin;
+
+        let mut t_min = 0.0_f32;
+        let mut t_max = 1.0_f32;
+        for axis in 0..2 {
+            let (o, d, h) = (origin[axis], delta[axis], self.h_extent[axis]);
+            if d.abs() < f32::EPSILON {
+                if o < -h || o > h {
+                    return None;
+                }
+                continue;
+            }
+            let inv_d = 1.0 / d;
+            let t0 = (-h - o) * inv_d;
+            let t1 = (h - o) * inv_d;
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+}
+
+/// Line-of-sight queries against the occluders used for lighting.
+///
+/// Updated every frame from the same set of occluders that is extracted for
+/// the GPU raymarch, so CPU queries agree with what the lighting shows. This
+/// includes the cells of the [`TileOccluderGrid`], which like on the GPU only
+/// block queries with [`OCCLUDER_MASK_ALL`].
+#[derive(Resource, Default, Debug, Clone)]
+pub struct LightVisibility
+{
+    occluders: Vec<LineOfSightOccluder>,
+    tile_grid: Option<TileOccluderGrid>,
+}
+
+impl LightVisibility
+{
+    pub fn occluders(&self) -> &[LineOfSightOccluder]
+    {
+        &self.occluders
+    }
+
+    /// Returns `true` if no occluder blocks the segment from `a` to `b`.
+    pub fn is_visible(&self, a: Vec2, b: Vec2) -> bool
+    {
+        self.is_visible_masked(a, b, OCCLUDER_MASK_ALL)
+    }
+
+    /// Same as [`Self::is_visible`], but only occluders whose group
+    /// intersects `occluder_mask` block the segment.
+    pub fn is_visible_masked(&self, a: Vec2, b: Vec2, occluder_mask: u32) -> bool
+    {
+        !self
+            .occluders
+            .iter()
+            .filter(|occluder| occluder.group & occluder_mask != 0)
+            .any(|occluder| occluder.intersects_segment(a, b))
+            && self
+                .blocking_tile_grid(occluder_mask)
+                .is_none_or(|grid| grid.first_solid_cell(a, b).is_none())
+    }
+
+    /// First occluder matching `occluder_mask` on the segment from `a` to
+    /// `b`, and the point where the segment enters it. Tile grid cells are
+    /// returned as an axis-aligned occluder of group `0`.
+    pub fn first_blocker_masked(
+        &self,
+        a: Vec2,
+        b: Vec2,
+        occluder_mask: u32,
+    ) -> Option<(LineOfSightOccluder, Vec2)>
+    {
+        let tile_blocker = self.blocking_tile_grid(occluder_mask).and_then(|grid| {
+            grid.first_solid_cell(a, b).map(|(cell, t)| {
+                let occluder = LineOfSightOccluder {
+                    center:       grid.cell_center(cell),
+                    inv_rotation: Quat::IDENTITY,
+                    h_extent:     grid.cell_size * 0.5,
+                    group:        0,
+                };
+                (occluder, t)
+            })
+        });
+        self.occluders
+            .iter()
+            .filter(|occluder| occluder.group & occluder_mask != 0)
+            .filter_map(|occluder| occluder.segment_entry(a, b).map(|t| (*occluder, t)))
+            .chain(tile_blocker)
+            .min_by(|(_, t0), (_, t1)| t0.total_cmp(t1))
+            .map(|(occluder, t)| (occluder, a.lerp(b, t)))
+    }
+
+    fn blocking_tile_grid(&self, occluder_mask: u32) -> Option<&TileOccluderGrid>
+    {
+        self.tile_grid
+            .as_ref()
+            .filter(|_| occluder_mask == OCCLUDER_MASK_ALL)
+    }
+}
+
+#[rustfmt::skip]
+pub(crate) fn system_update_light_visibility(
+        query_occluders:  Query<(&LightOccluder2D, &GlobalTransform, &InheritedVisibility, &ViewVisibility)>,
+        query_tile_grids: Query<Ref<TileOccluderGrid>>,
+    mut light_visibility: ResMut<LightVisibility>,
+) {
+    // Only the first grid is used, like when it is extracted.
+    match query_tile_grids.iter().next() {
+        Some(grid) if grid.is_changed() || light_visibility.tile_grid.is_none() => {
+            light_visibility.tile_grid = Some(grid.clone());
+        }
+        Some(_) => {}
+        None => light_visibility.tile_grid = None,
+    }
+
+    light_visibility.occluders.clear();
+    for (occluder, global_transform, hviz, vviz) in query_occluders.iter() {
+        if hviz.get() && vviz.get() && occluder.group != 0 {
+            light_visibility.occluders.push(LineOfSightOccluder {
+                center:       global_transform.translation().xy(),
+                inv_rotation: global_transform.rotation().inverse(),
+                h_extent:     occluder.h_size,
+                group:        occluder.group,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn visibility_with_grid(solid: &[UVec2]) -> LightVisibility
+    {
+        let mut grid = TileOccluderGrid::new(UVec2::new(8, 8), Vec2::splat(10.0), Vec2::new(-40.0, -40.0));
+        for cell in solid {
+            grid.set_solid(*cell, true);
+        }
+        LightVisibility {
+            occluders: Vec::new(),
+            tile_grid: Some(grid),
+        }
+    }
+
+    #[test]
+    fn tile_grid_blocks_segments_through_solid_cells()
+    {
+        // Cell (4, 4) spans `0..10` on both axes.
+        let visibility = visibility_with_grid(&[UVec2::new(4, 4)]);
+
+        assert!(!visibility.is_visible(Vec2::new(-30.0, 5.0), Vec2::new(30.0, 5.0)));
+        assert!(!visibility.is_visible(Vec2::new(-30.0, -25.0), Vec2::new(30.0, 35.0)));
+        assert!(visibility.is_visible(Vec2::new(-30.0, 15.0), Vec2::new(30.0, 15.0)));
+        // Segments ending before the cell or running outside the grid.
+        assert!(visibility.is_visible(Vec2::new(-30.0, 5.0), Vec2::new(-1.0, 5.0)));
+        assert!(visibility.is_visible(Vec2::new(-60.0, 50.0), Vec2::new(60.0, 50.0)));
+    }
+
+    #[test]
+    fn tile_grid_only_blocks_mask_all()
+    {
+        let visibility = visibility_with_grid(&[UVec2::new(4, 4)]);
+        assert!(visibility.is_visible_masked(Vec2::new(-30.0, 5.0), Vec2::new(30.0, 5.0), 0b01));
+    }
+
+    #[test]
+    fn first_blocker_is_the_entered_cell()
+    {
+        let visibility = visibility_with_grid(&[UVec2::new(4, 4), UVec2::new(6, 4)]);
+
+        let (occluder, hit) = visibility
+            .first_blocker_masked(Vec2::new(-30.0, 5.0), Vec2::new(30.0, 5.0), OCCLUDER_MASK_ALL)
+            .unwrap();
+        assert_eq!(occluder.center, Vec2::new(5.0, 5.0));
+        assert_eq!(occluder.h_extent, Vec2::splat(5.0));
+        assert!(hit.distance(Vec2::new(0.0, 5.0)) < 1e-4);
+
+        // Starting inside a solid cell blocks right away.
+        let (_, hit) = visibility
+            .first_blocker_masked(Vec2::new(25.0, 5.0), Vec2::new(-30.0, 5.0), OCCLUDER_MASK_ALL)
+            .unwrap();
+        assert_eq!(hit, Vec2::new(25.0, 5.0));
+    }
+}
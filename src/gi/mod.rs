@@ -1,21 +1,62 @@
+use bevy::camera::visibility::VisibilitySystems;
 use bevy::prelude::*;
+use bevy::render::diagnostic::RecordDiagnostics;
 use bevy::render::extract_resource::ExtractResourcePlugin;
+use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_graph::{self, RenderGraph, RenderLabel};
 use bevy::render::render_resource::*;
-use bevy::render::renderer::RenderContext;
+use bevy::render::renderer::{RenderAdapter, RenderContext};
+use bevy::render::texture::GpuImage;
 use bevy::render::{Render, RenderApp, RenderStartup, RenderSystems};
+#[cfg(not(feature = "hot_reload_shaders"))]
 use bevy::shader::load_shader_library;
+use bevy::transform::TransformSystems;
 use bevy::sprite_render::Material2dPlugin;
 use bevy::window::{PrimaryWindow, WindowResized};
 
 
 
 use self::pipeline::GiTargets;
-use crate::gi::compositing::{setup_post_processing_camera, CameraTargets, PostProcessingMaterial};
-use crate::gi::constants::{POST_PROCESSING_MATERIAL, POST_PROCESSING_RECT};
+use crate::gi::adaptive_rays::{system_update_adaptive_rays, GiAdaptiveRays};
+use crate::gi::bake::{system_invalidate_light_bake, system_update_light_bake, LightBake};
+use crate::gi::compositing::{
+    setup_post_processing_camera,
+    system_assign_unlit_layers,
+    system_hide_composite_during_warmup,
+    system_sync_unlit_camera,
+    system_sync_upscale_output,
+    system_update_target_transition,
+    update_camera_msaa,
+    update_layer_cameras_hdr,
+    update_post_processing_camera,
+    update_post_processing_material_asset,
+    warn_post_processing_camera_order,
+    CameraTargets,
+    GiTargetTransition,
+    PostProcessingHandles,
+    PostProcessingMaterial,
+    UpscaleMaterial,
+};
+use crate::gi::diagnostics::{
+    system_update_gi_convergence,
+    system_update_gi_diagnostics,
+    GiConvergence,
+    GiDiagnostics,
+    GiPass,
+};
+use crate::gi::exposure::{system_update_exposure, system_update_exposure_readback, GiExposure};
+use crate::gi::light_ray_debug::{
+    system_draw_light_rays,
+    system_pick_light_ray_point,
+    system_setup_light_ray_gizmos,
+    LightRayDebug,
+    LightRayGizmos,
+};
+use crate::gi::line_of_sight::{system_update_light_visibility, LightVisibility};
 use crate::gi::pipeline::{
     system_queue_bind_groups,
     system_setup_gi_pipeline,
+    GiTargetFormats,
     GiTargetsWrapper,
     LightPassPipeline,
     LightPassPipelineBindGroups,
@@ -27,21 +68,56 @@ use crate::gi::pipeline_assets::{
     EmbeddedShaderDependencies,
     LightPassPipelineAssets,
 };
-use crate::gi::resource::ComputedTargetSizes;
+use crate::gi::render_layer::MAX_EXTRA_LIT_LAYERS;
+use crate::gi::resource::{
+    system_update_frame_step,
+    system_update_gi_clock,
+    system_update_gi_warmup,
+    system_update_light_modulator,
+    ComputedTargetSizes,
+    FrameStepControl,
+    GiActiveLights,
+    GiPipelineReady,
+    GiPipelineStatus,
+    GiStatus,
+    GiUpdateClock,
+    GiWarmup,
+    LightModulator,
+    TargetScalingParams,
+};
+use crate::gi::scene_gizmos::{system_draw_scene_gizmos, system_setup_scene_gizmos, SceneGizmos};
+use crate::gi::types::{
+    system_animate_skylight_masks,
+    system_follow_attached_lights,
+    system_sync_shadow_casters,
+};
 use crate::prelude::BevyMagicLight2DSettings;
 
+mod blue_noise;
 mod constants;
-mod pipeline;
-mod pipeline_assets;
-mod types_gpu;
 
+pub mod adaptive_rays;
+pub mod bake;
 pub mod camera_viewer;
 pub mod compositing;
+pub mod control;
+pub mod diagnostics;
+pub mod exposure;
+#[cfg(feature = "egui")]
+pub mod inspector;
+pub mod light_ray_debug;
+pub mod line_of_sight;
+pub mod pipeline;
+pub mod pipeline_assets;
 pub mod render_layer;
 pub mod resource;
+pub mod scene_gizmos;
 pub mod types;
+pub mod types_gpu;
 pub mod util;
 
+pub use self::constants::{MAX_LIGHTS, MAX_OCCLUDERS};
+
 const WORKGROUP_SIZE: u32 = 8;
 
 pub struct BevyMagicLight2DPlugin;
@@ -49,13 +125,32 @@ pub struct BevyMagicLight2DPlugin;
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub struct LightPass2DRenderLabel;
 
+/// Systems that sync lights and occluders for the GI passes.
+///
+/// In the main world the set runs in `PostUpdate`, after transform
+/// propagation and visibility checks, and is followed by extraction into the
+/// render world (`ExtractSchedule`, where the set contains the extraction
+/// itself). Transforms of lights and occluders must be final before the set
+/// runs: systems mutating them in `Update`, or in `PostUpdate` ordered
+/// `.before(TransformSystems::Propagate)`, are lit in the same frame. Systems
+/// ordered `.after(GiExtractSet)` are only picked up on the next frame.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct GiExtractSet;
+
 impl Plugin for BevyMagicLight2DPlugin
 {
     fn build(&self, app: &mut App)
     {
         app.add_plugins((
             ExtractResourcePlugin::<GiTargetsWrapper>::default(),
+            ExtractResourcePlugin::<CameraTargets>::default(),
+            ExtractResourcePlugin::<GiPipelineStatus>::default(),
+            ExtractResourcePlugin::<GiStatus>::default(),
+            ExtractResourcePlugin::<GiActiveLights>::default(),
+            ExtractResourcePlugin::<LightBake>::default(),
+            ExtractResourcePlugin::<GiConvergence>::default(),
             Material2dPlugin::<PostProcessingMaterial>::default(),
+            Material2dPlugin::<UpscaleMaterial>::default(),
             bevy_egui::EguiPlugin::default(),
         ))
         .init_resource::<CameraTargets>()
@@ -63,6 +158,24 @@ impl Plugin for BevyMagicLight2DPlugin
         .init_resource::<BevyMagicLight2DSettings>()
         .init_resource::<ComputedTargetSizes>()
         .init_resource::<EmbeddedShaderDependencies>()
+        .init_resource::<GiPipelineStatus>()
+        .init_resource::<GiStatus>()
+        .init_resource::<GiActiveLights>()
+        .init_resource::<GiDiagnostics>()
+        .init_resource::<GiConvergence>()
+        .init_resource::<LightVisibility>()
+        .init_resource::<LightBake>()
+        .init_resource::<FrameStepControl>()
+        .init_resource::<GiUpdateClock>()
+        .init_resource::<GiWarmup>()
+        .init_resource::<GiExposure>()
+        .init_resource::<GiTargetTransition>()
+        .init_resource::<GiAdaptiveRays>()
+        .init_resource::<LightRayDebug>()
+        .init_resource::<LightModulator>()
+        .init_gizmo_group::<LightRayGizmos>()
+        .init_gizmo_group::<SceneGizmos>()
+        .add_message::<GiPipelineReady>()
         .add_systems(
             PreStartup,
             (
@@ -73,7 +186,86 @@ impl Plugin for BevyMagicLight2DPlugin
             )
                 .chain(),
         )
-        .add_systems(PreUpdate, handle_window_resize)
+        .add_systems(PreUpdate, (handle_window_resize, system_emit_pipeline_ready))
+        .add_systems(Update, (system_update_gi_diagnostics, system_update_exposure_readback))
+        .add_systems(PostUpdate, system_animate_skylight_masks.before(GiExtractSet))
+        .add_systems(
+            PostUpdate,
+            system_follow_attached_lights.before(TransformSystems::Propagate),
+        )
+        .add_systems(
+            PostUpdate,
+            (system_assign_unlit_layers, system_sync_unlit_camera).before(TransformSystems::Propagate),
+        )
+        .add_systems(
+            PostUpdate,
+            system_sync_shadow_casters
+                .after(TransformSystems::Propagate)
+                .before(GiExtractSet),
+        )
+        .configure_sets(
+            PostUpdate,
+            GiExtractSet
+                .after(TransformSystems::Propagate)
+                .after(VisibilitySystems::CheckVisibility),
+        )
+        .add_systems(PostUpdate, system_update_light_visibility.in_set(GiExtractSet))
+        .add_systems(
+            Update,
+            (system_setup_light_ray_gizmos, system_setup_scene_gizmos)
+                .run_if(resource_changed::<BevyMagicLight2DSettings>),
+        )
+        .add_systems(Update, system_pick_light_ray_point)
+        .add_systems(
+            PostUpdate,
+            system_draw_light_rays
+                .after(system_update_light_visibility)
+                .run_if(|debug: Res<LightRayDebug>| debug.point.is_some()),
+        )
+        .add_systems(
+            PostUpdate,
+            system_draw_scene_gizmos
+                .after(TransformSystems::Propagate)
+                .run_if(|settings: Res<BevyMagicLight2DSettings>| settings.scene_gizmos.is_some()),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                system_update_gi_warmup,
+                system_update_frame_step,
+                system_update_gi_clock,
+                system_update_gi_convergence,
+                system_update_light_modulator,
+                system_update_adaptive_rays,
+            )
+                .chain()
+                .in_set(GiExtractSet),
+        )
+        .add_systems(
+            PostUpdate,
+            (system_invalidate_light_bake, system_update_light_bake)
+                .chain()
+                .before(system_update_gi_clock)
+                .in_set(GiExtractSet),
+        )
+        .add_systems(
+            PreUpdate,
+            update_extra_lit_layer_targets
+                .run_if(resource_changed::<BevyMagicLight2DSettings>)
+                .after(handle_window_resize),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                update_post_processing_camera.run_if(resource_changed::<BevyMagicLight2DSettings>),
+                update_layer_cameras_hdr,
+                system_sync_upscale_output,
+                update_camera_msaa,
+                system_hide_composite_during_warmup,
+                warn_post_processing_camera_order,
+            )
+                .chain(),
+        )
         .add_systems(PostUpdate, 
             (
                 update_post_processing_material
@@ -82,25 +274,55 @@ impl Plugin for BevyMagicLight2DPlugin
                 update_post_processing_material
                     .run_if(resource_changed::<CameraTargets>)
                     .after(handle_window_resize),
+                update_post_processing_material
+                    .run_if(resource_changed::<BevyMagicLight2DSettings>)
+                    .after(handle_window_resize),
+                update_post_processing_material
+                    .run_if(resource_changed::<LightBake>)
+                    .after(system_update_light_bake),
             )
+        )
+        .add_systems(
+            PostUpdate,
+            (system_update_exposure, system_update_target_transition).after(update_post_processing_material),
         );
 
-        load_shader_library!(app, "shaders/gi_attenuation.wgsl");
-        load_shader_library!(app, "shaders/gi_camera.wgsl");
-        load_shader_library!(app, "shaders/gi_halton.wgsl");
-        load_shader_library!(app, "shaders/gi_math.wgsl");
-        load_shader_library!(app, "shaders/gi_post_processing.wgsl");
-        load_shader_library!(app, "shaders/gi_raymarch.wgsl");
-        load_shader_library!(app, "shaders/gi_sdf.wgsl");
-        load_shader_library!(app, "shaders/gi_ss_blend.wgsl");
-        load_shader_library!(app, "shaders/gi_ss_bounce.wgsl");
-        load_shader_library!(app, "shaders/gi_ss_filter.wgsl");
-        load_shader_library!(app, "shaders/gi_ss_probe.wgsl");
-        load_shader_library!(app, "shaders/gi_types.wgsl");
+        #[cfg(feature = "hot_reload_shaders")]
+        {
+            let asset_server = app.world().resource::<AssetServer>().clone();
+            if asset_server.get_source(pipeline_assets::SHADER_ASSET_SOURCE).is_err() {
+                log::error!(
+                    "`hot_reload_shaders` needs `HotReloadShadersPlugin` added before `DefaultPlugins`, the GI shaders won't load"
+                );
+            }
+            let shaders = pipeline_assets::SHADER_FILES
+                .iter()
+                .map(|file| pipeline_assets::load_shader(&asset_server, file))
+                .collect();
+            app.insert_resource(pipeline_assets::HotReloadShaders(shaders))
+                .add_systems(Update, pipeline_assets::system_log_shader_reloads);
+        }
+
+        #[cfg(not(feature = "hot_reload_shaders"))]
+        {
+            load_shader_library!(app, "shaders/gi_attenuation.wgsl");
+            load_shader_library!(app, "shaders/gi_camera.wgsl");
+            load_shader_library!(app, "shaders/gi_halton.wgsl");
+            load_shader_library!(app, "shaders/gi_math.wgsl");
+            load_shader_library!(app, "shaders/gi_post_processing.wgsl");
+            load_shader_library!(app, "shaders/gi_raymarch.wgsl");
+            load_shader_library!(app, "shaders/gi_sdf.wgsl");
+            load_shader_library!(app, "shaders/gi_ss_blend.wgsl");
+            load_shader_library!(app, "shaders/gi_ss_bounce.wgsl");
+            load_shader_library!(app, "shaders/gi_ss_filter.wgsl");
+            load_shader_library!(app, "shaders/gi_ss_probe.wgsl");
+            load_shader_library!(app, "shaders/gi_upscale.wgsl");
+            load_shader_library!(app, "shaders/gi_types.wgsl");
+        }
 
         let render_app = app.sub_app_mut(RenderApp);
         render_app
-            .add_systems(ExtractSchedule, system_extract_pipeline_assets)
+            .add_systems(ExtractSchedule, system_extract_pipeline_assets.in_set(GiExtractSet))
             .add_systems(
                 RenderStartup,
                 (
@@ -125,6 +347,22 @@ impl Plugin for BevyMagicLight2DPlugin
             bevy::render::graph::CameraDriverLabel,
         )
     }
+
+    fn finish(&self, app: &mut App)
+    {
+        // The render adapter is only available once the renderer is set up,
+        // the main app systems creating the targets run after this.
+        let formats = app
+            .get_sub_app(RenderApp)
+            .and_then(|render_app| render_app.world().get_resource::<RenderAdapter>())
+            .map(GiTargetFormats::detect)
+            .unwrap_or_default();
+
+        app.insert_resource(formats);
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.insert_resource(formats);
+        }
+    }
 }
 
 #[derive(Default)]
@@ -141,43 +379,131 @@ pub fn handle_window_resize(
     query_window: Query<&Window, With<PrimaryWindow>>,
 
         res_plugin_config:      Res<BevyMagicLight2DSettings>,
+        res_light_bake:         Res<LightBake>,
+        res_post_handles:       Res<PostProcessingHandles>,
     mut res_target_sizes:       ResMut<ComputedTargetSizes>,
     mut res_gi_targets_wrapper: ResMut<GiTargetsWrapper>,
     mut res_camera_targets:     ResMut<CameraTargets>,
+        res_target_formats:     Res<GiTargetFormats>,
+    mut res_target_transition:  ResMut<GiTargetTransition>,
+    mut res_frame_step:         ResMut<FrameStepControl>,
 
     mut window_resized_evr: MessageReader<WindowResized>,
+    mut last_scaling_params: Local<Option<TargetScalingParams>>,
 ) {
-    for _ in window_resized_evr.read() {
-        let window = query_window
-            .single()
-            .expect("Expected exactly one primary window");
+    // Targets are also rebuilt when the scaling params change at runtime,
+    // e.g. to trade SDF resolution for speed.
+    let resized = window_resized_evr.read().count() > 0;
+    let scaling_params = res_plugin_config.target_scaling_params;
+    let scaling_changed = last_scaling_params.is_some_and(|last| last != scaling_params);
+    *last_scaling_params = Some(scaling_params);
+    if !resized && !scaling_changed {
+        return;
+    }
 
-        *res_target_sizes =
-            ComputedTargetSizes::from_window(window, &res_plugin_config.target_scaling_params);
+    let window = query_window
+        .single()
+        .expect("Expected exactly one primary window");
 
-        if !res_target_sizes.is_valid() {
-            // Window might be minimized, skip updating resources.
-            return;
+    *res_target_sizes = ComputedTargetSizes::from_window(window, &scaling_params);
+
+    if !res_target_sizes.is_valid() {
+        // Window might be minimized, skip updating resources.
+        return;
+    }
+    
+    let _ = assets_mesh.insert(
+        res_post_handles.rect.id(),
+        Mesh::from(bevy::math::primitives::Rectangle::new(
+            res_target_sizes.primary_target_size.x,
+            res_target_sizes.primary_target_size.y,
+        )),
+    );
+
+    // A scaling change keeps the screen size, so the irradiance shown so far
+    // is faded out while the new targets converge.
+    let previous_irradiance = (scaling_changed && !resized)
+        .then(|| assets_material.get(res_post_handles.material.id()))
+        .flatten()
+        .map(|material| material.irradiance_image().clone());
+
+    // IMPORTANT: Update GI targets and camera targets BEFORE the material, so
+    // it references the correct texture handles. Images are replaced behind
+    // the existing handles, so repeated resizes don't accumulate image assets,
+    // and marking the wrapper changed still refreshes everything that depends
+    // on the targets.
+    match res_gi_targets_wrapper.targets {
+        Some(ref mut targets) => {
+            if previous_irradiance.is_some() {
+                // Fresh handles for the composited targets, the transition
+                // keeps the previous images alive until it ends.
+                targets.ss_blend_target = assets_image.reserve_handle();
+                targets.ss_filter_target = assets_image.reserve_handle();
+            }
+            targets.resize(&mut assets_image, &res_target_sizes, &res_target_formats)
         }
-        
-        let _ = assets_mesh.insert(
-            POST_PROCESSING_RECT.id(),
-            Mesh::from(bevy::math::primitives::Rectangle::new(
-                res_target_sizes.primary_target_size.x,
-                res_target_sizes.primary_target_size.y,
-            )),
-        );
+        None => {
+            res_gi_targets_wrapper.targets = Some(GiTargets::create(&mut assets_image, &res_target_sizes, &res_target_formats));
+        }
+    }
+    res_gi_targets_wrapper.set_changed();
+    res_camera_targets.update_handles(
+        &mut assets_image,
+        &res_target_sizes,
+        res_plugin_config.extra_lit_layers.len(),
+        res_plugin_config.hdr_layers,
+    );
+    log::debug!("GI targets resized, {} image assets", assets_image.len());
+
+    // Now point the post-processing material at the updated texture handles
+    update_post_processing_material_asset(
+        &mut assets_material,
+        &res_post_handles,
+        &res_camera_targets,
+        &res_gi_targets_wrapper,
+        &res_plugin_config,
+        &res_light_bake,
+    );
+
+    if let Some(previous_irradiance) = previous_irradiance {
+        let frames = res_target_sizes.reservoir_size(res_plugin_config.light_pass_params.reservoir_size);
+        res_target_transition.start(previous_irradiance, frames);
+        res_frame_step.request_temporal_reset();
+    }
+}
 
-        // IMPORTANT: Update GI targets and camera targets BEFORE recreating the material
-        // to ensure the post-processing material references the correct texture handles
-        *res_gi_targets_wrapper = GiTargetsWrapper{targets: Some(GiTargets::create(&mut assets_image, &res_target_sizes))};
-        res_camera_targets.update_handles(&mut assets_image, &res_target_sizes);
+/// Allocates or drops layer targets when
+/// [`BevyMagicLight2DSettings::extra_lit_layers`] changes.
+fn update_extra_lit_layer_targets(
+    mut assets_image: ResMut<Assets<Image>>,
+    mut camera_targets: ResMut<CameraTargets>,
+    res_plugin_config: Res<BevyMagicLight2DSettings>,
+    res_target_sizes: Res<ComputedTargetSizes>,
+) {
+    let extra_layer_count = res_plugin_config.extra_lit_layers.len().min(MAX_EXTRA_LIT_LAYERS);
+    let up_to_date = camera_targets.extra_targets.len() == extra_layer_count
+        && camera_targets.hdr == res_plugin_config.hdr_layers;
+    if up_to_date || !res_target_sizes.is_valid() {
+        return;
+    }
+    camera_targets.update_handles(
+        &mut assets_image,
+        &res_target_sizes,
+        res_plugin_config.extra_lit_layers.len(),
+        res_plugin_config.hdr_layers,
+    );
+}
 
-        // Now recreate the post-processing material with updated texture handles
-        let _ = assets_material.insert(
-            POST_PROCESSING_MATERIAL.id(),
-            PostProcessingMaterial::create(&res_camera_targets, &res_gi_targets_wrapper),
-        );
+/// Emits [`GiPipelineReady`] once the render world reports that all bind
+/// groups have been created.
+fn system_emit_pipeline_ready(
+    gi_pipeline_status: Res<GiPipelineStatus>,
+    mut ready_sent: Local<bool>,
+    mut ready_writer: MessageWriter<GiPipelineReady>,
+) {
+    if !*ready_sent && gi_pipeline_status.is_ready() {
+        *ready_sent = true;
+        ready_writer.write(GiPipelineReady);
     }
 }
 
@@ -204,10 +530,25 @@ impl render_graph::Node for LightPass2DNode
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
+        let light_bake = world.resource::<LightBake>();
+        if light_bake.is_baked() {
+            // Lighting is frozen in the baked image.
+            return Ok(());
+        }
+        if !world.resource::<LightPassPipelineAssets>().update_due {
+            // Throttled by `gi_update_hz`, the last results are composited.
+            return Ok(());
+        }
+
         if let Some(pipeline_bind_groups) = world.get_resource::<LightPassPipelineBindGroups>() {
             let pipeline_cache = world.resource::<PipelineCache>();
             let pipeline = world.resource::<LightPassPipeline>();
             let target_sizes = world.resource::<ComputedTargetSizes>();
+            let gi_assets = world.resource::<LightPassPipelineAssets>();
+            let gpu_images = world.resource::<RenderAssets<GpuImage>>();
+            let Some(gi_targets) = world.resource::<GiTargetsWrapper>().targets.as_ref() else {
+                return Ok(());
+            };
 
             if let (
                 Some(sdf_pipeline),
@@ -222,53 +563,106 @@ impl render_graph::Node for LightPass2DNode
                 pipeline_cache.get_compute_pipeline(pipeline.ss_blend_pipeline),
                 pipeline_cache.get_compute_pipeline(pipeline.ss_filter_pipeline),
             ) {
-                let sdf_w = target_sizes.sdf_target_usize.x;
-                let sdf_h = target_sizes.sdf_target_usize.y;
-
-                let mut pass =
-                    render_context
-                        .command_encoder()
-                        .begin_compute_pass(&ComputePassDescriptor { label: Some("light_pass_2d"), ..default() });
-
-                {
-                    let grid_w = sdf_w / WORKGROUP_SIZE;
-                    let grid_h = sdf_h / WORKGROUP_SIZE;
-                    pass.set_bind_group(0, &pipeline_bind_groups.sdf_bind_group, &[]);
-                    pass.set_pipeline(sdf_pipeline);
-                    pass.dispatch_workgroups(grid_w, grid_h, 1);
+                let diagnostics = render_context.diagnostic_recorder();
+                let probe_grid = target_sizes.probe_grid_usize / WORKGROUP_SIZE;
+
+                dispatch_gi_pass(
+                    render_context,
+                    &diagnostics,
+                    GiPass::Sdf,
+                    sdf_pipeline,
+                    &pipeline_bind_groups.sdf_bind_group,
+                    target_sizes.sdf_target_usize / WORKGROUP_SIZE,
+                );
+                dispatch_gi_pass(
+                    render_context,
+                    &diagnostics,
+                    GiPass::SsProbe,
+                    ss_probe_pipeline,
+                    &pipeline_bind_groups.ss_probe_bind_group,
+                    probe_grid,
+                );
+                // Disabled passes forward the previous pass's output, so
+                // later passes and the composite still see valid data.
+                if gi_assets.enable_bounce {
+                    dispatch_gi_pass(
+                        render_context,
+                        &diagnostics,
+                        GiPass::SsBounce,
+                        ss_bounce_pipeline,
+                        &pipeline_bind_groups.ss_bounce_bind_group,
+                        probe_grid,
+                    );
+                } else if let (Some(ss_probe), Some(ss_bounce)) = (
+                    gpu_images.get(&gi_targets.ss_probe_target),
+                    gpu_images.get(&gi_targets.ss_bounce_target),
+                ) {
+                    render_context.command_encoder().copy_texture_to_texture(
+                        ss_probe.texture.as_image_copy(),
+                        ss_bounce.texture.as_image_copy(),
+                        ss_probe.texture.size(),
+                    );
                 }
 
-                {
-                    let grid_w = target_sizes.probe_grid_usize.x / WORKGROUP_SIZE;
-                    let grid_h = target_sizes.probe_grid_usize.y / WORKGROUP_SIZE;
-                    pass.set_bind_group(0, &pipeline_bind_groups.ss_probe_bind_group, &[]);
-                    pass.set_pipeline(ss_probe_pipeline);
-                    pass.dispatch_workgroups(grid_w, grid_h, 1);
+                if gi_assets.enable_blend {
+                    dispatch_gi_pass(
+                        render_context,
+                        &diagnostics,
+                        GiPass::SsBlend,
+                        ss_blend_pipeline,
+                        &pipeline_bind_groups.ss_blend_bind_group,
+                        probe_grid,
+                    );
+                } else if let (Some(ss_bounce), Some(ss_blend)) = (
+                    gpu_images.get(&gi_targets.ss_bounce_target),
+                    gpu_images.get(&gi_targets.ss_blend_target),
+                ) {
+                    // Copy the atlas tile written this frame. The probe grid is
+                    // aligned to the work group size, so the last tiles may be
+                    // cut off by the atlas bounds.
+                    let mut source = ss_bounce.texture.as_image_copy();
+                    let tile_offset = current_atlas_tile_offset(gi_assets, target_sizes);
+                    source.origin = Origin3d { x: tile_offset.x, y: tile_offset.y, z: 0 };
+                    let atlas_size = ss_bounce.texture.size();
+                    let blend_size = ss_blend.texture.size();
+                    let copy_size = Extent3d {
+                        width:                 blend_size.width.min(atlas_size.width.saturating_sub(tile_offset.x)),
+                        height:                blend_size.height.min(atlas_size.height.saturating_sub(tile_offset.y)),
+                        depth_or_array_layers: 1,
+                    };
+                    if copy_size.width > 0 && copy_size.height > 0 {
+                        render_context.command_encoder().copy_texture_to_texture(
+                            source,
+                            ss_blend.texture.as_image_copy(),
+                            copy_size,
+                        );
+                    }
                 }
 
-                {
-                    let grid_w = target_sizes.probe_grid_usize.x / WORKGROUP_SIZE;
-                    let grid_h = target_sizes.probe_grid_usize.y / WORKGROUP_SIZE;
-                    pass.set_bind_group(0, &pipeline_bind_groups.ss_bounce_bind_group, &[]);
-                    pass.set_pipeline(ss_bounce_pipeline);
-                    pass.dispatch_workgroups(grid_w, grid_h, 1);
+                if gi_assets.enable_filter {
+                    dispatch_gi_pass(
+                        render_context,
+                        &diagnostics,
+                        GiPass::SsFilter,
+                        ss_filter_pipeline,
+                        &pipeline_bind_groups.ss_filter_bind_group,
+                        util::align_to_work_group_grid(target_sizes.primary_target_isize).as_uvec2() / WORKGROUP_SIZE,
+                    );
                 }
 
-                {
-                    let grid_w = target_sizes.probe_grid_usize.x / WORKGROUP_SIZE;
-                    let grid_h = target_sizes.probe_grid_usize.y / WORKGROUP_SIZE;
-                    pass.set_bind_group(0, &pipeline_bind_groups.ss_blend_bind_group, &[]);
-                    pass.set_pipeline(ss_blend_pipeline);
-                    pass.dispatch_workgroups(grid_w, grid_h, 1);
-                }
-
-                {
-                    let aligned = util::align_to_work_group_grid(target_sizes.primary_target_isize).as_uvec2();
-                    let grid_w = aligned.x / WORKGROUP_SIZE;
-                    let grid_h = aligned.y / WORKGROUP_SIZE;
-                    pass.set_bind_group(0, &pipeline_bind_groups.ss_filter_bind_group, &[]);
-                    pass.set_pipeline(ss_filter_pipeline);
-                    pass.dispatch_workgroups(grid_w, grid_h, 1);
+                if light_bake.is_copying() {
+                    if let (Some(ss_filter), Some(baked)) = (
+                        gpu_images.get(&gi_targets.ss_filter_target),
+                        light_bake.image().and_then(|image| gpu_images.get(image)),
+                    ) {
+                        render_context.command_encoder().copy_texture_to_texture(
+                            ss_filter.texture.as_image_copy(),
+                            baked.texture.as_image_copy(),
+                            baked.texture.size(),
+                        );
+                    } else {
+                        log::warn!("GI bake image not ready, baked lighting will be empty");
+                    }
                 }
             }
         } else {
@@ -279,6 +673,42 @@ impl render_graph::Node for LightPass2DNode
     }
 }
 
+/// Origin of the probe atlas tile written in the current frame, mirrors the
+/// tile selection in the probe and bounce shaders.
+fn current_atlas_tile_offset(gi_assets: &LightPassPipelineAssets, target_sizes: &ComputedTargetSizes) -> UVec2
+{
+    let params = gi_assets.light_pass_params.get();
+    let frame_index = params.frame_counter.max(0) as u32 % params.reservoir_size.max(1);
+    let probe_size = target_sizes.probe_size.max(UVec2::ONE);
+    let atlas_tile = UVec2::new(frame_index % probe_size.x, frame_index / probe_size.x);
+    atlas_tile * target_sizes.probe_grid_usize
+}
+
+/// Dispatches a single GI compute pass in its own compute pass, wrapped in a
+/// render diagnostics time span so it can be timed on the GPU.
+fn dispatch_gi_pass(
+    render_context: &mut RenderContext,
+    diagnostics: &impl RecordDiagnostics,
+    gi_pass: GiPass,
+    pipeline: &ComputePipeline,
+    bind_group: &BindGroup,
+    workgroups: UVec2,
+)
+{
+    let command_encoder = render_context.command_encoder();
+    let time_span = diagnostics.time_span(command_encoder, gi_pass.span_name());
+    {
+        let mut pass = command_encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some(gi_pass.span_name()),
+            ..default()
+        });
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.set_pipeline(pipeline);
+        pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+    }
+    time_span.end(command_encoder);
+}
+
 // RenderStartup initialization functions for Bevy 0.17
 fn init_light_pass_pipeline(mut commands: Commands)
 {
@@ -301,6 +731,9 @@ fn update_post_processing_material(
     mut materials: ResMut<Assets<PostProcessingMaterial>>,
     camera_targets: Res<CameraTargets>,
     gi_targets_wrapper: Res<GiTargetsWrapper>,
+    settings: Res<BevyMagicLight2DSettings>,
+    light_bake: Res<LightBake>,
+    post_handles: Res<PostProcessingHandles>,
 ) {
     log::debug!("Updating post-processing material due to GI targets change");
     
@@ -311,14 +744,23 @@ fn update_post_processing_material(
     }
     
     // Ensure camera targets are initialized
-    if camera_targets.floor_target.is_none() || camera_targets.walls_target.is_none() || camera_targets.objects_target.is_none() {
+    if camera_targets.floor_target.is_none()
+        || camera_targets.walls_target.is_none()
+        || camera_targets.objects_target.is_none()
+        || camera_targets.unlit_target.is_none()
+    {
         log::warn!("Camera targets not fully initialized, skipping material update");
         return;
     }
     
-    // Recreate the material with updated texture handles
-    let updated_material = PostProcessingMaterial::create(&camera_targets, &gi_targets_wrapper);
-    let _ = materials.insert(POST_PROCESSING_MATERIAL.id(), updated_material);
+    update_post_processing_material_asset(
+        &mut materials,
+        &post_handles,
+        &camera_targets,
+        &gi_targets_wrapper,
+        &settings,
+        &light_bake,
+    );
     
     log::debug!("Post-processing material updated successfully");
 }
@@ -4,26 +4,31 @@ use bevy::asset::RenderAssetUsages;
 use bevy::render::extract_resource::ExtractResource;
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_resource::*;
-use bevy::render::renderer::RenderDevice;
-use bevy::render::texture::GpuImage;
+use bevy::render::renderer::{RenderAdapter, RenderDevice};
+use bevy::render::texture::{FallbackImageZero, GpuImage};
+use bevy::shader::ShaderDefVal;
 
-use crate::gi::pipeline_assets::{load_embedded_shader, LightPassPipelineAssets};
-use crate::gi::resource::ComputedTargetSizes;
+use crate::gi::blue_noise::create_blue_noise_image;
+use crate::gi::compositing::CameraTargets;
+use crate::gi::constants::BLUE_NOISE_SIZE;
+use crate::gi::pipeline_assets::{load_shader, LightPassPipelineAssets};
+use crate::gi::resource::{ComputedTargetSizes, GiPipelineStatus, GiStatus};
 use crate::gi::types_gpu::{
     GpuCameraParams,
     GpuLightOccluderBuffer,
     GpuLightPassParams,
     GpuLightSourceBuffer,
     GpuProbeDataBuffer,
+    GpuRectLightSourceBuffer,
     GpuSkylightMaskBuffer,
+    GpuTileOccluderGrid,
 };
 
-const SDF_TARGET_FORMAT: TextureFormat = TextureFormat::R16Float;
-const SS_PROBE_TARGET_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
-const SS_BOUNCE_TARGET_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
-const SS_BLEND_TARGET_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
-const SS_FILTER_TARGET_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
-const SS_POSE_TARGET_FORMAT: TextureFormat = TextureFormat::Rg32Float;
+/// Storage formats of each target, most precise first. Later formats are
+/// fallbacks for adapters that can't use the first one as a storage texture.
+const SDF_TARGET_FORMATS: [TextureFormat; 2] = [TextureFormat::R16Float, TextureFormat::R32Float];
+const SS_TARGET_FORMATS: [TextureFormat; 2] = [TextureFormat::Rgba32Float, TextureFormat::Rgba16Float];
+const SS_POSE_TARGET_FORMATS: [TextureFormat; 2] = [TextureFormat::Rg32Float, TextureFormat::Rgba16Float];
 
 const SDF_PIPELINE_ENTRY: &str = "main";
 const SS_PROBE_PIPELINE_ENTRY: &str = "main";
@@ -47,65 +52,77 @@ pub struct GiTargets
     pub ss_blend_target:  Handle<Image>,
     pub ss_filter_target: Handle<Image>,
     pub ss_pose_target:   Handle<Image>,
+    /// Tiled blue noise jittering the indirect rays, never resized.
+    pub blue_noise:       Handle<Image>,
 }
 
 impl GiTargets
 {
-    pub fn create(images: &mut Assets<Image>, sizes: &ComputedTargetSizes) -> Self
+    pub fn create(
+        images: &mut Assets<Image>,
+        sizes: &ComputedTargetSizes,
+        formats: &GiTargetFormats,
+    ) -> Self
+    {
+        let targets = Self {
+            sdf_target:       images.reserve_handle(),
+            ss_probe_target:  images.reserve_handle(),
+            ss_bounce_target: images.reserve_handle(),
+            ss_blend_target:  images.reserve_handle(),
+            ss_filter_target: images.reserve_handle(),
+            ss_pose_target:   images.reserve_handle(),
+            blue_noise:       images.add(create_blue_noise_image(BLUE_NOISE_SIZE)),
+        };
+        targets.resize(images, sizes, formats);
+        targets
+    }
+
+    /// Replaces the images behind the existing handles, so recreating the
+    /// targets on resize doesn't allocate new assets.
+    pub fn resize(
+        &self,
+        images: &mut Assets<Image>,
+        sizes: &ComputedTargetSizes,
+        formats: &GiTargetFormats,
+    )
     {
         let sdf_tex = create_texture_2d(
             sizes.sdf_target_usize.into(),
-            SDF_TARGET_FORMAT,
+            formats.sdf,
             ImageFilterMode::Linear,
         );
         let ss_probe_tex = create_texture_2d(
             sizes.primary_target_usize.into(),
-            SS_PROBE_TARGET_FORMAT,
+            formats.probe,
             ImageFilterMode::Nearest,
         );
         let ss_bounce_tex = create_texture_2d(
             sizes.primary_target_usize.into(),
-            SS_BOUNCE_TARGET_FORMAT,
+            formats.probe,
             ImageFilterMode::Nearest,
         );
         let ss_blend_tex = create_texture_2d(
             sizes.probe_grid_usize.into(),
-            SS_BLEND_TARGET_FORMAT,
+            formats.probe,
             ImageFilterMode::Nearest,
         );
         let ss_filter_tex = create_texture_2d(
             sizes.primary_target_usize.into(),
-            SS_FILTER_TARGET_FORMAT,
+            formats.probe,
             ImageFilterMode::Nearest,
         );
         let ss_pose_tex = create_texture_2d(
             sizes.primary_target_usize.into(),
-            SS_POSE_TARGET_FORMAT,
+            formats.pose,
             ImageFilterMode::Nearest,
         );
 
-        let sdf_target: Handle<Image> = images.reserve_handle();
-        let ss_probe_target: Handle<Image> = images.reserve_handle();
-        let ss_bounce_target: Handle<Image> = images.reserve_handle();
-        let ss_blend_target: Handle<Image> = images.reserve_handle();
-        let ss_filter_target: Handle<Image> = images.reserve_handle();
-        let ss_pose_target: Handle<Image> = images.reserve_handle();
-
-        let _ = images.insert(sdf_target.id(), sdf_tex);
-        let _ = images.insert(ss_probe_target.id(), ss_probe_tex);
-        let _ = images.insert(ss_bounce_target.id(), ss_bounce_tex);
-        let _ = images.insert(ss_blend_target.id(), ss_blend_tex);
-        let _ = images.insert(ss_filter_target.id(), ss_filter_tex);
-        let _ = images.insert(ss_pose_target.id(), ss_pose_tex);
-
-        Self {
-            sdf_target,
-            ss_probe_target,
-            ss_bounce_target,
-            ss_blend_target,
-            ss_filter_target,
-            ss_pose_target,
-        }
+        let _ = images.insert(self.sdf_target.id(), sdf_tex);
+        let _ = images.insert(self.ss_probe_target.id(), ss_probe_tex);
+        let _ = images.insert(self.ss_bounce_target.id(), ss_bounce_tex);
+        let _ = images.insert(self.ss_blend_target.id(), ss_blend_tex);
+        let _ = images.insert(self.ss_filter_target.id(), ss_filter_tex);
+        let _ = images.insert(self.ss_pose_target.id(), ss_pose_tex);
     }
 }
 
@@ -140,7 +157,10 @@ fn create_texture_2d(size: (u32, u32), format: TextureFormat, filter: ImageFilte
     );
 
     image.texture_descriptor.usage =
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+        TextureUsages::COPY_SRC
+            | TextureUsages::COPY_DST
+            | TextureUsages::STORAGE_BINDING
+            | TextureUsages::TEXTURE_BINDING;
 
     image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
         mag_filter: filter,
@@ -154,15 +174,236 @@ fn create_texture_2d(size: (u32, u32), format: TextureFormat, filter: ImageFilte
     image
 }
 
+/// Persistent copy of the filtered irradiance, see
+/// [`LightBake`](crate::gi::bake::LightBake).
+pub(crate) fn create_baked_irradiance_image(size: UVec2, formats: &GiTargetFormats) -> Image
+{
+    create_texture_2d(size.into(), formats.probe, ImageFilterMode::Nearest)
+}
+
+/// Storage texture formats of the GI targets.
+///
+/// The preferred formats can't be used as storage textures on every backend,
+/// e.g. some web and mobile adapters, in that case lower precision fallbacks
+/// are picked when the plugin is finished and the shaders are compiled to
+/// match.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GiTargetFormats
+{
+    pub sdf:   TextureFormat,
+    /// Format of the probe, bounce, blend and filter targets.
+    pub probe: TextureFormat,
+    pub pose:  TextureFormat,
+}
+
+impl Default for GiTargetFormats
+{
+    fn default() -> Self
+    {
+        Self {
+            sdf:   SDF_TARGET_FORMATS[0],
+            probe: SS_TARGET_FORMATS[0],
+            pose:  SS_POSE_TARGET_FORMATS[0],
+        }
+    }
+}
+
+impl GiTargetFormats
+{
+    pub fn detect(adapter: &RenderAdapter) -> Self
+    {
+        let supports = |format: TextureFormat, flags: TextureFormatFeatureFlags| {
+            let features = adapter.get_texture_format_features(format);
+            features.allowed_usages.contains(TextureUsages::STORAGE_BINDING)
+                && features.flags.contains(flags)
+        };
+
+        Self {
+            // The SDF pass reads and writes its target.
+            sdf:   select_storage_format("SDF", &SDF_TARGET_FORMATS, |f| {
+                supports(f, TextureFormatFeatureFlags::STORAGE_READ_WRITE)
+            }),
+            probe: select_storage_format("probe", &SS_TARGET_FORMATS, |f| {
+                supports(f, TextureFormatFeatureFlags::empty())
+            }),
+            pose:  select_storage_format("pose", &SS_POSE_TARGET_FORMATS, |f| {
+                supports(f, TextureFormatFeatureFlags::empty())
+            }),
+        }
+    }
+
+    /// Shader defs selecting the storage texture declarations in the GI
+    /// shaders.
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal>
+    {
+        let mut shader_defs = vec![];
+        if self.sdf == TextureFormat::R32Float {
+            shader_defs.push("GI_SDF_R32F".into());
+        }
+        if self.probe == TextureFormat::Rgba16Float {
+            shader_defs.push("GI_TARGETS_RGBA16F".into());
+        }
+        if self.pose == TextureFormat::Rgba16Float {
+            shader_defs.push("GI_POSE_RGBA16F".into());
+        }
+        shader_defs
+    }
+}
+
+fn select_storage_format(
+    target: &str,
+    candidates: &[TextureFormat],
+    supported: impl Fn(TextureFormat) -> bool,
+) -> TextureFormat
+{
+    let preferred = candidates[0];
+    match candidates.iter().copied().find(|format| supported(*format)) {
+        Some(format) if format == preferred => format,
+        Some(format) => {
+            log::warn!(
+                "GI {} target format {:?} is not supported as a storage texture, falling back to {:?}",
+                target,
+                preferred,
+                format,
+            );
+            format
+        }
+        None => {
+            log::error!(
+                "GI {} target needs one of {:?} as a storage texture, none is supported by this adapter. Lighting will not render.",
+                target,
+                candidates,
+            );
+            preferred
+        }
+    }
+}
+
 #[rustfmt::skip]
 pub fn system_setup_gi_pipeline(
     mut images:          ResMut<Assets<Image>>,
     mut targets_wrapper: ResMut<GiTargetsWrapper>,
     targets_sizes:   Res<ComputedTargetSizes>,
+    target_formats:  Res<GiTargetFormats>,
 ) {
-    targets_wrapper.targets = Some(GiTargets::create(&mut images, &targets_sizes));
+    targets_wrapper.targets = Some(GiTargets::create(&mut images, &targets_sizes, &target_formats));
 }
 
+/// Declares the bindings of a pass. The same enum indexes both the layout and
+/// the bind group, and `entries` only accepts exactly one entry per binding,
+/// so adding a binding without updating both sides fails to compile.
+macro_rules! pass_bindings {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $index:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[repr(u32)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum $name
+        {
+            $($variant = $index),*
+        }
+
+        impl $name
+        {
+            pub const COUNT: usize = [$($name::$variant),*].len();
+
+            fn entries<T>(entries: [T; Self::COUNT]) -> [T; Self::COUNT]
+            {
+                entries
+            }
+        }
+    };
+}
+
+pass_bindings! {
+    /// Bindings of `gi_sdf.wgsl`.
+    SdfBinding {
+        Camera    = 0,
+        Occluders = 1,
+        Sdf       = 2,
+        TileGrid  = 3,
+    }
+}
+
+pass_bindings! {
+    /// Bindings of `gi_ss_probe.wgsl`.
+    SsProbeBinding {
+        Camera           = 0,
+        LightPassParams  = 1,
+        Probes           = 2,
+        SkylightMasks    = 3,
+        Lights           = 4,
+        Sdf              = 5,
+        SdfSampler       = 6,
+        ProbeOut         = 7,
+        Occluders        = 8,
+        RectLights       = 9,
+        HeightMap        = 10,
+        HeightMapSampler = 11,
+        TileGrid         = 12,
+    }
+}
+
+pass_bindings! {
+    /// Bindings of `gi_ss_bounce.wgsl`.
+    SsBounceBinding {
+        Camera          = 0,
+        LightPassParams = 1,
+        Sdf             = 2,
+        SdfSampler      = 3,
+        ProbeIn         = 4,
+        BounceOut       = 5,
+        Floor           = 6,
+        FloorSampler    = 7,
+        Objects         = 8,
+        ObjectsSampler  = 9,
+        BlueNoise       = 10,
+        Lights          = 11,
+        RectLights      = 12,
+    }
+}
+
+pass_bindings! {
+    /// Bindings of `gi_ss_blend.wgsl`.
+    SsBlendBinding {
+        Camera          = 0,
+        LightPassParams = 1,
+        Probes          = 2,
+        Sdf             = 3,
+        SdfSampler      = 4,
+        BounceIn        = 5,
+        BlendOut        = 6,
+    }
+}
+
+pass_bindings! {
+    /// Bindings of `gi_ss_filter.wgsl`.
+    SsFilterBinding {
+        Camera          = 0,
+        LightPassParams = 1,
+        Probes          = 2,
+        Sdf             = 3,
+        SdfSampler      = 4,
+        BlendIn         = 5,
+        FilterOut       = 6,
+        PoseOut         = 7,
+    }
+}
+
+/// Bind group layouts and pipelines of the GI passes.
+///
+/// Binding 0 is always `CameraParams` (uniform) and, except for the SDF pass,
+/// binding 1 is `LightPassParams` (uniform), see the per-pass binding enums
+/// such as [`SsBlendBinding`]. The blend layout, which reads the probes, is:
+///
+/// | Binding | Resource                                  |
+/// |---------|-------------------------------------------|
+/// | 2       | `ProbeDataBuffer`, read-only storage      |
+/// | 3, 4    | SDF texture and sampler                   |
+/// | 5       | Bounce probe atlas, read storage texture  |
+/// | 6       | Blended probes, write storage texture     |
+///
+/// WGSL types are in `bevy_magic_light_2d::gi_types` and
+/// `bevy_magic_light_2d::gi_camera`.
 #[derive(Resource)]
 pub struct LightPassPipeline
 {
@@ -178,249 +419,371 @@ pub struct LightPassPipeline
     pub ss_filter_pipeline:          CachedComputePipelineId,
 }
 
+/// Why the bind groups of the compute passes weren't created this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GiNotReady
+{
+    /// The GI targets haven't been created yet.
+    Targets,
+    /// A buffer of `LightPassPipelineAssets` isn't bound yet.
+    Buffer(&'static str),
+    /// A texture isn't prepared on the GPU yet.
+    Texture(&'static str),
+}
+
 pub fn system_queue_bind_groups(
     mut commands: Commands,
     pipeline: Res<LightPassPipeline>,
     gpu_images: Res<RenderAssets<GpuImage>>,
     targets_wrapper: Res<GiTargetsWrapper>,
+    camera_targets: Res<CameraTargets>,
     gi_compute_assets: Res<LightPassPipelineAssets>,
+    gi_pipeline_status: Res<GiPipelineStatus>,
+    gi_status: Res<GiStatus>,
     render_device: Res<RenderDevice>,
+    fallback_image_zero: Res<FallbackImageZero>,
 )
 {
-    // Debug: Check if targets are initialized
-    if targets_wrapper.targets.is_none() {
-        log::warn!("GI targets not initialized - skipping bind group creation");
-        return;
+    // Cleared unless the bind groups are created below.
+    gi_status.set_bind_groups_ready(false);
+    gi_status.set_targets_initialized(targets_wrapper.targets.is_some());
+
+    let bind_groups = create_bind_groups(
+        &pipeline,
+        &gpu_images,
+        &targets_wrapper,
+        &camera_targets,
+        &gi_compute_assets,
+        &render_device,
+        &fallback_image_zero,
+    );
+    match bind_groups {
+        Ok(bind_groups) => {
+            commands.insert_resource(bind_groups);
+            if !gi_pipeline_status.is_ready() {
+                gi_pipeline_status.mark_ready();
+            }
+            gi_status.set_bind_groups_ready(true);
+        }
+        // Scene color targets are regular images and may take a few frames
+        // to be prepared.
+        Err(reason @ GiNotReady::Texture(_)) => {
+            log::debug!("Skipping GI bind group creation: {:?} not ready", reason);
+        }
+        // Normal during initialization.
+        Err(reason) => {
+            log::warn!("Skipping GI bind group creation: {:?} not ready", reason);
+        }
     }
+}
 
-    // Check buffer binding availability
-    if let (
-        Some(light_sources),
-        Some(light_occluders),
-        Some(camera_params),
-        Some(gi_state),
-        Some(probes),
-        Some(skylight_masks),
-    ) = (
-        gi_compute_assets.light_sources.binding(),
-        gi_compute_assets.light_occluders.binding(),
-        gi_compute_assets.camera_params.binding(),
-        gi_compute_assets.light_pass_params.binding(),
-        gi_compute_assets.probes.binding(),
-        gi_compute_assets.skylight_masks.binding(),
-    ) {
-        let targets = targets_wrapper
-            .targets
-            .as_ref()
-            .expect("Targets should be initialized");
-
-        let sdf_view_image = gpu_images
-            .get(&targets.sdf_target)
-            .expect("SDF target not found");
-        let ss_probe_image = gpu_images
-            .get(&targets.ss_probe_target)
-            .expect("SS Probe target not found");
-        let ss_bounce_image = gpu_images
-            .get(&targets.ss_bounce_target)
-            .expect("SS Bounce target not found");
-        let ss_blend_image = gpu_images
-            .get(&targets.ss_blend_target)
-            .expect("SS Blend target not found");
-        let ss_filter_image = gpu_images
-            .get(&targets.ss_filter_target)
-            .expect("SS Filter target not found");
-        let ss_pose_image = gpu_images
-            .get(&targets.ss_pose_target)
-            .expect("SS Pose target not found");
-
-        let sdf_bind_group = render_device.create_bind_group(
-            "gi_sdf_bind_group",
-            &pipeline.sdf_bind_group_layout,
-            &[
-                BindGroupEntry {
-                    binding:  0,
-                    resource: camera_params.clone(),
-                },
-                BindGroupEntry {
-                    binding:  1,
-                    resource: light_occluders.clone(),
-                },
-                BindGroupEntry {
-                    binding:  2,
-                    resource: BindingResource::TextureView(&sdf_view_image.texture_view),
-                },
-            ],
-        );
+fn create_bind_groups(
+    pipeline: &LightPassPipeline,
+    gpu_images: &RenderAssets<GpuImage>,
+    targets_wrapper: &GiTargetsWrapper,
+    camera_targets: &CameraTargets,
+    gi_compute_assets: &LightPassPipelineAssets,
+    render_device: &RenderDevice,
+    fallback_image_zero: &FallbackImageZero,
+) -> Result<LightPassPipelineBindGroups, GiNotReady>
+{
+    let targets = targets_wrapper.targets.as_ref().ok_or(GiNotReady::Targets)?;
 
-        let ss_probe_bind_group = render_device.create_bind_group(
-            "gi_ss_probe_bind_group",
-            &pipeline.ss_probe_bind_group_layout,
-            &[
-                BindGroupEntry {
-                    binding:  0,
-                    resource: camera_params.clone(),
-                },
-                BindGroupEntry {
-                    binding:  1,
-                    resource: gi_state.clone(),
-                },
-                BindGroupEntry {
-                    binding:  2,
-                    resource: probes.clone(),
-                },
-                BindGroupEntry {
-                    binding:  3,
-                    resource: skylight_masks.clone(),
-                },
-                BindGroupEntry {
-                    binding:  4,
-                    resource: light_sources.clone(),
-                },
-                BindGroupEntry {
-                    binding:  5,
-                    resource: BindingResource::TextureView(&sdf_view_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  6,
-                    resource: BindingResource::Sampler(&sdf_view_image.sampler),
-                },
-                BindGroupEntry {
-                    binding:  7,
-                    resource: BindingResource::TextureView(&ss_probe_image.texture_view),
-                },
-            ],
-        );
+    let light_sources = gi_compute_assets
+        .light_sources
+        .binding()
+        .ok_or(GiNotReady::Buffer("light_sources"))?;
+    let rect_lights = gi_compute_assets
+        .rect_lights
+        .binding()
+        .ok_or(GiNotReady::Buffer("rect_lights"))?;
+    let light_occluders = gi_compute_assets
+        .light_occluders
+        .binding()
+        .ok_or(GiNotReady::Buffer("light_occluders"))?;
+    let camera_params = gi_compute_assets
+        .camera_params
+        .binding()
+        .ok_or(GiNotReady::Buffer("camera_params"))?;
+    let gi_state = gi_compute_assets
+        .light_pass_params
+        .binding()
+        .ok_or(GiNotReady::Buffer("light_pass_params"))?;
+    let probes = gi_compute_assets
+        .probes
+        .binding()
+        .ok_or(GiNotReady::Buffer("probes"))?;
+    let skylight_masks = gi_compute_assets
+        .skylight_masks
+        .binding()
+        .ok_or(GiNotReady::Buffer("skylight_masks"))?;
+    let tile_grid = gi_compute_assets
+        .tile_grid
+        .binding()
+        .ok_or(GiNotReady::Buffer("tile_grid"))?;
 
-        let ss_bounce_bind_group = render_device.create_bind_group(
-            "gi_bounce_bind_group",
-            &pipeline.ss_bounce_bind_group_layout,
-            &[
-                BindGroupEntry {
-                    binding:  0,
-                    resource: camera_params.clone(),
-                },
-                BindGroupEntry {
-                    binding:  1,
-                    resource: gi_state.clone(),
-                },
-                BindGroupEntry {
-                    binding:  2,
-                    resource: BindingResource::TextureView(&sdf_view_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  3,
-                    resource: BindingResource::Sampler(&sdf_view_image.sampler),
-                },
-                BindGroupEntry {
-                    binding:  4,
-                    resource: BindingResource::TextureView(&ss_probe_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  5,
-                    resource: BindingResource::TextureView(&ss_bounce_image.texture_view),
-                },
-            ],
-        );
+    let gpu_image = |handle: &Handle<Image>, name: &'static str| {
+        gpu_images.get(handle).ok_or(GiNotReady::Texture(name))
+    };
+    let sdf_view_image = gpu_image(&targets.sdf_target, "sdf_target")?;
+    let ss_probe_image = gpu_image(&targets.ss_probe_target, "ss_probe_target")?;
+    let ss_bounce_image = gpu_image(&targets.ss_bounce_target, "ss_bounce_target")?;
+    let ss_blend_image = gpu_image(&targets.ss_blend_target, "ss_blend_target")?;
+    let ss_filter_image = gpu_image(&targets.ss_filter_target, "ss_filter_target")?;
+    let ss_pose_image = gpu_image(&targets.ss_pose_target, "ss_pose_target")?;
 
-        let ss_blend_bind_group = render_device.create_bind_group(
-            "gi_blend_bind_group",
-            &pipeline.ss_blend_bind_group_layout,
-            &[
-                BindGroupEntry {
-                    binding:  0,
-                    resource: camera_params.clone(),
-                },
-                BindGroupEntry {
-                    binding:  1,
-                    resource: gi_state.clone(),
-                },
-                BindGroupEntry {
-                    binding:  2,
-                    resource: probes.clone(),
-                },
-                BindGroupEntry {
-                    binding:  3,
-                    resource: BindingResource::TextureView(&sdf_view_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  4,
-                    resource: BindingResource::Sampler(&sdf_view_image.sampler),
-                },
-                BindGroupEntry {
-                    binding:  5,
-                    resource: BindingResource::TextureView(&ss_bounce_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  6,
-                    resource: BindingResource::TextureView(&ss_blend_image.texture_view),
-                },
-            ],
-        );
+    let scene_image = |handle: &Option<Handle<Image>>, name: &'static str| {
+        handle.as_ref().and_then(|h| gpu_images.get(h)).ok_or(GiNotReady::Texture(name))
+    };
+    let floor_image = scene_image(&camera_targets.floor_target, "floor_target")?;
+    let objects_image = scene_image(&camera_targets.objects_target, "objects_target")?;
+    let blue_noise_image = gpu_image(&targets.blue_noise, "blue_noise")?;
 
-        let ss_filter_bind_group = render_device.create_bind_group(
-            "ss_filter_bind_group",
-            &pipeline.ss_filter_bind_group_layout,
-            &[
-                BindGroupEntry {
-                    binding:  0,
-                    resource: camera_params.clone(),
-                },
-                BindGroupEntry {
-                    binding:  1,
-                    resource: gi_state.clone(),
-                },
-                BindGroupEntry {
-                    binding:  2,
-                    resource: probes.clone(),
-                },
-                BindGroupEntry {
-                    binding:  3,
-                    resource: BindingResource::TextureView(&sdf_view_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  4,
-                    resource: BindingResource::Sampler(&sdf_view_image.sampler),
-                },
-                BindGroupEntry {
-                    binding:  5,
-                    resource: BindingResource::TextureView(&ss_blend_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  6,
-                    resource: BindingResource::TextureView(&ss_filter_image.texture_view),
-                },
-                BindGroupEntry {
-                    binding:  7,
-                    resource: BindingResource::TextureView(&ss_pose_image.texture_view),
-                },
-            ],
-        );
+    // A zero height map is flat and casts no shadows, it stands in
+    // while no height map is set or it isn't prepared yet.
+    let height_map_image = gi_compute_assets
+        .height_map
+        .as_ref()
+        .and_then(|h| gpu_images.get(h))
+        .unwrap_or(&**fallback_image_zero);
 
-        commands.insert_resource(LightPassPipelineBindGroups {
-            sdf_bind_group,
-            ss_probe_bind_group,
-            ss_bounce_bind_group,
-            ss_blend_bind_group,
-            ss_filter_bind_group,
-        });
-    } else {
-        // Some buffers aren't bound yet - this is normal during initialization
-        log::warn!("Some GPU buffers are not bound - skipping bind group creation");
-    }
+    let sdf_bind_group = render_device.create_bind_group(
+        "gi_sdf_bind_group",
+        &pipeline.sdf_bind_group_layout,
+        &SdfBinding::entries([
+            BindGroupEntry {
+                binding:  SdfBinding::Camera as u32,
+                resource: camera_params.clone(),
+            },
+            BindGroupEntry {
+                binding:  SdfBinding::Occluders as u32,
+                resource: light_occluders.clone(),
+            },
+            BindGroupEntry {
+                binding:  SdfBinding::Sdf as u32,
+                resource: BindingResource::TextureView(&sdf_view_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SdfBinding::TileGrid as u32,
+                resource: tile_grid.clone(),
+            },
+        ]),
+    );
+
+    let ss_probe_bind_group = render_device.create_bind_group(
+        "gi_ss_probe_bind_group",
+        &pipeline.ss_probe_bind_group_layout,
+        &SsProbeBinding::entries([
+            BindGroupEntry {
+                binding:  SsProbeBinding::Camera as u32,
+                resource: camera_params.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::LightPassParams as u32,
+                resource: gi_state.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::Probes as u32,
+                resource: probes.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::SkylightMasks as u32,
+                resource: skylight_masks.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::Lights as u32,
+                resource: light_sources.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::Sdf as u32,
+                resource: BindingResource::TextureView(&sdf_view_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::SdfSampler as u32,
+                resource: BindingResource::Sampler(&sdf_view_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::ProbeOut as u32,
+                resource: BindingResource::TextureView(&ss_probe_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::Occluders as u32,
+                resource: light_occluders.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::RectLights as u32,
+                resource: rect_lights.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::HeightMap as u32,
+                resource: BindingResource::TextureView(&height_map_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::HeightMapSampler as u32,
+                resource: BindingResource::Sampler(&height_map_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsProbeBinding::TileGrid as u32,
+                resource: tile_grid,
+            },
+        ]),
+    );
+
+    let ss_bounce_bind_group = render_device.create_bind_group(
+        "gi_bounce_bind_group",
+        &pipeline.ss_bounce_bind_group_layout,
+        &SsBounceBinding::entries([
+            BindGroupEntry {
+                binding:  SsBounceBinding::Camera as u32,
+                resource: camera_params.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::LightPassParams as u32,
+                resource: gi_state.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::Sdf as u32,
+                resource: BindingResource::TextureView(&sdf_view_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::SdfSampler as u32,
+                resource: BindingResource::Sampler(&sdf_view_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::ProbeIn as u32,
+                resource: BindingResource::TextureView(&ss_probe_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::BounceOut as u32,
+                resource: BindingResource::TextureView(&ss_bounce_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::Floor as u32,
+                resource: BindingResource::TextureView(&floor_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::FloorSampler as u32,
+                resource: BindingResource::Sampler(&floor_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::Objects as u32,
+                resource: BindingResource::TextureView(&objects_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::ObjectsSampler as u32,
+                resource: BindingResource::Sampler(&objects_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::BlueNoise as u32,
+                resource: BindingResource::TextureView(&blue_noise_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::Lights as u32,
+                resource: light_sources.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsBounceBinding::RectLights as u32,
+                resource: rect_lights.clone(),
+            },
+        ]),
+    );
+
+    let ss_blend_bind_group = render_device.create_bind_group(
+        "gi_blend_bind_group",
+        &pipeline.ss_blend_bind_group_layout,
+        &SsBlendBinding::entries([
+            BindGroupEntry {
+                binding:  SsBlendBinding::Camera as u32,
+                resource: camera_params.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsBlendBinding::LightPassParams as u32,
+                resource: gi_state.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsBlendBinding::Probes as u32,
+                resource: probes.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsBlendBinding::Sdf as u32,
+                resource: BindingResource::TextureView(&sdf_view_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBlendBinding::SdfSampler as u32,
+                resource: BindingResource::Sampler(&sdf_view_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsBlendBinding::BounceIn as u32,
+                resource: BindingResource::TextureView(&ss_bounce_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsBlendBinding::BlendOut as u32,
+                resource: BindingResource::TextureView(&ss_blend_image.texture_view),
+            },
+        ]),
+    );
+
+    let ss_filter_bind_group = render_device.create_bind_group(
+        "ss_filter_bind_group",
+        &pipeline.ss_filter_bind_group_layout,
+        &SsFilterBinding::entries([
+            BindGroupEntry {
+                binding:  SsFilterBinding::Camera as u32,
+                resource: camera_params.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::LightPassParams as u32,
+                resource: gi_state.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::Probes as u32,
+                resource: probes.clone(),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::Sdf as u32,
+                resource: BindingResource::TextureView(&sdf_view_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::SdfSampler as u32,
+                resource: BindingResource::Sampler(&sdf_view_image.sampler),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::BlendIn as u32,
+                resource: BindingResource::TextureView(&ss_blend_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::FilterOut as u32,
+                resource: BindingResource::TextureView(&ss_filter_image.texture_view),
+            },
+            BindGroupEntry {
+                binding:  SsFilterBinding::PoseOut as u32,
+                resource: BindingResource::TextureView(&ss_pose_image.texture_view),
+            },
+        ]),
+    );
+
+    Ok(LightPassPipelineBindGroups {
+        sdf_bind_group,
+        ss_probe_bind_group,
+        ss_bounce_bind_group,
+        ss_blend_bind_group,
+        ss_filter_bind_group,
+    })
 }
 
 impl FromWorld for LightPassPipeline
 {
     fn from_world(world: &mut World) -> Self
     {
+        let formats = *world.resource::<GiTargetFormats>();
         let render_device = world.resource::<RenderDevice>();
 
         let sdf_bind_group_layout = render_device.create_bind_group_layout(
             "sdf_bind_group_layout",
-            &[
+            &SdfBinding::entries([
                 // Camera.
                 BindGroupLayoutEntry {
-                    binding:    0,
+                    binding:    SdfBinding::Camera as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -431,7 +794,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // Light occluders.
                 BindGroupLayoutEntry {
-                    binding:    1,
+                    binding:    SdfBinding::Occluders as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Storage { read_only: true },
@@ -442,24 +805,35 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF texture.
                 BindGroupLayoutEntry {
-                    binding:    2,
+                    binding:    SdfBinding::Sdf as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::ReadWrite,
-                        format:         SDF_TARGET_FORMAT,
+                        format:         formats.sdf,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
-            ],
+                // Tile occluder grid.
+                BindGroupLayoutEntry {
+                    binding:    SdfBinding::TileGrid as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Buffer {
+                        ty:                 BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size:   Some(GpuTileOccluderGrid::min_size()),
+                    },
+                    count:      None,
+                },
+            ]),
         );
 
         let ss_probe_bind_group_layout = render_device.create_bind_group_layout(
             "ss_probe_bind_group_layout",
-            &[
+            &SsProbeBinding::entries([
                 // Camera.
                 BindGroupLayoutEntry {
-                    binding:    0,
+                    binding:    SsProbeBinding::Camera as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -470,7 +844,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // GI State.
                 BindGroupLayoutEntry {
-                    binding:    1,
+                    binding:    SsProbeBinding::LightPassParams as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -481,7 +855,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // Probes.
                 BindGroupLayoutEntry {
-                    binding:    2,
+                    binding:    SsProbeBinding::Probes as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Storage { read_only: true },
@@ -492,7 +866,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // SkylightMasks.
                 BindGroupLayoutEntry {
-                    binding:    3,
+                    binding:    SsProbeBinding::SkylightMasks as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Storage { read_only: true },
@@ -503,7 +877,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // Light sources.
                 BindGroupLayoutEntry {
-                    binding:    4,
+                    binding:    SsProbeBinding::Lights as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Storage { read_only: true },
@@ -514,7 +888,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF.
                 BindGroupLayoutEntry {
-                    binding:    5,
+                    binding:    SsProbeBinding::Sdf as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Texture {
                         sample_type:    TextureSampleType::Float { filterable: true },
@@ -525,31 +899,82 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF Sampler.
                 BindGroupLayoutEntry {
-                    binding:    6,
+                    binding:    SsProbeBinding::SdfSampler as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Sampler(SamplerBindingType::Filtering),
                     count:      None,
                 },
                 // SS Probe.
                 BindGroupLayoutEntry {
-                    binding:    7,
+                    binding:    SsProbeBinding::ProbeOut as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::WriteOnly,
-                        format:         SS_PROBE_TARGET_FORMAT,
+                        format:         formats.probe,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count:      None,
+                },
+                // Light occluders.
+                BindGroupLayoutEntry {
+                    binding:    SsProbeBinding::Occluders as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Buffer {
+                        ty:                 BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size:   Some(GpuLightOccluderBuffer::min_size()),
+                    },
+                    count:      None,
+                },
+                // Rect light sources.
+                BindGroupLayoutEntry {
+                    binding:    SsProbeBinding::RectLights as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Buffer {
+                        ty:                 BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size:   Some(GpuRectLightSourceBuffer::min_size()),
+                    },
+                    count:      None,
+                },
+                // Height map.
+                BindGroupLayoutEntry {
+                    binding:    SsProbeBinding::HeightMap as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Texture {
+                        sample_type:    TextureSampleType::Float { filterable: true },
                         view_dimension: TextureViewDimension::D2,
+                        multisampled:   false,
                     },
                     count:      None,
                 },
-            ],
+                // Height map sampler.
+                BindGroupLayoutEntry {
+                    binding:    SsProbeBinding::HeightMapSampler as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Sampler(SamplerBindingType::Filtering),
+                    count:      None,
+                },
+                // Tile occluder grid.
+                BindGroupLayoutEntry {
+                    binding:    SsProbeBinding::TileGrid as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Buffer {
+                        ty:                 BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size:   Some(GpuTileOccluderGrid::min_size()),
+                    },
+                    count:      None,
+                },
+            ]),
         );
 
         let ss_bounce_bind_group_layout = render_device.create_bind_group_layout(
             "ss_bounce_bind_group_layout",
-            &[
+            &SsBounceBinding::entries([
                 // Camera.
                 BindGroupLayoutEntry {
-                    binding:    0,
+                    binding:    SsBounceBinding::Camera as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -560,7 +985,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // GI State.
                 BindGroupLayoutEntry {
-                    binding:    1,
+                    binding:    SsBounceBinding::LightPassParams as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -571,7 +996,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF.
                 BindGroupLayoutEntry {
-                    binding:    2,
+                    binding:    SsBounceBinding::Sdf as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Texture {
                         sample_type:    TextureSampleType::Float { filterable: true },
@@ -582,42 +1007,111 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF Sampler.
                 BindGroupLayoutEntry {
-                    binding:    3,
+                    binding:    SsBounceBinding::SdfSampler as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Sampler(SamplerBindingType::Filtering),
                     count:      None,
                 },
                 // SS Probe.
                 BindGroupLayoutEntry {
-                    binding:    4,
+                    binding:    SsBounceBinding::ProbeIn as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::ReadOnly,
-                        format:         SS_PROBE_TARGET_FORMAT,
+                        format:         formats.probe,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
                 // SS Bounce.
                 BindGroupLayoutEntry {
-                    binding:    5,
+                    binding:    SsBounceBinding::BounceOut as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::WriteOnly,
-                        format:         SS_BOUNCE_TARGET_FORMAT,
+                        format:         formats.probe,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count:      None,
+                },
+                // Floor color.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::Floor as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Texture {
+                        sample_type:    TextureSampleType::Float { filterable: true },
                         view_dimension: TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count:      None,
+                },
+                // Floor color Sampler.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::FloorSampler as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Sampler(SamplerBindingType::Filtering),
+                    count:      None,
+                },
+                // Objects color.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::Objects as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Texture {
+                        sample_type:    TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count:      None,
+                },
+                // Objects color Sampler.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::ObjectsSampler as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Sampler(SamplerBindingType::Filtering),
+                    count:      None,
+                },
+                // Blue noise.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::BlueNoise as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Texture {
+                        sample_type:    TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count:      None,
+                },
+                // Light sources.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::Lights as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Buffer {
+                        ty:                 BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size:   Some(GpuLightSourceBuffer::min_size()),
                     },
                     count:      None,
                 },
-            ],
+                // Rect light sources.
+                BindGroupLayoutEntry {
+                    binding:    SsBounceBinding::RectLights as u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty:         BindingType::Buffer {
+                        ty:                 BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size:   Some(GpuRectLightSourceBuffer::min_size()),
+                    },
+                    count:      None,
+                },
+            ]),
         );
 
         let ss_blend_bind_group_layout = render_device.create_bind_group_layout(
             "ss_blend_bind_group_layout",
-            &[
+            &SsBlendBinding::entries([
                 // Camera.
                 BindGroupLayoutEntry {
-                    binding:    0,
+                    binding:    SsBlendBinding::Camera as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -628,7 +1122,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // GI State.
                 BindGroupLayoutEntry {
-                    binding:    1,
+                    binding:    SsBlendBinding::LightPassParams as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -639,7 +1133,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // Probes.
                 BindGroupLayoutEntry {
-                    binding:    2,
+                    binding:    SsBlendBinding::Probes as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Storage { read_only: true },
@@ -650,7 +1144,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF.
                 BindGroupLayoutEntry {
-                    binding:    3,
+                    binding:    SsBlendBinding::Sdf as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Texture {
                         sample_type:    TextureSampleType::Float { filterable: true },
@@ -661,42 +1155,42 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF Sampler.
                 BindGroupLayoutEntry {
-                    binding:    4,
+                    binding:    SsBlendBinding::SdfSampler as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Sampler(SamplerBindingType::Filtering),
                     count:      None,
                 },
                 // SS Bounces.
                 BindGroupLayoutEntry {
-                    binding:    5,
+                    binding:    SsBlendBinding::BounceIn as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::ReadOnly,
-                        format:         SS_BOUNCE_TARGET_FORMAT,
+                        format:         formats.probe,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
                 // SS Blend.
                 BindGroupLayoutEntry {
-                    binding:    6,
+                    binding:    SsBlendBinding::BlendOut as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::WriteOnly,
-                        format:         SS_BLEND_TARGET_FORMAT,
+                        format:         formats.probe,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
-            ],
+            ]),
         );
 
         let ss_filter_bind_group_layout = render_device.create_bind_group_layout(
             "ss_filter_bind_group_layout",
-            &[
+            &SsFilterBinding::entries([
                 // Camera.
                 BindGroupLayoutEntry {
-                    binding:    0,
+                    binding:    SsFilterBinding::Camera as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -707,7 +1201,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // GI State.
                 BindGroupLayoutEntry {
-                    binding:    1,
+                    binding:    SsFilterBinding::LightPassParams as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Uniform,
@@ -718,7 +1212,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // Probes.
                 BindGroupLayoutEntry {
-                    binding:    2,
+                    binding:    SsFilterBinding::Probes as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Buffer {
                         ty:                 BufferBindingType::Storage { read_only: true },
@@ -729,7 +1223,7 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF.
                 BindGroupLayoutEntry {
-                    binding:    3,
+                    binding:    SsFilterBinding::Sdf as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Texture {
                         sample_type:    TextureSampleType::Float { filterable: true },
@@ -740,55 +1234,55 @@ impl FromWorld for LightPassPipeline
                 },
                 // SDF Sampler.
                 BindGroupLayoutEntry {
-                    binding:    4,
+                    binding:    SsFilterBinding::SdfSampler as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::Sampler(SamplerBindingType::Filtering),
                     count:      None,
                 },
                 // SS Blend.
                 BindGroupLayoutEntry {
-                    binding:    5,
+                    binding:    SsFilterBinding::BlendIn as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::ReadOnly,
-                        format:         SS_BLEND_TARGET_FORMAT,
+                        format:         formats.probe,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
                 // SS Filter.
                 BindGroupLayoutEntry {
-                    binding:    6,
+                    binding:    SsFilterBinding::FilterOut as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::WriteOnly,
-                        format:         SS_FILTER_TARGET_FORMAT,
+                        format:         formats.probe,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
                 // SS pose.
                 BindGroupLayoutEntry {
-                    binding:    7,
+                    binding:    SsFilterBinding::PoseOut as u32,
                     visibility: ShaderStages::COMPUTE,
                     ty:         BindingType::StorageTexture {
                         access:         StorageTextureAccess::WriteOnly,
-                        format:         SS_POSE_TARGET_FORMAT,
+                        format:         formats.pose,
                         view_dimension: TextureViewDimension::D2,
                     },
                     count:      None,
                 },
-            ],
+            ]),
         );
 
         let (shader_sdf, gi_ss_probe, gi_ss_bounce, gi_ss_blend, gi_ss_filter) = {
             let assets_server = world.resource::<AssetServer>();
             (
-                load_embedded_shader(assets_server, "gi_sdf.wgsl"),
-                load_embedded_shader(assets_server, "gi_ss_probe.wgsl"),
-                load_embedded_shader(assets_server, "gi_ss_bounce.wgsl"),
-                load_embedded_shader(assets_server, "gi_ss_blend.wgsl"),
-                load_embedded_shader(assets_server, "gi_ss_filter.wgsl"),
+                load_shader(assets_server, "gi_sdf.wgsl"),
+                load_shader(assets_server, "gi_ss_probe.wgsl"),
+                load_shader(assets_server, "gi_ss_bounce.wgsl"),
+                load_shader(assets_server, "gi_ss_blend.wgsl"),
+                load_shader(assets_server, "gi_ss_filter.wgsl"),
             )
         };
 
@@ -798,7 +1292,7 @@ impl FromWorld for LightPassPipeline
             label:                            Some("gi_sdf_pipeline".into()),
             layout:                           vec![sdf_bind_group_layout.clone()],
             shader:                           shader_sdf,
-            shader_defs:                      vec![],
+            shader_defs:                      formats.shader_defs(),
             entry_point:                      Some(SDF_PIPELINE_ENTRY.into()),
             push_constant_ranges:             vec![],
             zero_initialize_workgroup_memory: false,
@@ -808,7 +1302,7 @@ impl FromWorld for LightPassPipeline
             label:                            Some("gi_ss_probe_pipeline".into()),
             layout:                           vec![ss_probe_bind_group_layout.clone()],
             shader:                           gi_ss_probe,
-            shader_defs:                      vec![],
+            shader_defs:                      formats.shader_defs(),
             entry_point:                      Some(SS_PROBE_PIPELINE_ENTRY.into()),
             push_constant_ranges:             vec![],
             zero_initialize_workgroup_memory: false,
@@ -818,7 +1312,7 @@ impl FromWorld for LightPassPipeline
             label:                            Some("gi_ss_bounce_pipeline".into()),
             layout:                           vec![ss_bounce_bind_group_layout.clone()],
             shader:                           gi_ss_bounce,
-            shader_defs:                      vec![],
+            shader_defs:                      formats.shader_defs(),
             entry_point:                      Some(SS_BOUNCE_PIPELINE_ENTRY.into()),
             push_constant_ranges:             vec![],
             zero_initialize_workgroup_memory: false,
@@ -828,7 +1322,7 @@ impl FromWorld for LightPassPipeline
             label:                            Some("gi_blend_pipeline".into()),
             layout:                           vec![ss_blend_bind_group_layout.clone()],
             shader:                           gi_ss_blend,
-            shader_defs:                      vec![],
+            shader_defs:                      formats.shader_defs(),
             entry_point:                      Some(SS_BLEND_PIPELINE_ENTRY.into()),
             push_constant_ranges:             vec![],
             zero_initialize_workgroup_memory: false,
@@ -838,7 +1332,7 @@ impl FromWorld for LightPassPipeline
             label:                            Some("gi_filer_pipeline".into()),
             layout:                           vec![ss_filter_bind_group_layout.clone()],
             shader:                           gi_ss_filter,
-            shader_defs:                      vec![],
+            shader_defs:                      formats.shader_defs(),
             entry_point:                      Some(SS_FILTER_PIPELINE_ENTRY.into()),
             push_constant_ranges:             vec![],
             zero_initialize_workgroup_memory: false,
@@ -863,3 +1357,34 @@ impl FromWorld for LightPassPipeline
         }
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn sizes(width: u32, height: u32) -> ComputedTargetSizes
+    {
+        let mut window = Window::default();
+        window.resolution.set_physical_resolution(width, height);
+        window.resolution.set_scale_factor_override(Some(1.0));
+        ComputedTargetSizes::from_window(&window, &default())
+    }
+
+    #[test]
+    fn resizing_reuses_target_images()
+    {
+        let mut images = Assets::<Image>::default();
+        let formats = GiTargetFormats::default();
+        let targets = GiTargets::create(&mut images, &sizes(800, 600), &formats);
+        let image_count = images.len();
+
+        for i in 0..100 {
+            targets.resize(&mut images, &sizes(800 + i * 3, 600 + i), &formats);
+        }
+
+        assert_eq!(images.len(), image_count);
+        let sdf = images.get(&targets.sdf_target).expect("SDF target exists");
+        assert_eq!(sdf.size(), sizes(1097, 699).sdf_target_usize);
+    }
+}
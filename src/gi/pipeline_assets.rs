@@ -1,12 +1,32 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
 use bevy::prelude::*;
 use bevy::render::render_resource::{StorageBuffer, UniformBuffer};
 use bevy::render::renderer::{RenderDevice, RenderQueue};
 use bevy::render::Extract;
 use rand::Rng;
 
-use crate::gi::constants::GI_SCREEN_PROBE_SIZE;
-use crate::gi::resource::ComputedTargetSizes;
-use crate::gi::types::{LightOccluder2D, OmniLightSource2D, SkylightLight2D, SkylightMask2D};
+use crate::gi::adaptive_rays::GiAdaptiveRays;
+use crate::gi::constants::{MAX_LIGHTS, MAX_OCCLUDERS, SDF_VIEW_COVERAGE};
+use crate::gi::resource::{
+    ActiveLight,
+    ComputedTargetSizes,
+    FrameStepControl,
+    GiActiveLights,
+    GiStatus,
+    GiUpdateClock,
+    LightModulator,
+};
+use crate::gi::types::{
+    DirectionalLight2D,
+    LightOccluder2D,
+    ModulatedLight,
+    OmniLightSource2D,
+    RectLightSource2D,
+    SkylightLight2D,
+    SkylightMask2D,
+    TileOccluderGrid,
+};
 use crate::gi::types_gpu::{
     GpuCameraParams,
     GpuLightOccluder2D,
@@ -14,10 +34,15 @@ use crate::gi::types_gpu::{
     GpuLightPassParams,
     GpuLightSourceBuffer,
     GpuOmniLightSource,
+    GpuProbeData,
     GpuProbeDataBuffer,
+    GpuRectLightSource,
+    GpuRectLightSourceBuffer,
     GpuSkylightMaskBuffer,
     GpuSkylightMaskData,
+    GpuTileOccluderGrid,
 };
+use crate::gi::util;
 use crate::prelude::BevyMagicLight2DSettings;
 use crate::FloorCamera;
 
@@ -40,23 +65,131 @@ pub(crate) fn system_load_embedded_shader_dependencies(
     // Manual preloading is no longer needed with Bevy 0.17's improved shader loading
 }
 
-pub(crate) fn load_embedded_shader(asset_server: &AssetServer, shader_file: &str)
-    -> Handle<Shader>
+/// Shader files in `src/gi/shaders`, the entry points import the others.
+#[cfg(feature = "hot_reload_shaders")]
+pub(crate) const SHADER_FILES: [&str; 13] = [
+    "gi_attenuation.wgsl",
+    "gi_camera.wgsl",
+    "gi_halton.wgsl",
+    "gi_math.wgsl",
+    "gi_post_processing.wgsl",
+    "gi_raymarch.wgsl",
+    "gi_sdf.wgsl",
+    "gi_ss_blend.wgsl",
+    "gi_ss_bounce.wgsl",
+    "gi_ss_filter.wgsl",
+    "gi_ss_probe.wgsl",
+    "gi_upscale.wgsl",
+    "gi_types.wgsl",
+];
+
+/// Asset source reading `src/gi/shaders` from disk, registered by
+/// [`HotReloadShadersPlugin`].
+#[cfg(feature = "hot_reload_shaders")]
+pub const SHADER_ASSET_SOURCE: &str = "bevy_magic_light_2d_shaders";
+
+/// Registers the asset source the GI shaders are loaded from with
+/// `hot_reload_shaders`, so edits to `src/gi/shaders` are picked up by the
+/// file watcher and the pipelines are recompiled. Asset sources can only be
+/// added before the `AssetPlugin`, so add this before `DefaultPlugins`.
+#[cfg(feature = "hot_reload_shaders")]
+pub struct HotReloadShadersPlugin;
+
+#[cfg(feature = "hot_reload_shaders")]
+impl Plugin for HotReloadShadersPlugin
 {
-    // With load_shader_library!, shaders are embedded and loaded using embedded:// protocol
-    // Try the embedded protocol format that should work with the macro
-    asset_server.load(format!("embedded://bevy_magic_light_2d/gi/shaders/{}", shader_file))
+    fn build(&self, app: &mut App)
+    {
+        use bevy::asset::io::AssetSourceBuilder;
+
+        app.register_asset_source(
+            SHADER_ASSET_SOURCE,
+            AssetSourceBuilder::platform_default(concat!(env!("CARGO_MANIFEST_DIR"), "/src/gi/shaders"), None),
+        );
+    }
+}
+
+/// Asset path of a shader in `src/gi/shaders`: read from disk with
+/// `hot_reload_shaders`, embedded in the crate otherwise.
+pub(crate) fn shader_path(shader_file: &str) -> String
+{
+    #[cfg(feature = "hot_reload_shaders")]
+    return format!("{}://{}", SHADER_ASSET_SOURCE, shader_file);
+
+    #[cfg(not(feature = "hot_reload_shaders"))]
+    format!("embedded://bevy_magic_light_2d/gi/shaders/{}", shader_file)
+}
+
+pub(crate) fn load_shader(asset_server: &AssetServer, shader_file: &str) -> Handle<Shader>
+{
+    asset_server.load(shader_path(shader_file))
+}
+
+/// Handles of the shaders loaded from disk with `hot_reload_shaders`. The
+/// import libraries aren't referenced by any pipeline, holding them keeps
+/// them loaded.
+#[cfg(feature = "hot_reload_shaders")]
+#[derive(Resource)]
+pub(crate) struct HotReloadShaders(#[allow(dead_code)] pub Vec<Handle<Shader>>);
+
+/// With `hot_reload_shaders`, the shaders are loaded from disk and reloaded
+/// when edited. The pipeline cache then recompiles every pipeline using
+/// them, this just reports it.
+#[cfg(feature = "hot_reload_shaders")]
+pub(crate) fn system_log_shader_reloads(
+    mut shader_events: MessageReader<AssetEvent<Shader>>,
+    asset_server: Res<AssetServer>,
+)
+{
+    for event in shader_events.read() {
+        if let AssetEvent::Modified { id } = event {
+            if let Some(path) = asset_server.get_path(*id) {
+                if path.source().as_str() == Some(SHADER_ASSET_SOURCE) {
+                    log::info!("Reloaded shader {}", path);
+                }
+            }
+        }
+    }
 }
 
+/// Render world buffers shared by the GI passes.
+///
+/// Filled during extraction and uploaded in `RenderSystems::PrepareResources`,
+/// so a custom render node ordered after `LightPass2DRenderLabel` can bind
+/// them, e.g. `probes` together with the `ss_blend_target` probe atlas.
 #[rustfmt::skip]
 #[derive(Default, Resource)]
 pub struct LightPassPipelineAssets {
     pub camera_params:     UniformBuffer<GpuCameraParams>,
     pub light_pass_params: UniformBuffer<GpuLightPassParams>,
     pub light_sources:     StorageBuffer<GpuLightSourceBuffer>,
+    pub rect_lights:       StorageBuffer<GpuRectLightSourceBuffer>,
     pub light_occluders:   StorageBuffer<GpuLightOccluderBuffer>,
     pub probes:            StorageBuffer<GpuProbeDataBuffer>,
     pub skylight_masks:    StorageBuffer<GpuSkylightMaskBuffer>,
+    pub tile_grid:         StorageBuffer<GpuTileOccluderGrid>,
+
+    /// Pass toggles from [`BevyMagicLight2DSettings`], read by the node.
+    pub enable_bounce:     bool,
+    pub enable_blend:      bool,
+    pub enable_filter:     bool,
+
+    /// Whether the passes run this frame, see
+    /// [`BevyMagicLight2DSettings::gi_update_hz`].
+    pub update_due:        bool,
+
+    /// See [`BevyMagicLight2DSettings::height_map`].
+    pub height_map:        Option<Handle<Image>>,
+
+    /// Hash of the occluder data, the occluder buffer is only uploaded
+    /// when it changes.
+    light_occluders_hash:  Option<u64>,
+    light_occluders_dirty: bool,
+
+    /// Entity of the uploaded [`TileOccluderGrid`], the grid buffer is
+    /// only uploaded when the grid changes.
+    tile_grid_entity:      Option<Entity>,
+    tile_grid_dirty:       bool,
 }
 
 impl LightPassPipelineAssets
@@ -64,11 +197,19 @@ impl LightPassPipelineAssets
     pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue)
     {
         self.light_sources.write_buffer(device, queue);
-        self.light_occluders.write_buffer(device, queue);
+        self.rect_lights.write_buffer(device, queue);
+        if self.light_occluders_dirty || self.light_occluders.buffer().is_none() {
+            self.light_occluders.write_buffer(device, queue);
+            self.light_occluders_dirty = false;
+        }
         self.camera_params.write_buffer(device, queue);
         self.light_pass_params.write_buffer(device, queue);
         self.probes.write_buffer(device, queue);
         self.skylight_masks.write_buffer(device, queue);
+        if self.tile_grid_dirty || self.tile_grid.buffer().is_none() {
+            self.tile_grid.write_buffer(device, queue);
+            self.tile_grid_dirty = false;
+        }
     }
 }
 
@@ -81,66 +222,282 @@ pub fn system_prepare_pipeline_assets(
     gi_compute_assets.write_buffer(&render_device, &render_queue);
 }
 
+/// Warns when `count` exceeds `cap`. Only warns again once the count
+/// changes, so a steady overflow doesn't flood the log.
+fn warn_over_capacity(what: &str, count: usize, cap: usize, last_warned: &mut usize)
+{
+    if count <= cap {
+        *last_warned = 0;
+        return;
+    }
+    if *last_warned != count {
+        *last_warned = count;
+        log::warn!(
+            "{} {} are visible but at most {} are supported, the rest are ignored",
+            count,
+            what,
+            cap,
+        );
+    }
+}
+
+/// Every field of `GpuLightOccluder2D` must be hashed, otherwise changes to
+/// it on static occluders are never uploaded.
+fn hash_occluders(occluders: &GpuLightOccluderBuffer) -> u64
+{
+    let mut hasher = DefaultHasher::new();
+    occluders.count.hash(&mut hasher);
+    for occluder in occluders.data.iter() {
+        occluder.center.to_array().map(f32::to_bits).hash(&mut hasher);
+        occluder.rotation.to_array().map(f32::to_bits).hash(&mut hasher);
+        occluder.h_extent.to_array().map(f32::to_bits).hash(&mut hasher);
+        occluder.group.hash(&mut hasher);
+        occluder.height.to_bits().hash(&mut hasher);
+        occluder.corner_radius.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
 #[rustfmt::skip]
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::type_complexity)]
 pub fn system_extract_pipeline_assets(
     res_light_settings:         Extract<Res<BevyMagicLight2DSettings>>,
     res_target_sizes:           Extract<Res<ComputedTargetSizes>>,
+    res_frame_step:             Extract<Res<FrameStepControl>>,
+    res_gi_clock:               Extract<Res<GiUpdateClock>>,
+    res_light_modulator:        Extract<Res<LightModulator>>,
+    res_gi_status:              Extract<Res<GiStatus>>,
+    res_adaptive_rays:          Extract<Res<GiAdaptiveRays>>,
+    res_active_lights:          Extract<Res<GiActiveLights>>,
 
-    query_lights:               Extract<Query<(&GlobalTransform, &OmniLightSource2D, &InheritedVisibility, &ViewVisibility)>>,
-    query_occluders:            Extract<Query<(&LightOccluder2D, &GlobalTransform, &Transform, &InheritedVisibility, &ViewVisibility)>>,
+    query_lights:               Extract<Query<(Entity, &GlobalTransform, &OmniLightSource2D, Option<&ModulatedLight>, &InheritedVisibility, &ViewVisibility)>>,
+    query_rect_lights:          Extract<Query<(&GlobalTransform, &RectLightSource2D, Option<&ModulatedLight>, &InheritedVisibility, &ViewVisibility)>>,
+    query_occluders:            Extract<Query<(&LightOccluder2D, &GlobalTransform, &InheritedVisibility, &ViewVisibility)>>,
     query_camera:               Extract<Query<(&Camera, &GlobalTransform), With<FloorCamera>>>,
     query_masks:                Extract<Query<(&GlobalTransform, &SkylightMask2D)>>,
     query_skylight_light:       Extract<Query<&SkylightLight2D>>,
+    query_directional_light:    Extract<Query<(&DirectionalLight2D, Option<&InheritedVisibility>)>>,
+    query_tile_grids:           Extract<Query<(Entity, Ref<TileOccluderGrid>)>>,
 
     mut gpu_target_sizes:       ResMut<ComputedTargetSizes>,
     mut gpu_pipeline_assets:    ResMut<LightPassPipelineAssets>,
     mut gpu_frame_counter:      Local<i32>,
+    mut frames_since_reset:     Local<u32>,
+    mut warned_counts:          Local<(usize, usize, usize)>,
+    mut warned_tile_grids:      Local<bool>,
 ) {
     let light_pass_config = &res_light_settings.light_pass_params;
 
     *gpu_target_sizes = **res_target_sizes;
 
+    gpu_pipeline_assets.enable_bounce = res_light_settings.enable_bounce;
+    gpu_pipeline_assets.enable_blend  = res_light_settings.enable_blend;
+    gpu_pipeline_assets.enable_filter = res_light_settings.enable_filter;
+    gpu_pipeline_assets.height_map    = res_light_settings.height_map.clone();
+
+    // Grid edits are picked up even on skipped frames, change detection
+    // would miss them otherwise.
+    {
+        let mut tile_grids = query_tile_grids.iter();
+        let grid = tile_grids.next();
+        if grid.is_some() && tile_grids.next().is_some() && !*warned_tile_grids {
+            log::warn!("Multiple TileOccluderGrid found, only the first one is used");
+            *warned_tile_grids = true;
+        }
+
+        let entity = grid.as_ref().map(|(entity, _)| *entity);
+        let changed = grid.as_ref().is_some_and(|(_, grid)| grid.is_changed());
+        if changed || entity != gpu_pipeline_assets.tile_grid_entity {
+            *gpu_pipeline_assets.tile_grid.get_mut() = match grid {
+                Some((_, grid)) if !grid.bits().is_empty() => GpuTileOccluderGrid {
+                    origin:    grid.origin,
+                    cell_size: grid.cell_size.max(Vec2::splat(f32::EPSILON)),
+                    size:      grid.size(),
+                    bits:      grid.bits().to_vec(),
+                },
+                _ => GpuTileOccluderGrid::default(),
+            };
+            gpu_pipeline_assets.tile_grid_entity = entity;
+            gpu_pipeline_assets.tile_grid_dirty  = true;
+        }
+    }
+
+    // Both the camera transform and its projection are used, so zooming by
+    // `Transform::scale` and by `OrthographicProjection::scaling_mode` give
+    // the same result. The projection is degenerate until the camera has a
+    // viewport, e.g. on the first frame or while minimized.
+    let camera_matrices = query_camera.single().ok().and_then(|(camera, camera_global_transform)| {
+        let projection = camera.clip_from_view();
+        projection
+            .determinant()
+            .is_normal()
+            .then(|| (camera_global_transform.to_matrix(), projection))
+    });
+
+    res_gi_status.set_camera_found(camera_matrices.is_some());
+
+    // Resets requested on skipped frames apply to the next update.
+    if res_frame_step.resets() {
+        *frames_since_reset = 0;
+    }
+
+    // Skipped frames keep the previous lights and camera, so the frame
+    // counter and probe cameras stay consistent with what was rendered.
+    gpu_pipeline_assets.update_due = res_gi_clock.update_due();
+    if !gpu_pipeline_assets.update_due {
+        return;
+    }
+
+    let modulator_level = res_light_modulator.level();
+
+    let probe_atlas_frames = gpu_target_sizes.probe_atlas_frames().max(1);
+    let reservoir_size = gpu_target_sizes.reservoir_size(light_pass_config.reservoir_size);
+    let frame_cycle = gpu_target_sizes.frame_cycle(light_pass_config.reservoir_size);
+    {
+        let probes = gpu_pipeline_assets.probes.get_mut();
+        if probes.data.len() != probe_atlas_frames as usize {
+            probes.count = probe_atlas_frames;
+            probes.data.resize(probe_atlas_frames as usize, GpuProbeData::default());
+        }
+        if *gpu_frame_counter >= frame_cycle as i32 {
+            *gpu_frame_counter = 0;
+        }
+    }
+
+    // GI is computed for a zoomed out view, see `gi_camera_margin`.
+    let gi_zoom = 1.0 + res_light_settings.gi_camera_margin.max(0.0);
+    let clip_from_gi_clip = Mat4::from_scale(Vec3::new(gi_zoom, gi_zoom, 1.0));
+
+    // World-space bounds of the lit area, lights that can't reach it are culled.
+    let view_rect = camera_matrices.map(|(view, projection)| {
+        let world_from_clip = view * projection.inverse() * clip_from_gi_clip;
+        let a = world_from_clip.project_point3(Vec3::new(-1.0, -1.0, 0.0)).truncate();
+        let b = world_from_clip.project_point3(Vec3::new(1.0, 1.0, 0.0)).truncate();
+        Rect::from_corners(a, b)
+    });
+
     {
         let light_sources = gpu_pipeline_assets.light_sources.get_mut();
         let mut rng = rand::rng();
         light_sources.count = 0;
         light_sources.data.clear();
-        for (transform, light_source, hviz, vviz) in query_lights.iter() {
-            if hviz.get() && vviz.get() {
+        let mut light_entities = Vec::new();
+        for (entity, transform, light_source, modulated, hviz, vviz) in query_lights.iter() {
+            if light_source.enabled && hviz.get() && vviz.get() {
+                let intensity = modulated.map_or(light_source.intensity, |m| m.intensity(modulator_level));
+                let light = OmniLightSource2D {
+                    intensity: intensity
+                        + rng.random_range(-1.0..1.0) * light_source.jitter_intensity,
+                    ..*light_source
+                };
+                let center = Vec2::new(
+                    transform.translation().x
+                        + rng.random_range(-1.0..1.0) * light_source.jitter_translation,
+                    transform.translation().y
+                        + rng.random_range(-1.0..1.0) * light_source.jitter_translation,
+                );
+                let radius = light.effective_radius(light_pass_config.light_cull_epsilon);
+
+                if let Some(view_rect) = view_rect {
+                    let closest = center.clamp(view_rect.min, view_rect.max);
+                    if closest.distance_squared(center) > radius * radius {
+                        continue;
+                    }
+                }
+
                 light_sources.count += 1;
-                light_sources.data.push(GpuOmniLightSource::new(
-                    OmniLightSource2D {
-                        intensity: light_source.intensity
-                            + rng.random_range(-1.0..1.0) * light_source.jitter_intensity,
-                        ..*light_source
-                    },
-                    Vec2::new(
-                        transform.translation().x
-                            + rng.random_range(-1.0..1.0) * light_source.jitter_translation,
-                        transform.translation().y
-                            + rng.random_range(-1.0..1.0) * light_source.jitter_translation,
-                    ),
-                ));
+                if light_sources.data.len() < MAX_LIGHTS {
+                    light_sources.data.push(GpuOmniLightSource::new(light, center, radius));
+                    light_entities.push(entity);
+                }
             }
         }
+
+        warn_over_capacity("lights", light_sources.count as usize, MAX_LIGHTS, &mut warned_counts.0);
+        light_sources.count = light_sources.data.len() as u32;
+
+        res_active_lights.update(
+            query_lights.iter().count(),
+            light_sources.data.len(),
+            light_entities.iter().zip(light_sources.data.iter()).map(|(entity, light)| ActiveLight {
+                entity:    *entity,
+                position:  light.center,
+                intensity: light.intensity,
+                radius:    light.radius,
+            }),
+        );
+    }
+
+    {
+        let rect_lights = gpu_pipeline_assets.rect_lights.get_mut();
+        rect_lights.count = 0;
+        rect_lights.data.clear();
+        for (transform, light, modulated, hviz, vviz) in query_rect_lights.iter() {
+            if light.enabled && hviz.get() && vviz.get() {
+                let light = &RectLightSource2D {
+                    intensity: modulated.map_or(light.intensity, |m| m.intensity(modulator_level)),
+                    ..*light
+                };
+                let center = transform.translation().truncate();
+                let axis_x = transform.transform_vector3(Vec3::X * light.half_size.x).truncate();
+                let axis_y = transform.transform_vector3(Vec3::Y * light.half_size.y).truncate();
+                let radius = RectLightSource2D {
+                    half_size: Vec2::new(axis_x.length(), axis_y.length()),
+                    ..*light
+                }
+                .effective_radius(light_pass_config.light_cull_epsilon);
+
+                if let Some(view_rect) = view_rect {
+                    let closest = center.clamp(view_rect.min, view_rect.max);
+                    if closest.distance_squared(center) > radius * radius {
+                        continue;
+                    }
+                }
+
+                rect_lights.count += 1;
+                if rect_lights.data.len() < MAX_LIGHTS {
+                    rect_lights.data.push(GpuRectLightSource::new(light, center, axis_x, axis_y, radius));
+                }
+            }
+        }
+
+        warn_over_capacity("rect lights", rect_lights.count as usize, MAX_LIGHTS, &mut warned_counts.2);
+        rect_lights.count = rect_lights.data.len() as u32;
     }
 
     {
         let light_occluders = gpu_pipeline_assets.light_occluders.get_mut();
         light_occluders.count = 0;
         light_occluders.data.clear();
-        for (occluder, global_transform, transform, hviz, vviz) in query_occluders.iter() {
+        light_occluders.data.reserve(query_occluders.iter().len());
+        for (occluder, global_transform, hviz, vviz) in query_occluders.iter() {
             if hviz.get() && vviz.get() {
+                // Use the world-space rotation so that occluders parented to
+                // rotated entities are oriented correctly. The inverse maps
+                // world-space samples into the occluder's local frame.
                 light_occluders.count += 1;
-                light_occluders.data.push(GpuLightOccluder2D {
-                    center: global_transform.translation().xy(),
-                    rotation: transform.rotation.inverse().into(),
-                    h_extent: occluder.h_size,
-                });
+                if light_occluders.data.len() < MAX_OCCLUDERS {
+                    light_occluders.data.push(GpuLightOccluder2D {
+                        center: global_transform.translation().xy(),
+                        rotation: global_transform.rotation().inverse().into(),
+                        h_extent: occluder.h_size,
+                        group: occluder.group,
+                        height: occluder.height,
+                        corner_radius: occluder.corner_radius.max(0.0),
+                    });
+                }
             }
         }
+
+        warn_over_capacity("occluders", light_occluders.count as usize, MAX_OCCLUDERS, &mut warned_counts.1);
+        light_occluders.count = light_occluders.data.len() as u32;
+
+        let occluders_hash = hash_occluders(light_occluders);
+        if gpu_pipeline_assets.light_occluders_hash != Some(occluders_hash) {
+            gpu_pipeline_assets.light_occluders_hash  = Some(occluders_hash);
+            gpu_pipeline_assets.light_occluders_dirty = true;
+        }
     }
 
     {
@@ -152,20 +509,20 @@ pub fn system_extract_pipeline_assets(
             skylight_masks.data.push(GpuSkylightMaskData::new(
                 transform.translation().truncate(),
                 mask.h_size,
+                mask.tint,
             ));
         }
     }
 
     {
-        if let Ok((camera, camera_global_transform)) = query_camera.single() {
+        if let Some((view, projection)) = camera_matrices {
             let camera_params = gpu_pipeline_assets.camera_params.get_mut();
-            let projection = camera.clip_from_view();
             let inverse_projection = projection.inverse();
-            let view = camera_global_transform.to_matrix();
             let inverse_view = view.inverse();
 
-            camera_params.view_proj = projection * inverse_view;
-            camera_params.inverse_view_proj = view * inverse_projection;
+            camera_params.view_proj = clip_from_gi_clip.inverse() * projection * inverse_view;
+            camera_params.inverse_view_proj = view * inverse_projection * clip_from_gi_clip;
+            camera_params.display_scale = gi_zoom;
             camera_params.screen_size = Vec2::new(
                 gpu_target_sizes.primary_target_size.x,
                 gpu_target_sizes.primary_target_size.y,
@@ -175,45 +532,97 @@ pub fn system_extract_pipeline_assets(
                 1.0 / gpu_target_sizes.primary_target_size.y,
             );
 
-            let scale = 2.0;
-            camera_params.sdf_scale     = Vec2::splat(scale);
-            camera_params.inv_sdf_scale = Vec2::splat(1. / scale);
+            // Shaders address the SDF by uv, so its resolution doesn't
+            // matter here, only the area it covers.
+            camera_params.sdf_scale     = Vec2::splat(SDF_VIEW_COVERAGE);
+            camera_params.inv_sdf_scale = Vec2::splat(1. / SDF_VIEW_COVERAGE);
 
+            let (view_proj, inverse_view_proj) = (camera_params.view_proj, camera_params.inverse_view_proj);
             let probes = gpu_pipeline_assets.probes.get_mut();
-            probes.data[*gpu_frame_counter as usize].camera_pose =
-                camera_global_transform.translation().truncate();
+            probes.data[*gpu_frame_counter as usize] = GpuProbeData {
+                camera_pose: view.w_axis.truncate().truncate(),
+                view_proj,
+                inverse_view_proj,
+            };
         } else {
-            log::warn!("Failed to get camera");
+            // Clearing the probe camera drops the history of this frame.
+            log::warn!("Failed to get camera or its projection is degenerate");
             let probes = gpu_pipeline_assets.probes.get_mut();
-            probes.data[*gpu_frame_counter as usize].camera_pose = Vec2::ZERO;
+            probes.data[*gpu_frame_counter as usize] = GpuProbeData::default();
         }
     }
 
     {
         let light_pass_params = gpu_pipeline_assets.light_pass_params.get_mut();
         light_pass_params.frame_counter = *gpu_frame_counter;
-        light_pass_params.probe_size = GI_SCREEN_PROBE_SIZE;
+        light_pass_params.history_length = (*frames_since_reset + 1).min(reservoir_size) as i32;
+        light_pass_params.frame_cycle   = frame_cycle as i32;
+        light_pass_params.probe_size = gpu_target_sizes.probe_size.as_ivec2();
         light_pass_params.probe_atlas_cols            = gpu_target_sizes.probe_grid_isize.x;
         light_pass_params.probe_atlas_rows            = gpu_target_sizes.probe_grid_isize.y;
-        light_pass_params.reservoir_size              = light_pass_config.reservoir_size;
-        light_pass_params.smooth_kernel_size_h        = light_pass_config.smooth_kernel_size.0;
-        light_pass_params.smooth_kernel_size_w        = light_pass_config.smooth_kernel_size.1;
+        light_pass_params.reservoir_size              = reservoir_size;
+        light_pass_params.smooth_kernel_size_h        = res_light_settings.effective_smooth_kernel_size().0;
+        light_pass_params.smooth_kernel_size_w        = res_light_settings.effective_smooth_kernel_size().1;
         light_pass_params.direct_light_contrib        = light_pass_config.direct_light_contrib;
         light_pass_params.indirect_light_contrib      = light_pass_config.indirect_light_contrib;
         light_pass_params.indirect_rays_radius_factor = light_pass_config.indirect_rays_radius_factor;
-        light_pass_params.indirect_rays_per_sample    = light_pass_config.indirect_rays_per_sample;
+        light_pass_params.indirect_rays_per_sample    = res_adaptive_rays.indirect_rays_per_sample();
+        light_pass_params.minimum_ambient             = light_pass_config.minimum_ambient.to_vec3();
+        light_pass_params.specular_strength           = light_pass_config.specular_strength;
+        light_pass_params.shininess                   = light_pass_config.shininess.max(1.0);
+        light_pass_params.light_sample_budget         = light_pass_config.light_sample_budget;
+        light_pass_params.indirect_blue_noise         = light_pass_config.indirect_blue_noise as u32;
+        light_pass_params.rect_light_samples          = light_pass_config.rect_light_samples.max(1);
+        light_pass_params.max_luminance_clamp         = light_pass_config.max_luminance_clamp.max(0.0);
+        light_pass_params.filter_edge_aware           = light_pass_config.filter_edge_aware as u32;
+        light_pass_params.height_map_enabled          = res_light_settings.height_map.is_some() as u32;
+        light_pass_params.height_scale                = light_pass_config.height_scale.max(0.0);
+        light_pass_params.light_height                = light_pass_config.light_height.max(0.0);
+        light_pass_params.fog_density                 = light_pass_config.fog_density.max(0.0);
+        light_pass_params.fog_color                   = light_pass_config.fog_color.to_vec3();
+        light_pass_params.raymarch_max_steps          = light_pass_config.raymarch_max_steps.clamp(1, 1024) as i32;
+        light_pass_params.raymarch_max_distance       = light_pass_config.raymarch_max_distance.max(0.0);
+        light_pass_params.light_accumulation          = light_pass_config.light_accumulation as u32;
     }
 
     {
         let light_pass_params = gpu_pipeline_assets.light_pass_params.get_mut();
         light_pass_params.skylight_color = Vec3::splat(0.0);
-        for new_gi_state in query_skylight_light.iter() {
+        let skylight_lights = query_skylight_light
+            .iter()
+            .filter(|_| res_light_settings.skylight_enabled);
+        for new_gi_state in skylight_lights {
             let srgba = new_gi_state.color.to_srgba();
             light_pass_params.skylight_color.x += srgba.red * new_gi_state.intensity;
             light_pass_params.skylight_color.y += srgba.green * new_gi_state.intensity;
             light_pass_params.skylight_color.z += srgba.blue * new_gi_state.intensity;
         }
+        if let Some(knee) = res_light_settings.skylight_knee {
+            light_pass_params.skylight_color = util::soft_knee(light_pass_params.skylight_color, knee);
+        }
+    }
+
+    {
+        let light_pass_params = gpu_pipeline_assets.light_pass_params.get_mut();
+        // A sun without a direction would light every probe unoccluded.
+        let sun = query_directional_light
+            .iter()
+            .filter(|(_, hviz)| hviz.is_none_or(|hviz| hviz.get()))
+            .map(|(sun, _)| sun)
+            .find(|sun| sun.direction.length_squared() > 0.0);
+        if let Some(sun) = sun {
+            light_pass_params.sun_direction = sun.direction.normalize_or_zero();
+            light_pass_params.sun_color     = sun.color.to_srgba().to_vec3() * sun.intensity;
+            light_pass_params.sun_penumbra  = sun.angular_radius.clamp(0.0, 1.0).tan();
+        } else {
+            light_pass_params.sun_direction = Vec2::ZERO;
+            light_pass_params.sun_color     = Vec3::ZERO;
+            light_pass_params.sun_penumbra  = 0.0;
+        }
     }
 
-    *gpu_frame_counter = (*gpu_frame_counter + 1) % (GI_SCREEN_PROBE_SIZE * GI_SCREEN_PROBE_SIZE);
+    if res_frame_step.advances() {
+        *gpu_frame_counter = (*gpu_frame_counter + 1) % frame_cycle as i32;
+        *frames_since_reset = frames_since_reset.saturating_add(1);
+    }
 }
@@ -1,10 +1,92 @@
-use bevy::camera::visibility::Layer;
+use bevy::camera::visibility::{Layer, RenderLayers};
+use bevy::prelude::*;
 
 pub const CAMERA_LAYER_FLOOR: Layer = 1;
 pub const CAMERA_LAYER_WALLS: Layer = 2;
 pub const CAMERA_LAYER_OBJECTS: Layer = 3;
+pub const CAMERA_LAYER_UNLIT: Layer = 4;
 
 
 pub const ALL_LAYERS: &[Layer] = &[CAMERA_LAYER_FLOOR, CAMERA_LAYER_WALLS, CAMERA_LAYER_OBJECTS];
 
 pub const CAMERA_LAYER_POST_PROCESSING: Layer = 42;
+pub const CAMERA_LAYER_UPSCALE: Layer = 43;
+
+/// Maximum number of additional lit layers composited by the post-processing
+/// pass, see `BevyMagicLight2DSettings::extra_lit_layers`.
+pub const MAX_EXTRA_LIT_LAYERS: usize = 4;
+
+/// Layers of the content targets and of the composite, see
+/// [`BevyMagicLight2DSettings::render_layers`](crate::gi::resource::BevyMagicLight2DSettings::render_layers).
+/// Defaults to the `CAMERA_LAYER_*` constants. Remap them when they collide
+/// with layers the game already uses, and spawn sprites and content
+/// cameras with the remapped layers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Reflect)]
+pub struct MagicLightLayers
+{
+    pub floor:           Layer,
+    pub walls:           Layer,
+    pub objects:         Layer,
+    /// Layer of [`Unlit`](crate::gi::types::Unlit) sprites.
+    pub unlit:           Layer,
+    /// Layer of the post-processing camera and quad. The plugin keeps them
+    /// on this layer when it changes.
+    pub post_processing: Layer,
+    /// Layer of the camera and quad presenting the
+    /// [full resolution output](crate::gi::resource::BevyMagicLight2DSettings::full_resolution_output).
+    pub upscale:         Layer,
+}
+
+impl MagicLightLayers
+{
+    pub fn floor_layers(&self) -> RenderLayers
+    {
+        RenderLayers::layer(self.floor)
+    }
+
+    pub fn walls_layers(&self) -> RenderLayers
+    {
+        RenderLayers::layer(self.walls)
+    }
+
+    pub fn objects_layers(&self) -> RenderLayers
+    {
+        RenderLayers::layer(self.objects)
+    }
+
+    pub fn unlit_layers(&self) -> RenderLayers
+    {
+        RenderLayers::layer(self.unlit)
+    }
+
+    pub fn post_processing_layers(&self) -> RenderLayers
+    {
+        RenderLayers::layer(self.post_processing)
+    }
+
+    pub fn upscale_layers(&self) -> RenderLayers
+    {
+        RenderLayers::layer(self.upscale)
+    }
+
+    /// Floor, walls and objects layers combined.
+    pub fn content_layers(&self) -> RenderLayers
+    {
+        RenderLayers::from_layers(&[self.floor, self.walls, self.objects])
+    }
+}
+
+impl Default for MagicLightLayers
+{
+    fn default() -> Self
+    {
+        Self {
+            floor:           CAMERA_LAYER_FLOOR,
+            walls:           CAMERA_LAYER_WALLS,
+            objects:         CAMERA_LAYER_OBJECTS,
+            unlit:           CAMERA_LAYER_UNLIT,
+            post_processing: CAMERA_LAYER_POST_PROCESSING,
+            upscale:         CAMERA_LAYER_UPSCALE,
+        }
+    }
+}
@@ -1,32 +1,421 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bevy::camera::visibility::Layer;
 use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
 #[cfg(feature = "egui")]
 use bevy_inspector_egui::prelude::ReflectInspectorOptions;
 #[cfg(feature = "egui")]
 use bevy_inspector_egui::InspectorOptions;
 
+use crate::gi::adaptive_rays::AdaptiveRayParams;
+use crate::gi::bake::LightBake;
 use crate::gi::constants::GI_SCREEN_PROBE_SIZE;
+use crate::gi::exposure::AutoExposureParams;
+use crate::gi::render_layer::MagicLightLayers;
+use crate::gi::scene_gizmos::SceneGizmoParams;
 use crate::gi::util;
 
-#[derive(Copy, Clone, Reflect)]
+#[derive(Copy, Clone, PartialEq, Reflect)]
 pub struct TargetScalingParams
 {
-    /// Scale factor for SDF map.
+    /// Resolution of the SDF map relative to the primary target,
+    /// independent of the probe grid. Lower values make the SDF pass cheaper
+    /// at the cost of rounder shadow edges. Targets are rebuilt when it
+    /// changes.
     pub sdf_scale: f32,
+
+    /// Screen-space stride between two probes, in pixels. Also defines the
+    /// layout of the probe atlas, so the temporal cycle is `x * y` frames.
+    pub probe_size: UVec2,
 }
 
 impl Default for TargetScalingParams
 {
     fn default() -> Self
     {
-        Self { sdf_scale: 0.5 }
+        Self {
+            sdf_scale:  0.5,
+            probe_size: UVec2::splat(GI_SCREEN_PROBE_SIZE as u32),
+        }
     }
 }
 
-#[derive(Resource, Default, Copy, Clone, Reflect)]
+#[derive(Resource, Clone, Reflect)]
 pub struct BevyMagicLight2DSettings
 {
-    pub light_pass_params:     LightPassParams,
-    pub target_scaling_params: TargetScalingParams,
+    pub light_pass_params:             LightPassParams,
+    pub target_scaling_params:         TargetScalingParams,
+    pub post_processing_camera_params: PostProcessingCameraParams,
+    /// When set, the final composite is rendered into this image instead of
+    /// the window, so it can be used as an input to a custom post chain. The
+    /// image should match the primary target size.
+    pub composite_target:              Option<Handle<Image>>,
+    /// Renders the composite at the primary target size into
+    /// `CameraTargets::upscale_source`, and presents it to the window at its
+    /// full physical resolution with this filter. UI and other cameras
+    /// drawing to the window stay at native resolution. The presenting
+    /// camera has `post_processing_camera_params.order + 1`. Ignored while
+    /// `composite_target` is set.
+    pub full_resolution_output:        Option<UpscaleFilter>,
+    /// MSAA of the content cameras rendering into the layer targets and of
+    /// the cameras spawned by the plugin. Applied to all of them, so their
+    /// pipelines are specialized consistently.
+    pub msaa:                          Msaa,
+    /// What the post-processing pass outputs, useful when tuning lights.
+    pub debug_output:                  DebugView,
+    /// Gizmo overlay of the lights, occluders and skylight masks, drawn on
+    /// top of the composite. `None` hides it.
+    pub scene_gizmos:                  Option<SceneGizmoParams>,
+    /// Additional render layers lit by GI, composited on top of the objects
+    /// layer in order. Each gets a target in `CameraTargets::extra_targets`,
+    /// up to `MAX_EXTRA_LIT_LAYERS`.
+    pub extra_lit_layers:              Vec<Layer>,
+    /// Render layers of the floor, walls and objects content and of the
+    /// composite.
+    pub render_layers:                 MagicLightLayers,
+    /// How irradiance is upscaled in the post-processing pass. `Nearest`
+    /// keeps crisp probe blocks for pixel-art, `Linear` interpolates them.
+    pub irradiance_filter:             IrradianceFilter,
+    /// Hard-edged lighting for pixel-art: removes bloom from the
+    /// post-processing camera, samples irradiance and the full resolution
+    /// output with nearest filtering and turns off the smoothing kernel of
+    /// the filter pass. Overrides `irradiance_filter`,
+    /// `full_resolution_output`'s filter and
+    /// `LightPassParams::smooth_kernel_size` while set, without changing
+    /// them.
+    pub pixel_art_mode:                bool,
+    /// Alpha convention of the floor, walls, objects and extra layer
+    /// targets, used when compositing them.
+    pub layer_alpha:                   LayerAlpha,
+    /// Render the floor, walls, objects and extra layers into `Rgba16Float`
+    /// targets with HDR cameras, so emissive sprites brighter than `1.0`
+    /// keep their intensity in the composite and feed bloom. `Hdr` is added
+    /// to and removed from the cameras rendering into those targets.
+    pub hdr_layers:                    bool,
+    /// Keep the coverage of the layers in the composite, so it is
+    /// transparent where nothing is drawn and can be overlaid on other
+    /// cameras, e.g. a separately rendered parallax background. The
+    /// composite is blended over what its target already shows: set
+    /// `post_processing_camera_params.clear_color` to `ClearColorConfig::None`
+    /// to keep a background camera of lower order, or to a transparent color
+    /// for an image `composite_target`. The content cameras need transparent
+    /// clear colors as well. A background camera must match
+    /// `post_processing_camera_params.hdr` to share its main texture.
+    pub transparent_background:        bool,
+    /// Extra area lit around the view, as a fraction of its size. GI is
+    /// computed for the larger area and cropped when compositing, so fast
+    /// pans don't reveal unlit edges, at the cost of probe density.
+    pub gi_camera_margin:              f32,
+    /// Caps how often GI is recomputed, in updates per second. The composite
+    /// still runs every frame with the latest lighting, and the temporal
+    /// frame counter only advances on updates. `None` updates every frame.
+    pub gi_update_hz:                  Option<f32>,
+    /// Contrast of the final composite around mid-grey, `1.0` leaves it
+    /// unchanged.
+    pub contrast:                      f32,
+    /// Saturation of the final composite, luminance is preserved. `0.0` is
+    /// greyscale, `1.0` leaves it unchanged.
+    pub saturation:                    f32,
+    /// Circular falloff of the composite towards the screen edges,
+    /// regardless of lights. `None` disables it.
+    pub vignette:                      Option<Vignette>,
+    /// Emissive outline along occluder edges, taken from the SDF, for a
+    /// stylized neon look. Not affected by lighting. `None` disables it.
+    pub occluder_outline:              Option<OccluderOutline>,
+    /// Strength of the objects layer mirrored below each object onto the
+    /// floor, e.g. for wet or polished floors. Reflections fade with the
+    /// distance to the object and near occluders. `0.0` disables them.
+    pub reflection_strength:           f32,
+    /// How much of the irradiance color tints the objects and extra lit
+    /// layers, in `0..=1`. Lower values blend towards the irradiance
+    /// luminance, so saturated lights don't over-tint white sprites. `1.0`
+    /// keeps the full color.
+    pub color_bleed:                   f32,
+    /// Per-layer multipliers of the indirect (bounced) share of the light,
+    /// e.g. to keep character sprites crisp while the floor takes the full
+    /// bounce.
+    pub layer_indirect:                LayerIndirect,
+    /// Multiplier applied to irradiance before compositing, used while
+    /// `auto_exposure` is `None`.
+    pub exposure:                      f32,
+    /// Adapts the exposure to the measured scene irradiance over time. The
+    /// current value is in `GiExposure`.
+    pub auto_exposure:                 Option<AutoExposureParams>,
+    /// Whether `SkylightLight2D` entities contribute any light. Disable to
+    /// drop ambient skylight to zero, e.g. inside caves, without despawning
+    /// them.
+    pub skylight_enabled:              bool,
+    /// Brightness above which the summed color of all `SkylightLight2D`
+    /// entities is softly compressed, rolling off towards `knee + 1.0`.
+    /// Keeps stacked skylights from blowing out the ambient. `None` sums
+    /// them linearly.
+    pub skylight_knee:                 Option<f32>,
+    /// Screen-space height of the scene in the red channel, e.g. rendered
+    /// by a camera into an image matching the primary target size. When
+    /// set, terrain and tall objects shadow lights behind them, see
+    /// `LightPassParams::height_scale`.
+    pub height_map:                    Option<Handle<Image>>,
+    /// Run the indirect bounce pass. When disabled, direct light is passed
+    /// through unchanged.
+    pub enable_bounce:                 bool,
+    /// Scales the indirect rays per sample with the measured frame time to
+    /// hold a target frame rate, overriding
+    /// `LightPassParams::indirect_rays_per_sample`. The current count is in
+    /// `GiAdaptiveRays`.
+    pub adaptive_rays:                 Option<AdaptiveRayParams>,
+    /// Run the temporal blend pass. When disabled, only the current frame's
+    /// probes are used.
+    pub enable_blend:                  bool,
+    /// Run the spatial filter pass. When disabled, the unfiltered probe grid
+    /// is composited instead.
+    pub enable_filter:                 bool,
+}
+
+impl Default for BevyMagicLight2DSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            light_pass_params:             LightPassParams::default(),
+            target_scaling_params:         TargetScalingParams::default(),
+            post_processing_camera_params: PostProcessingCameraParams::default(),
+            composite_target:              None,
+            full_resolution_output:        None,
+            msaa:                          Msaa::Off,
+            debug_output:                  DebugView::default(),
+            scene_gizmos:                  None,
+            extra_lit_layers:              Vec::new(),
+            render_layers:                 MagicLightLayers::default(),
+            irradiance_filter:             IrradianceFilter::default(),
+            pixel_art_mode:                false,
+            layer_alpha:                   LayerAlpha::default(),
+            hdr_layers:                    false,
+            transparent_background:        false,
+            gi_camera_margin:              0.0,
+            gi_update_hz:                  None,
+            contrast:                      1.0,
+            saturation:                    1.0,
+            vignette:                      None,
+            occluder_outline:              None,
+            reflection_strength:           0.0,
+            color_bleed:                   1.0,
+            layer_indirect:                LayerIndirect::default(),
+            exposure:                      1.0,
+            auto_exposure:                 None,
+            skylight_enabled:              true,
+            skylight_knee:                 None,
+            height_map:                    None,
+            enable_bounce:                 true,
+            adaptive_rays:                 None,
+            enable_blend:                  true,
+            enable_filter:                 true,
+        }
+    }
+}
+
+impl BevyMagicLight2DSettings
+{
+    /// Irradiance filter used when compositing, see `pixel_art_mode`.
+    pub fn effective_irradiance_filter(&self) -> IrradianceFilter
+    {
+        if self.pixel_art_mode {
+            IrradianceFilter::Nearest
+        } else {
+            self.irradiance_filter
+        }
+    }
+
+    /// Filter of the full resolution output, see `pixel_art_mode`.
+    pub fn effective_upscale_filter(&self) -> Option<UpscaleFilter>
+    {
+        self.full_resolution_output
+            .map(|filter| if self.pixel_art_mode { UpscaleFilter::Nearest } else { filter })
+    }
+
+    /// Kernel size of the filter pass, see `pixel_art_mode`.
+    pub fn effective_smooth_kernel_size(&self) -> (u32, u32)
+    {
+        if self.pixel_art_mode {
+            (0, 0)
+        } else {
+            self.light_pass_params.smooth_kernel_size
+        }
+    }
+}
+
+/// Screen-space falloff of the composite, see
+/// [`BevyMagicLight2DSettings::vignette`].
+#[derive(Copy, Clone, Debug, Reflect)]
+pub struct Vignette
+{
+    /// Distance from the screen center where the falloff starts, in half
+    /// screen heights. `1.0` touches the top and bottom edges.
+    pub radius:   f32,
+    /// Width of the falloff, in half screen heights.
+    pub softness: f32,
+    /// Color the scene fades to.
+    pub color:    Color,
+}
+
+impl Default for Vignette
+{
+    fn default() -> Self
+    {
+        Self {
+            radius:   0.8,
+            softness: 0.4,
+            color:    Color::BLACK,
+        }
+    }
+}
+
+/// Multipliers of the indirect light each layer receives in the composite,
+/// see [`BevyMagicLight2DSettings::layer_indirect`]. Direct light is not
+/// affected. Extra lit layers use the objects multiplier.
+#[derive(Copy, Clone, Debug, Reflect)]
+pub struct LayerIndirect
+{
+    pub floor:   f32,
+    pub walls:   f32,
+    pub objects: f32,
+}
+
+impl Default for LayerIndirect
+{
+    fn default() -> Self
+    {
+        Self {
+            floor:   1.0,
+            walls:   1.0,
+            objects: 1.0,
+        }
+    }
+}
+
+impl From<LayerIndirect> for Vec3
+{
+    fn from(value: LayerIndirect) -> Self
+    {
+        Vec3::new(value.floor, value.walls, value.objects).max(Vec3::ZERO)
+    }
+}
+
+/// Glowing outline drawn along occluder edges in the composite, see
+/// [`BevyMagicLight2DSettings::occluder_outline`].
+#[derive(Copy, Clone, Debug, Reflect)]
+pub struct OccluderOutline
+{
+    /// Color added on top of the lit scene, values above `1.0` feed bloom
+    /// on HDR cameras.
+    pub color:     Color,
+    /// Width of the glow on either side of the edge, in world units.
+    pub thickness: f32,
+}
+
+impl Default for OccluderOutline
+{
+    fn default() -> Self
+    {
+        Self {
+            color:     Color::srgb(0.2, 1.0, 1.0),
+            thickness: 2.0,
+        }
+    }
+}
+
+/// Upscaling filter applied to irradiance in the post-processing pass.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum IrradianceFilter
+{
+    #[default]
+    Nearest,
+    Linear,
+}
+
+/// How the direct light of overlapping lights is combined.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum LightAccumulation
+{
+    /// Contributions are summed, physically plausible but many overlapping
+    /// lights quickly blow out to white.
+    #[default]
+    Additive,
+    /// Only the brightest contribution is kept, per color channel. Dense
+    /// clusters of lights stay at the brightness of the strongest one, for a
+    /// more stylized look.
+    Max,
+}
+
+/// Filter presenting the composite at full resolution, see
+/// [`BevyMagicLight2DSettings::full_resolution_output`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum UpscaleFilter
+{
+    /// Blocky, keeps pixel art crisp at integer scales.
+    #[default]
+    Nearest,
+    Bilinear,
+    /// Bilinear with an unsharp mask, recovers some of the edge contrast
+    /// lost by the interpolation.
+    Sharpen,
+}
+
+/// How color is stored in the layer targets.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum LayerAlpha
+{
+    /// Color is independent of alpha.
+    #[default]
+    Straight,
+    /// Color is already multiplied by alpha. This is what alpha blended
+    /// sprites produce in a target cleared to transparent black, compositing
+    /// it as straight alpha darkens soft edges.
+    Premultiplied,
+}
+
+/// Output of the post-processing pass.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum DebugView
+{
+    /// Scene colors lit by the filtered irradiance.
+    #[default]
+    Final,
+    /// Filtered irradiance only, without scene colors.
+    IrradianceOnly,
+    /// Distance field of the occluders.
+    SdfOnly,
+    /// Temporally blended probe irradiance, before filtering.
+    ProbesOnly,
+    /// Distance field of the occluders as a heatmap over the unlit scene,
+    /// near is red and far is blue.
+    SdfHeatmap,
+}
+
+#[derive(Copy, Clone, Reflect)]
+pub struct PostProcessingCameraParams
+{
+    /// Render order of the post-processing camera. Must not collide with
+    /// other cameras rendering to the window.
+    pub order:       isize,
+    /// Clear behavior of the post-processing camera.
+    pub clear_color: ClearColorConfig,
+    /// Whether the post-processing camera renders in HDR. Bloom needs HDR,
+    /// it is removed from the camera while this is off.
+    pub hdr:         bool,
+}
+
+impl Default for PostProcessingCameraParams
+{
+    fn default() -> Self
+    {
+        Self {
+            order:       1,
+            clear_color: ClearColorConfig::Default,
+            hdr:         true,
+        }
+    }
 }
 
 #[rustfmt::skip]
@@ -34,7 +423,8 @@ pub struct BevyMagicLight2DSettings
 #[cfg_attr(feature = "egui", derive(InspectorOptions))]
 #[cfg_attr(feature = "egui", reflect(InspectorOptions))]
 pub struct LightPassParams {
-    /// Number of previous frames to keep in the reservoir.
+    /// Number of previous frames to keep in the reservoir. Capped by the
+    /// number of tiles in the probe atlas.
     #[cfg_attr(feature = "egui", inspector(min = 1, max = 64))]
     pub reservoir_size: u32,
 
@@ -57,6 +447,84 @@ pub struct LightPassParams {
 
     #[cfg_attr(feature = "egui", inspector(min = 1.0, max = 100.0))]
     pub indirect_rays_radius_factor: f32,
+
+    /// Ambient light added to every probe unconditionally, so that shadowed
+    /// areas never crush to pure black. Alpha is ignored.
+    pub minimum_ambient: LinearRgba,
+
+    /// Light contribution below which a light is considered to have no
+    /// effect. Used to derive each light's radius for culling.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0, max = 1.0))]
+    pub light_cull_epsilon: f32,
+
+    /// Strength of the screen-space specular highlight, 0 disables it.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0, max = 4.0))]
+    pub specular_strength: f32,
+
+    /// Blinn-Phong exponent of the specular highlight, higher values give
+    /// smaller, sharper highlights.
+    #[cfg_attr(feature = "egui", inspector(min = 1.0, max = 256.0))]
+    pub shininess: f32,
+
+    /// Maximum number of lights shaded per probe and frame. When more lights
+    /// are visible, lights are importance sampled by their estimated
+    /// contribution and the temporal reservoir averages the result. 0 shades
+    /// every light.
+    #[cfg_attr(feature = "egui", inspector(min = 0, max = 256))]
+    pub light_sample_budget: u32,
+
+    /// Jitter indirect rays with a tiled blue-noise texture instead of the
+    /// per-frame halton offset shared by all probes. Gives less structured
+    /// noise at low `indirect_rays_per_sample`.
+    pub indirect_blue_noise: bool,
+
+    /// Points sampled on each `RectLightSource2D` per probe and frame. More
+    /// samples give smoother penumbras at a linear cost.
+    #[cfg_attr(feature = "egui", inspector(min = 1, max = 32))]
+    pub rect_light_samples: u32,
+    /// Luminance each bounce and filter sample is clamped to before it is
+    /// accumulated, removes single bright "firefly" pixels from temporal
+    /// sampling. High values disable the clamp.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0))]
+    pub max_luminance_clamp: f32,
+    /// Weight filter samples by how similar their distance to occluders is,
+    /// so light doesn't bleed across thin walls. Disable for the plain
+    /// bilateral blur.
+    pub filter_edge_aware:   bool,
+    /// Height in world units of a `1.0` texel of the height map.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0))]
+    pub height_scale:        f32,
+    /// Height of lights above the scene right under them, in world units.
+    /// Shadows from the height map get longer as lights get lower.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0))]
+    pub light_height:        f32,
+    /// Density of a participating medium filling the scene, per world unit.
+    /// Light travelling through it is dimmed and scattered into a glow that
+    /// extends along unoccluded paths, giving light shafts behind openings.
+    /// The glow fades out towards each light's effective radius. 0 disables
+    /// fog.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0, max = 0.1))]
+    pub fog_density:         f32,
+    /// Tint of the light scattered by the fog. Alpha is ignored.
+    pub fog_color:           LinearRgba,
+    /// Maximum SDF raymarch iterations of shadow and bounce rays. Each step
+    /// advances by the distance to the closest occluder, so rays passing
+    /// many occluders or grazing walls need more steps to reach far lights.
+    /// More steps cost time on every probe, too few cut shadow rays short
+    /// and darken distant lights.
+    #[cfg_attr(feature = "egui", inspector(min = 1, max = 1024))]
+    pub raymarch_max_steps:    u32,
+    /// Distance from a probe, in world units, up to which shadow rays test
+    /// occlusion. Occluders farther along the ray towards a light or the
+    /// sun cast no shadow. Lower it to skip far occluders in small scenes,
+    /// raise it for large open levels.
+    #[cfg_attr(feature = "egui", inspector(min = 0.0))]
+    pub raymarch_max_distance: f32,
+    /// How the direct light of omni lights, rect lights and the sun is
+    /// combined in a probe. Skylight and ambient are always added on top.
+    /// With a `light_sample_budget`, `Max` applies to the sampled estimate
+    /// of each light.
+    pub light_accumulation:    LightAccumulation,
 }
 
 impl Default for LightPassParams
@@ -70,6 +538,22 @@ impl Default for LightPassParams
             indirect_light_contrib:      0.5,
             indirect_rays_per_sample:    32,
             indirect_rays_radius_factor: 3.5,
+            minimum_ambient:             LinearRgba::BLACK,
+            light_cull_epsilon:          0.005,
+            specular_strength:           0.0,
+            shininess:                   32.0,
+            light_sample_budget:         0,
+            indirect_blue_noise:         false,
+            rect_light_samples:          4,
+            max_luminance_clamp:         1000.0,
+            filter_edge_aware:           true,
+            height_scale:                64.0,
+            light_height:                32.0,
+            fog_density:                 0.0,
+            fog_color:                   LinearRgba::WHITE,
+            raymarch_max_steps:          32,
+            raymarch_max_distance:       10000.0,
+            light_accumulation:          LightAccumulation::default(),
         }
     }
 }
@@ -88,6 +572,8 @@ pub struct ComputedTargetSizes
     pub probe_grid_size:  Vec2,
     pub probe_grid_isize: IVec2,
     pub probe_grid_usize: UVec2,
+
+    pub probe_size: UVec2,
 }
 
 impl ComputedTargetSizes
@@ -110,8 +596,9 @@ impl ComputedTargetSizes
             util::align_to_work_group_grid(sizes.sdf_target_size.ceil().as_ivec2());
         sizes.sdf_target_usize = sizes.sdf_target_isize.as_uvec2();
 
+        sizes.probe_size = params.probe_size.max(UVec2::ONE);
         sizes.probe_grid_isize = util::align_to_work_group_grid(
-            (sizes.primary_target_size / (GI_SCREEN_PROBE_SIZE as f32))
+            (sizes.primary_target_size / sizes.probe_size.as_vec2())
                 .ceil()
                 .as_ivec2(),
         );
@@ -121,6 +608,29 @@ impl ComputedTargetSizes
         sizes
     }
 
+    /// Number of probe tiles in the atlas, i.e. the length of the temporal
+    /// frame cycle.
+    pub fn probe_atlas_frames(&self) -> u32
+    {
+        self.probe_size.x * self.probe_size.y
+    }
+
+    /// Reservoir size actually used on the GPU, capped by the probe atlas.
+    pub fn reservoir_size(&self, requested: u32) -> u32
+    {
+        requested.clamp(1, self.probe_atlas_frames().max(1))
+    }
+
+    /// Length of the temporal frame cycle: the largest multiple of the
+    /// reservoir size that fits in the probe atlas. The frame counter wraps
+    /// at this value, so the halton sample index (`frame % reservoir_size`)
+    /// never jumps when it does.
+    pub fn frame_cycle(&self, requested_reservoir_size: u32) -> u32
+    {
+        let reservoir_size = self.reservoir_size(requested_reservoir_size);
+        self.probe_atlas_frames().max(1) / reservoir_size * reservoir_size
+    }
+
     pub fn is_valid(&self) -> bool
     {
         self.primary_target_usize.x > 0
@@ -131,3 +641,507 @@ impl ComputedTargetSizes
             && self.probe_grid_usize.y > 0
     }
 }
+
+/// Sent once, the first frame the GI pipeline has created all of its bind
+/// groups and is able to render lighting.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct GiPipelineReady;
+
+/// Readiness flag shared between the main world and the render world.
+///
+/// The render world sets it from `system_queue_bind_groups`, the main world
+/// reads it back and emits [`GiPipelineReady`].
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub struct GiPipelineStatus
+{
+    ready: Arc<AtomicBool>,
+}
+
+impl GiPipelineStatus
+{
+    pub fn is_ready(&self) -> bool
+    {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn mark_ready(&self)
+    {
+        self.ready.store(true, Ordering::Release);
+    }
+}
+
+/// Health of the GI pipeline, updated every frame by the render world.
+///
+/// Shared with the render world like [`GiPipelineStatus`], so it can be read
+/// from main world systems, e.g. to show a HUD indicator or to wait for
+/// lighting before revealing a level.
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub struct GiStatus
+{
+    camera_found:        Arc<AtomicBool>,
+    targets_initialized: Arc<AtomicBool>,
+    bind_groups_ready:   Arc<AtomicBool>,
+}
+
+impl GiStatus
+{
+    /// Whether there is exactly one `FloorCamera` with a usable projection.
+    pub fn camera_found(&self) -> bool
+    {
+        self.camera_found.load(Ordering::Acquire)
+    }
+
+    /// Whether the GI targets have been created.
+    pub fn targets_initialized(&self) -> bool
+    {
+        self.targets_initialized.load(Ordering::Acquire)
+    }
+
+    /// Whether the bind groups of the compute passes were created this
+    /// frame.
+    pub fn bind_groups_ready(&self) -> bool
+    {
+        self.bind_groups_ready.load(Ordering::Acquire)
+    }
+
+    /// Whether GI was computed with a valid camera this frame.
+    pub fn is_healthy(&self) -> bool
+    {
+        self.camera_found() && self.targets_initialized() && self.bind_groups_ready()
+    }
+
+    pub(crate) fn set_camera_found(&self, value: bool)
+    {
+        self.camera_found.store(value, Ordering::Release);
+    }
+
+    pub(crate) fn set_targets_initialized(&self, value: bool)
+    {
+        self.targets_initialized.store(value, Ordering::Release);
+    }
+
+    pub(crate) fn set_bind_groups_ready(&self, value: bool)
+    {
+        self.bind_groups_ready.store(value, Ordering::Release);
+    }
+}
+
+/// Lights kept in [`GiActiveLights`], further lights are only counted.
+pub const ACTIVE_LIGHTS_SNAPSHOT_CAPACITY: usize = 256;
+
+/// An `OmniLightSource2D` as it was uploaded to the GPU.
+#[derive(Clone, Copy, Debug)]
+pub struct ActiveLight
+{
+    pub entity:    Entity,
+    /// World position, including `jitter_translation`.
+    pub position:  Vec2,
+    /// Intensity including `jitter_intensity` and any `ModulatedLight`.
+    pub intensity: f32,
+    /// Culling radius, see `OmniLightSource2D::effective_radius`.
+    pub radius:    f32,
+}
+
+/// Omni lights uploaded to the GPU by the last GI update, written by the
+/// render world and shared like [`GiStatus`], e.g. for a lighting debugger
+/// showing "37/50 lights active".
+///
+/// Lights are left out when disabled, hidden, out of reach of the view, or
+/// over `MAX_LIGHTS`.
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub struct GiActiveLights
+{
+    snapshot: Arc<Mutex<ActiveLightsSnapshot>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ActiveLightsSnapshot
+{
+    /// Number of `OmniLightSource2D` entities, active or not.
+    pub total:  usize,
+    /// Number of lights uploaded to the GPU.
+    pub active: usize,
+    /// The first [`ACTIVE_LIGHTS_SNAPSHOT_CAPACITY`] active lights.
+    pub lights: Vec<ActiveLight>,
+}
+
+impl GiActiveLights
+{
+    /// Copy of the lights of the last GI update.
+    pub fn snapshot(&self) -> ActiveLightsSnapshot
+    {
+        self.snapshot.lock().map(|snapshot| snapshot.clone()).unwrap_or_default()
+    }
+
+    pub(crate) fn update(&self, total: usize, active: usize, lights: impl Iterator<Item = ActiveLight>)
+    {
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            snapshot.total = total;
+            snapshot.active = active;
+            snapshot.lights.clear();
+            snapshot.lights.extend(lights.take(ACTIVE_LIGHTS_SNAPSHOT_CAPACITY));
+        }
+    }
+}
+
+/// Controls how the temporal frame counter advances.
+///
+/// By default it advances once per rendered frame. With `auto` disabled it
+/// only advances once per [`FrameStepControl::step`], which makes captures
+/// deterministic, e.g. stepping through a full frame cycle per video frame.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct FrameStepControl
+{
+    pub auto:      bool,
+    pending:       u32,
+    advance:       bool,
+    reset:         bool,
+    reset_pending: bool,
+}
+
+impl Default for FrameStepControl
+{
+    fn default() -> Self
+    {
+        Self {
+            auto:          true,
+            pending:       0,
+            advance:       true,
+            reset:         false,
+            reset_pending: false,
+        }
+    }
+}
+
+impl FrameStepControl
+{
+    /// Advances the frame counter by one on the next rendered frame. Steps
+    /// requested in the same frame are spread over the following frames,
+    /// they are ignored while `auto` is set.
+    pub fn step(&mut self)
+    {
+        self.pending += 1;
+    }
+
+    /// Whether the frame counter advances on the frame being extracted.
+    pub fn advances(&self) -> bool
+    {
+        self.advance
+    }
+
+    /// Drops the temporal history on the next frame, e.g. after a teleport
+    /// or a scene cut, so lighting of the previous view doesn't ghost into
+    /// the new one. The history then refills over one reservoir.
+    pub fn request_temporal_reset(&mut self)
+    {
+        self.reset_pending = true;
+    }
+
+    /// Whether the temporal history is reset on the frame being extracted.
+    pub fn resets(&self) -> bool
+    {
+        self.reset
+    }
+}
+
+pub(crate) fn system_update_frame_step(
+    mut frame_step: ResMut<FrameStepControl>,
+    warmup: Res<GiWarmup>,
+)
+{
+    frame_step.reset = std::mem::take(&mut frame_step.reset_pending);
+    if frame_step.auto || warmup.is_warming_up() {
+        frame_step.pending = 0;
+        frame_step.advance = true;
+    } else {
+        frame_step.advance = frame_step.pending > 0;
+        frame_step.pending = frame_step.pending.saturating_sub(1);
+    }
+}
+
+/// Decides which frames recompute GI, see
+/// [`BevyMagicLight2DSettings::gi_update_hz`].
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub(crate) struct GiUpdateClock
+{
+    accumulator: f32,
+    update_due:  bool,
+}
+
+impl GiUpdateClock
+{
+    pub(crate) fn update_due(&self) -> bool
+    {
+        self.update_due
+    }
+}
+
+pub(crate) fn system_update_gi_clock(
+    mut clock: ResMut<GiUpdateClock>,
+    settings: Res<BevyMagicLight2DSettings>,
+    warmup: Res<GiWarmup>,
+    light_bake: Res<LightBake>,
+    time: Res<Time>,
+)
+{
+    // The bake copy only happens on a GI update, it can't be throttled.
+    let hz = settings
+        .gi_update_hz
+        .filter(|hz| *hz > 0.0 && !warmup.is_warming_up() && !light_bake.is_copying());
+    let Some(hz) = hz else {
+        clock.accumulator = 0.0;
+        clock.update_due = true;
+        return;
+    };
+
+    let period = 1.0 / hz;
+    clock.accumulator += time.delta_secs();
+    clock.update_due = clock.accumulator >= period;
+    if clock.update_due {
+        // Don't catch up on missed updates after a long frame.
+        clock.accumulator = (clock.accumulator - period).min(period);
+    }
+}
+
+/// Pre-converges GI before the first visible frame, e.g. during a loading
+/// screen.
+///
+/// While warming up, GI is recomputed and the temporal frame counter
+/// advances every frame regardless of `gi_update_hz` and
+/// [`FrameStepControl`], and the post-processing camera is deactivated so
+/// the still noisy composite isn't presented. Frames only count once the
+/// pipeline is ready.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct GiWarmup
+{
+    requested: Option<u32>,
+    remaining: u32,
+}
+
+impl GiWarmup
+{
+    /// Warms up for `frames` frames. `0` warms up for one full temporal
+    /// reservoir, after which every history slot holds a current sample.
+    pub fn start(&mut self, frames: u32)
+    {
+        self.requested = Some(frames);
+    }
+
+    /// Whether a warmup is requested or running.
+    pub fn is_warming_up(&self) -> bool
+    {
+        self.requested.is_some() || self.remaining > 0
+    }
+
+    /// Frames left until the warmup finishes.
+    pub fn remaining(&self) -> u32
+    {
+        self.remaining
+    }
+}
+
+pub(crate) fn system_update_gi_warmup(
+    mut warmup: ResMut<GiWarmup>,
+    settings: Res<BevyMagicLight2DSettings>,
+    target_sizes: Res<ComputedTargetSizes>,
+    pipeline_status: Res<GiPipelineStatus>,
+)
+{
+    if let Some(frames) = warmup.requested.take() {
+        warmup.remaining = match frames {
+            0 => target_sizes.reservoir_size(settings.light_pass_params.reservoir_size),
+            frames => frames,
+        };
+        return;
+    }
+    if warmup.remaining > 0 && pipeline_status.is_ready() {
+        warmup.remaining -= 1;
+    }
+}
+
+/// External signal driving [`ModulatedLight`](crate::gi::types::ModulatedLight)s,
+/// e.g. a beat envelope from audio analysis. The game writes `value` at
+/// whatever rate it has new data, the lights follow [`Self::level`].
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct LightModulator
+{
+    pub value:     f32,
+    /// Time constant in seconds the level follows `value` with, smoothing
+    /// the same regardless of frame rate. `0.0` follows it immediately.
+    pub smoothing: f32,
+    level:         f32,
+}
+
+impl Default for LightModulator
+{
+    fn default() -> Self
+    {
+        Self {
+            value:     1.0,
+            smoothing: 0.0,
+            level:     1.0,
+        }
+    }
+}
+
+impl LightModulator
+{
+    /// Smoothed value applied to lights this frame.
+    pub fn level(&self) -> f32
+    {
+        self.level
+    }
+}
+
+pub(crate) fn system_update_light_modulator(mut modulator: ResMut<LightModulator>, time: Res<Time>)
+{
+    let level = if modulator.smoothing > 0.0 {
+        let t = 1.0 - (-time.delta_secs() / modulator.smoothing).exp();
+        modulator.level + (modulator.value - modulator.level) * t
+    } else {
+        modulator.value
+    };
+    if modulator.level != level {
+        modulator.level = level;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::gi::WORKGROUP_SIZE;
+
+    fn window(width: u32, height: u32, scale_factor: f32) -> Window
+    {
+        let mut window = Window::default();
+        window.resolution.set_physical_resolution(width, height);
+        window.resolution.set_scale_factor_override(Some(scale_factor));
+        window
+    }
+
+    fn params(sdf_scale: f32, probe_size: u32) -> TargetScalingParams
+    {
+        TargetScalingParams {
+            sdf_scale,
+            probe_size: UVec2::splat(probe_size),
+        }
+    }
+
+    fn assert_aligned(size: UVec2)
+    {
+        assert_eq!(size.x % WORKGROUP_SIZE, 0, "{size} not aligned");
+        assert_eq!(size.y % WORKGROUP_SIZE, 0, "{size} not aligned");
+    }
+
+    #[test]
+    fn default_params()
+    {
+        let sizes = ComputedTargetSizes::from_window(&window(800, 600, 1.0), &default());
+
+        assert!(sizes.is_valid());
+        assert_eq!(sizes.primary_target_usize, UVec2::new(800, 600));
+        assert_eq!(sizes.sdf_target_usize, UVec2::new(400, 304));
+        assert_eq!(sizes.probe_size, UVec2::splat(8));
+        assert_eq!(sizes.probe_grid_usize, UVec2::new(104, 80));
+    }
+
+    #[test]
+    fn scale_factor_uses_logical_size()
+    {
+        let sizes = ComputedTargetSizes::from_window(&window(1600, 1200, 2.0), &default());
+
+        assert_eq!(sizes.primary_target_usize, UVec2::new(800, 600));
+        assert_eq!(sizes.probe_grid_usize, UVec2::new(104, 80));
+    }
+
+    #[test]
+    fn sizes_cover_window_and_are_aligned()
+    {
+        let windows = [(800, 600), (1280, 720), (1920, 1080), (1, 1), (333, 777), (2561, 1441)];
+        let params = [params(0.5, 8), params(1.0, 4), params(0.25, 16), params(0.3, 5)];
+
+        for (width, height) in windows {
+            for p in params {
+                let sizes = ComputedTargetSizes::from_window(&window(width, height, 1.0), &p);
+                let primary = sizes.primary_target_size;
+
+                assert!(sizes.is_valid());
+                assert_aligned(sizes.sdf_target_usize);
+                assert_aligned(sizes.probe_grid_usize);
+
+                // Targets are padded, never cropped.
+                let sdf_size = sizes.sdf_target_usize.as_vec2();
+                assert!(sdf_size.cmpge((primary * p.sdf_scale).ceil()).all());
+                assert!((sizes.probe_grid_size * p.probe_size.as_vec2()).cmpge(primary).all());
+
+                // Padding is less than one workgroup.
+                let wg = Vec2::splat(WORKGROUP_SIZE as f32);
+                assert!(sdf_size.cmplt((primary * p.sdf_scale).ceil() + wg).all());
+                assert!(sizes
+                    .probe_grid_size
+                    .cmplt((primary / p.probe_size.as_vec2()).ceil() + wg)
+                    .all());
+            }
+        }
+    }
+
+    #[test]
+    fn zero_probe_size_is_clamped()
+    {
+        let sizes = ComputedTargetSizes::from_window(&window(64, 64, 1.0), &params(0.5, 0));
+
+        assert_eq!(sizes.probe_size, UVec2::ONE);
+        assert_eq!(sizes.probe_grid_usize, UVec2::new(64, 64));
+        assert!(sizes.is_valid());
+    }
+
+    #[test]
+    fn minimized_window_is_invalid()
+    {
+        for (width, height) in [(0, 0), (0, 600), (800, 0)] {
+            let sizes = ComputedTargetSizes::from_window(&window(width, height, 1.0), &default());
+            assert!(!sizes.is_valid(), "{width}x{height} should be invalid");
+        }
+
+        assert!(!ComputedTargetSizes::default().is_valid());
+    }
+
+    #[test]
+    fn frame_cycle_is_multiple_of_reservoir()
+    {
+        let sizes = ComputedTargetSizes::from_window(&window(800, 600, 1.0), &params(0.5, 8));
+
+        assert_eq!(sizes.probe_atlas_frames(), 64);
+        for requested in [0, 1, 3, 8, 10, 64, 100] {
+            let reservoir_size = sizes.reservoir_size(requested);
+            let frame_cycle = sizes.frame_cycle(requested);
+            assert!((1..=64).contains(&reservoir_size));
+            assert_eq!(frame_cycle % reservoir_size, 0);
+            assert!(frame_cycle <= 64);
+        }
+    }
+
+    #[test]
+    fn pixel_art_mode_overrides_filters()
+    {
+        let mut settings = BevyMagicLight2DSettings {
+            irradiance_filter: IrradianceFilter::Linear,
+            full_resolution_output: Some(UpscaleFilter::Sharpen),
+            ..default()
+        };
+        settings.light_pass_params.smooth_kernel_size = (2, 3);
+
+        assert_eq!(settings.effective_irradiance_filter(), IrradianceFilter::Linear);
+        assert_eq!(settings.effective_upscale_filter(), Some(UpscaleFilter::Sharpen));
+        assert_eq!(settings.effective_smooth_kernel_size(), (2, 3));
+
+        settings.pixel_art_mode = true;
+        assert_eq!(settings.effective_irradiance_filter(), IrradianceFilter::Nearest);
+        assert_eq!(settings.effective_upscale_filter(), Some(UpscaleFilter::Nearest));
+        assert_eq!(settings.effective_smooth_kernel_size(), (0, 0));
+
+        settings.full_resolution_output = None;
+        assert_eq!(settings.effective_upscale_filter(), None);
+    }
+}
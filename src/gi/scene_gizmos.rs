@@ -0,0 +1,138 @@
+//! Gizmo overlay of the lights, occluders and skylight masks in the scene,
+//! see [`BevyMagicLight2DSettings::scene_gizmos`].
+
+use bevy::prelude::*;
+
+use crate::gi::compositing::PostProcessingCamera;
+use crate::gi::resource::BevyMagicLight2DSettings;
+use crate::gi::types::{LightOccluder2D, OmniLightSource2D, RectLightSource2D, SkylightMask2D};
+use crate::FloorCamera;
+
+/// Colors and scale of the scene gizmo overlay.
+#[derive(Copy, Clone, Debug, Reflect)]
+pub struct SceneGizmoParams
+{
+    pub light_color:         Color,
+    pub occluder_color:      Color,
+    pub skylight_mask_color: Color,
+    /// Multiplier of the drawn light radius, the light's effective radius
+    /// (see `LightPassParams::light_cull_epsilon`) at `1.0`. Lights without
+    /// a finite radius only get their center marker.
+    pub light_radius_scale:  f32,
+    /// Radius of the light center markers, in world units.
+    pub light_marker_size:   f32,
+}
+
+impl Default for SceneGizmoParams
+{
+    fn default() -> Self
+    {
+        Self {
+            light_color:         Color::srgb(1.0, 0.9, 0.3),
+            occluder_color:      Color::srgb(0.3, 0.6, 1.0),
+            skylight_mask_color: Color::srgb(0.7, 0.4, 1.0),
+            light_radius_scale:  1.0,
+            light_marker_size:   4.0,
+        }
+    }
+}
+
+/// Gizmos of the overlay, drawn by the post-processing camera like
+/// [`LightRayGizmos`](crate::gi::light_ray_debug::LightRayGizmos).
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct SceneGizmos;
+
+pub(crate) fn system_setup_scene_gizmos(
+    mut config_store: ResMut<GizmoConfigStore>,
+    settings: Res<BevyMagicLight2DSettings>,
+)
+{
+    let (config, _) = config_store.config_mut::<SceneGizmos>();
+    config.render_layers = settings.render_layers.post_processing_layers();
+}
+
+#[rustfmt::skip]
+pub(crate) fn system_draw_scene_gizmos(
+    mut gizmos:              Gizmos<SceneGizmos>,
+        settings:            Res<BevyMagicLight2DSettings>,
+        query_lights:        Query<(&GlobalTransform, &OmniLightSource2D, &InheritedVisibility)>,
+        query_rect_lights:   Query<(&GlobalTransform, &RectLightSource2D, &InheritedVisibility)>,
+        query_occluders:     Query<(&GlobalTransform, &LightOccluder2D, &InheritedVisibility)>,
+        query_skylight_mask: Query<(&GlobalTransform, &SkylightMask2D, &InheritedVisibility)>,
+        query_camera:        Query<(&Camera, &GlobalTransform), With<FloorCamera>>,
+        query_post_camera:   Query<(&Camera, &GlobalTransform), With<PostProcessingCamera>>,
+) {
+    let Some(params) = settings.scene_gizmos else {
+        return;
+    };
+    let (Ok((camera, transform)), Ok((post_camera, post_transform))) =
+        (query_camera.single(), query_post_camera.single())
+    else {
+        return;
+    };
+
+    // Scene positions are drawn where the composite shows them.
+    let to_overlay = |world_pose: Vec2| {
+        camera
+            .world_to_viewport(transform, world_pose.extend(0.0))
+            .and_then(|viewport| post_camera.viewport_to_world_2d(post_transform, viewport))
+            .ok()
+    };
+    let mut circle = |center: Vec2, radius: f32, color: Color| {
+        if !radius.is_finite() || radius <= 0.0 {
+            return;
+        }
+        if let (Some(overlay_center), Some(overlay_edge)) =
+            (to_overlay(center), to_overlay(center + Vec2::X * radius))
+        {
+            gizmos.circle_2d(overlay_center, overlay_center.distance(overlay_edge), color);
+        }
+    };
+
+    let epsilon = settings.light_pass_params.light_cull_epsilon;
+    let mut boxes = Vec::new();
+
+    for (t, light, hviz) in query_lights.iter() {
+        if !light.enabled || !hviz.get() {
+            continue;
+        }
+        let center = t.translation().xy();
+        circle(center, params.light_marker_size, params.light_color);
+        circle(center, light.effective_radius(epsilon) * params.light_radius_scale, params.light_color);
+    }
+    for (t, light, hviz) in query_rect_lights.iter() {
+        if !light.enabled || !hviz.get() {
+            continue;
+        }
+        let center = t.translation().xy();
+        circle(center, light.effective_radius(epsilon) * params.light_radius_scale, params.light_color);
+        boxes.push((box_outline(t, light.half_size), params.light_color));
+    }
+    for (t, occluder, hviz) in query_occluders.iter() {
+        if hviz.get() {
+            boxes.push((box_outline(t, occluder.h_size), params.occluder_color));
+        }
+    }
+    for (t, mask, hviz) in query_skylight_mask.iter() {
+        if hviz.get() {
+            boxes.push((box_outline(t, mask.h_size), params.skylight_mask_color));
+        }
+    }
+
+    for (outline, color) in boxes {
+        let outline = outline.map(&to_overlay);
+        if outline.iter().all(Option::is_some) {
+            gizmos.linestrip_2d(outline.into_iter().flatten(), color);
+        }
+    }
+}
+
+/// Closed outline of a box of half size `h_size` in world space. Scale is
+/// ignored, like when the boxes are uploaded.
+fn box_outline(transform: &GlobalTransform, h_size: Vec2) -> [Vec2; 5]
+{
+    let (rotation, center) = (transform.rotation(), transform.translation().xy());
+    let corner = |x: f32, y: f32| center + (rotation * (h_size * Vec2::new(x, y)).extend(0.0)).truncate();
+    let first = corner(-1.0, -1.0);
+    [first, corner(1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0), first]
+}
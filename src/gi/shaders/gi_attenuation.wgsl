@@ -17,6 +17,16 @@ fn light_attenuation_r_two(
     return clamp(att, 0.0, 1000.0);
 }
 
+fn light_attenuation_at_dist_r_two(
+    d: f32,
+    a: f32,
+    b: f32,
+    c: f32,
+) -> f32 {
+    let att = a / (b + c * d * d);
+    return clamp(att, 0.0, 1000.0);
+}
+
 fn light_attenuation_r(
     sample_pose: vec2<f32>,
     light_pose:  vec2<f32>,
@@ -7,6 +7,7 @@ struct CameraParams {
     inverse_view_proj :  mat4x4<f32>,
     sdf_scale: vec2<f32>,
     inv_sdf_scale: vec2<f32>,
+    display_scale: f32,
 }
 
 fn screen_to_ndc(
@@ -34,6 +35,16 @@ fn screen_to_world(
     return (inverse_view_proj * vec4<f32>(screen_to_ndc(screen_pose, screen_size, screen_size_inv), 0.0, 1.0)).xy;
 }
 
+// Converts an offset in screen pixels to world units. Unlike positions, this
+// holds for any projection scaling, not only one world unit per pixel.
+fn screen_offset_to_world(
+    screen_offset:     vec2<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    screen_size_inv:   vec2<f32>) -> vec2<f32> {
+    let ndc_offset = vec2<f32>(screen_offset.x, -screen_offset.y) * screen_size_inv * 2.0;
+    return (inverse_view_proj * vec4<f32>(ndc_offset, 0.0, 0.0)).xy;
+}
+
 fn world_to_ndc(
     world_pose:  vec2<f32>,
     view_proj:   mat4x4<f32>) -> vec2<f32> {
@@ -47,6 +58,11 @@ fn world_to_screen(
     return ndc_to_screen(world_to_ndc(world_pose, view_proj), screen_size);
 }
 
+fn ndc_to_screen_uv(ndc: vec2<f32>) -> vec2<f32> {
+    let uv = (ndc + 1.0) * 0.5;
+    return vec2<f32>(uv.x, 1.0 - uv.y);
+}
+
 fn world_to_sdf_uv(world_pose: vec2<f32>, view_proj: mat4x4<f32>, inv_sdf_scale: vec2<f32>) -> vec2<f32> {
     let ndc = world_to_ndc(world_pose, view_proj);
     let ndc_sdf = ndc * inv_sdf_scale;
@@ -1,5 +1,5 @@
 #define_import_path bevy_magic_light_2d::gi_math
-#import bevy_magic_light_2d::gi_types::Quaternion
+#import bevy_magic_light_2d::gi_types::{Quaternion, LightOccluder}
 
 // [Drobot2014a] Low Level Optimizations for GCN
 fn fast_sqrt(x: f32) -> f32 {
@@ -60,4 +60,47 @@ fn quat_dot(quat1: Quaternion, quat2: Quaternion) -> Quaternion {
 fn quat_mul(q: Quaternion, v: vec3<f32>) -> vec3<f32> {
     let r = quat_dot(q, quat_dot(Quaternion(vec4<f32>(v, 0.0)), quat_inv(q)));
     return r.data.xyz;
-}
\ No newline at end of file
+}
+
+/// Signed distance from a world-space point to a box occluder.
+/// `occluder.rotation` is the inverse of the occluder's world rotation, so
+/// applying it to the offset from the center yields the point in the
+/// occluder's local (axis-aligned) frame.
+fn sdf_occluder(p: vec2<f32>, occluder: LightOccluder) -> f32 {
+    let local_p = quat_mul(occluder.rotation, vec3<f32>(p - occluder.center, 0.0)).xy;
+    // Rounded box: the box shrunk by the radius, inflated back by it.
+    let r       = min(occluder.corner_radius, min(occluder.h_extent.x, occluder.h_extent.y));
+    let d       = abs(local_p) - occluder.h_extent + r;
+    let d_max   = max(d, vec2<f32>(0.0));
+    let d_o     = length(d_max);
+    let d_i     = min(max(d.x, d.y), 0.0);
+    return d_o + d_i - r;
+}
+
+/// Distance along `dir` (normalized) from `p` to where the ray leaves the
+/// occluder's box.
+fn occluder_ray_exit(p: vec2<f32>, dir: vec2<f32>, occluder: LightOccluder) -> f32 {
+    let local_p   = quat_mul(occluder.rotation, vec3<f32>(p - occluder.center, 0.0)).xy;
+    let local_dir = quat_mul(occluder.rotation, vec3<f32>(dir, 0.0)).xy;
+
+    var t_far = 1e+10;
+    for (var axis = 0; axis < 2; axis++) {
+        if abs(local_dir[axis]) < 1e-6 {
+            continue;
+        }
+        let t0 = (-occluder.h_extent[axis] - local_p[axis]) / local_dir[axis];
+        let t1 = ( occluder.h_extent[axis] - local_p[axis]) / local_dir[axis];
+        t_far  = min(t_far, max(t0, t1));
+    }
+    return max(t_far, 0.0);
+}
+
+// Scales `color` down so its luminance doesn't exceed `max_luminance`, the
+// hue is kept.
+fn clamp_luminance(color: vec3<f32>, max_luminance: f32) -> vec3<f32> {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if luminance <= max_luminance {
+        return color;
+    }
+    return color * (max_luminance / luminance);
+}
@@ -13,6 +13,207 @@
 @group(2) @binding(5) var in_objects_sampler:            sampler;
 @group(2) @binding(6) var in_irradiance_texture:         texture_2d<f32>;
 @group(2) @binding(7) var in_irradiance_texture_sampler: sampler;
+@group(2) @binding(8) var in_sdf_texture:                texture_2d<f32>;
+@group(2) @binding(9) var in_sdf_sampler:                sampler;
+@group(2) @binding(10) var in_probes_texture:            texture_2d<f32>;
+@group(2) @binding(11) var in_probes_sampler:            sampler;
+@group(2) @binding(12) var<uniform> debug_view:          u32;
+@group(2) @binding(13) var in_extra_texture_0:            texture_2d<f32>;
+@group(2) @binding(14) var in_extra_sampler_0:            sampler;
+@group(2) @binding(15) var in_extra_texture_1:            texture_2d<f32>;
+@group(2) @binding(16) var in_extra_sampler_1:            sampler;
+@group(2) @binding(17) var in_extra_texture_2:            texture_2d<f32>;
+@group(2) @binding(18) var in_extra_sampler_2:            sampler;
+@group(2) @binding(19) var in_extra_texture_3:            texture_2d<f32>;
+@group(2) @binding(20) var in_extra_sampler_3:            sampler;
+@group(2) @binding(21) var<uniform> extra_layer_count:   u32;
+@group(2) @binding(22) var<uniform> irradiance_filter:   u32;
+@group(2) @binding(23) var<uniform> layer_alpha:         u32;
+@group(2) @binding(24) var<uniform> gi_camera_margin:    f32;
+@group(2) @binding(25) var<uniform> contrast:            f32;
+@group(2) @binding(26) var<uniform> saturation:          f32;
+@group(2) @binding(27) var<uniform> exposure:            f32;
+@group(2) @binding(28) var<uniform> vignette:            Vignette;
+@group(2) @binding(29) var<uniform> reflection_strength: f32;
+@group(2) @binding(30) var<uniform> color_bleed:         f32;
+@group(2) @binding(31) var<uniform> layer_indirect:      vec3<f32>;
+@group(2) @binding(32) var<uniform> occluder_outline:    OccluderOutline;
+@group(2) @binding(33) var in_unlit_texture:             texture_2d<f32>;
+@group(2) @binding(34) var in_unlit_sampler:             sampler;
+@group(2) @binding(35) var in_previous_irradiance_texture: texture_2d<f32>;
+@group(2) @binding(36) var in_previous_irradiance_sampler: sampler;
+@group(2) @binding(37) var<uniform> target_transition:   f32;
+@group(2) @binding(38) var<uniform> transparent_background: u32;
+
+struct Vignette {
+    color:    vec3<f32>,
+    radius:   f32,
+    softness: f32,
+    enabled:  u32,
+}
+
+struct OccluderOutline {
+    color:     vec3<f32>,
+    thickness: f32,
+    enabled:   u32,
+}
+
+// GI targets cover the view enlarged by the margin, crop them to the view.
+fn to_gi_uv(uv: vec2<f32>) -> vec2<f32> {
+    return 0.5 + (uv - 0.5) / (1.0 + gi_camera_margin);
+}
+
+// Mirrors `SDF_VIEW_COVERAGE`, the SDF covers this multiple of the GI view.
+const SDF_VIEW_COVERAGE: f32 = 2.0;
+
+// SDF texture coordinates of a point at `gi_uv` in the GI targets.
+fn gi_to_sdf_uv(gi_uv: vec2<f32>) -> vec2<f32> {
+    return 0.5 + (gi_uv - 0.5) / SDF_VIEW_COVERAGE;
+}
+
+const IRRADIANCE_FILTER_LINEAR: u32 = 1u;
+
+// Irradiance targets are float32 and can't be filtered by the sampler, so
+// linear upscaling is done manually.
+fn load_irradiance(t: texture_2d<f32>, s: sampler, uv: vec2<f32>) -> vec3<f32> {
+    if irradiance_filter == IRRADIANCE_FILTER_LINEAR {
+        return bilinear_sample_rgba(t, s, uv).xyz;
+    }
+    return textureSample(t, s, uv).xyz;
+}
+
+// Same as `load_irradiance`, with the indirect luminance in `w`.
+fn load_irradiance_indirect(t: texture_2d<f32>, s: sampler, uv: vec2<f32>) -> vec4<f32> {
+    if irradiance_filter == IRRADIANCE_FILTER_LINEAR {
+        return bilinear_sample_rgba(t, s, uv);
+    }
+    return textureSample(t, s, uv);
+}
+
+// Irradiance at `uv`, faded from the irradiance of the targets before they
+// were rebuilt for new scaling params.
+fn load_composited_irradiance(uv: vec2<f32>) -> vec4<f32> {
+    let irradiance = load_irradiance_indirect(in_irradiance_texture, in_irradiance_texture_sampler, uv);
+    if target_transition <= 0.0 {
+        return irradiance;
+    }
+    let previous = load_irradiance_indirect(in_previous_irradiance_texture, in_previous_irradiance_sampler, uv);
+    return mix(irradiance, previous, target_transition);
+}
+
+// Scales the indirect share of `irradiance` by `multiplier`. Indirect light
+// is assumed to have the same color as the total.
+fn scale_indirect(irradiance: vec4<f32>, multiplier: f32) -> vec3<f32> {
+    let luminance = dot(irradiance.xyz, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let share     = saturate(irradiance.w / max(luminance, 1e-6));
+    return irradiance.xyz * (1.0 + (multiplier - 1.0) * share);
+}
+
+const LAYER_ALPHA_PREMULTIPLIED: u32 = 1u;
+
+// Draws a lit layer over `dst`. Lighting is a per-channel multiply, so a
+// premultiplied color stays premultiplied after it.
+fn composite_layer(dst: vec3<f32>, lit: vec3<f32>, alpha: f32) -> vec3<f32> {
+    if layer_alpha == LAYER_ALPHA_PREMULTIPLIED {
+        return lit + dst * (1.0 - alpha);
+    }
+    return mix(dst, lit, alpha);
+}
+
+// Coverage of `dst` after drawing a layer with `alpha` over it.
+fn composite_alpha(dst: f32, alpha: f32) -> f32 {
+    return alpha + dst * (1.0 - alpha);
+}
+
+// Applied to the final, display encoded, composite.
+fn color_grade(color: vec3<f32>) -> vec3<f32> {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let saturated = mix(vec3<f32>(luminance), color, saturation);
+    return max((saturated - 0.5) * contrast + 0.5, vec3<f32>(0.0));
+}
+
+// Distance is measured in half screen heights, so the falloff is circular.
+fn apply_vignette(color: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    if vignette.enabled == 0u {
+        return color;
+    }
+    var p = (uv - 0.5) * 2.0;
+    p.x *= view.viewport.z / view.viewport.w;
+    let falloff = smoothstep(vignette.radius, vignette.radius + vignette.softness, length(p));
+    return mix(color, vignette.color, falloff);
+}
+
+// Glow along the zero crossing of the SDF, on both sides of the edge.
+fn occluder_outline_glow(gi_uv: vec2<f32>) -> vec3<f32> {
+    if occluder_outline.enabled == 0u {
+        return vec3<f32>(0.0);
+    }
+    let dist = textureSampleLevel(in_sdf_texture, in_sdf_sampler, gi_to_sdf_uv(gi_uv), 0.0).r;
+    let glow = 1.0 - smoothstep(0.0, occluder_outline.thickness, abs(dist));
+    return occluder_outline.color * glow;
+}
+
+// Reflections search this many pixels above a floor pixel for the bottom
+// edge of an object, and fade out towards the end of the search.
+const REFLECTION_MAX_DISTANCE: f32 = 128.0;
+const REFLECTION_STEPS:        i32 = 32;
+// Distance to occluders in world units below which reflections fade out.
+const REFLECTION_SDF_FADE:     f32 = 32.0;
+
+// Objects layer mirrored about the bottom edge of the first object above
+// `uv`, the alpha is the reflection's weight.
+fn floor_reflection(uv: vec2<f32>, gi_uv: vec2<f32>) -> vec4<f32> {
+    if reflection_strength <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    // Pixels covered by an object show the object, not the floor.
+    if textureSampleLevel(in_objects_texture, in_objects_sampler, uv, 0.0).a > 0.5 {
+        return vec4<f32>(0.0);
+    }
+
+    let pixel_size = 1.0 / view.viewport.zw;
+    let step       = REFLECTION_MAX_DISTANCE / f32(REFLECTION_STEPS);
+    for (var i = 1; i <= REFLECTION_STEPS; i++) {
+        let distance = f32(i) * step;
+        let edge_uv  = uv - vec2<f32>(0.0, distance * pixel_size.y);
+        if edge_uv.y < 0.0 {
+            break;
+        }
+        if textureSampleLevel(in_objects_texture, in_objects_sampler, edge_uv, 0.0).a <= 0.5 {
+            continue;
+        }
+
+        let mirrored_uv = uv - vec2<f32>(0.0, 2.0 * distance * pixel_size.y);
+        if mirrored_uv.y < 0.0 {
+            break;
+        }
+        let mirrored = textureSampleLevel(in_objects_texture, in_objects_sampler, mirrored_uv, 0.0);
+        let dist     = textureSampleLevel(in_sdf_texture, in_sdf_sampler, gi_to_sdf_uv(gi_uv), 0.0).r;
+        let fade     = (1.0 - distance / REFLECTION_MAX_DISTANCE)
+                     * saturate(dist / REFLECTION_SDF_FADE);
+        return vec4<f32>(mirrored.rgb, mirrored.a * fade * reflection_strength);
+    }
+    return vec4<f32>(0.0);
+}
+
+const DEBUG_VIEW_FINAL:           u32 = 0u;
+const DEBUG_VIEW_IRRADIANCE_ONLY: u32 = 1u;
+const DEBUG_VIEW_SDF_ONLY:        u32 = 2u;
+const DEBUG_VIEW_PROBES_ONLY:     u32 = 3u;
+const DEBUG_VIEW_SDF_HEATMAP:     u32 = 4u;
+
+// Distance in world units mapped to the far (blue) end of the heatmap.
+const SDF_HEATMAP_RANGE: f32 = 256.0;
+
+// Red -> yellow -> green -> cyan -> blue.
+fn heatmap_color(t: f32) -> vec3<f32> {
+    let h = saturate(t) * 4.0;
+    return saturate(vec3<f32>(
+        2.0 - h,
+        min(h, 4.0 - h),
+        h - 2.0,
+    ));
+}
 
 fn lin_to_srgb(color: vec3<f32>) -> vec3<f32> {
    let x = color * 12.92;
@@ -34,12 +235,42 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
     let in_walls_diffuse   = textureSample(in_walls_texture,   in_walls_sampler, uv);
     let in_objects_diffuse = textureSample(in_objects_texture, in_objects_sampler, uv);
 
-    let in_irradiance = textureSample(in_irradiance_texture, in_irradiance_texture_sampler, uv).xyz;
+    let gi_uv = to_gi_uv(uv);
+    let in_irradiance_indirect = load_composited_irradiance(gi_uv);
+    let in_irradiance          = in_irradiance_indirect.xyz;
+
+    if debug_view == DEBUG_VIEW_IRRADIANCE_ONLY {
+        return vec4<f32>(lin_to_srgb(in_irradiance), 1.0);
+    }
+    if debug_view == DEBUG_VIEW_SDF_ONLY {
+        // Inside occluders is red, distance outside fades from black to white.
+        let dist = textureSample(in_sdf_texture, in_sdf_sampler, gi_to_sdf_uv(gi_uv)).r;
+        if dist <= 0.0 {
+            return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+        }
+        return vec4<f32>(vec3<f32>(saturate(dist / 128.0)), 1.0);
+    }
+    if debug_view == DEBUG_VIEW_SDF_HEATMAP {
+        // Inside occluders is darkened so their outline stands out.
+        let dist  = textureSample(in_sdf_texture, in_sdf_sampler, gi_to_sdf_uv(gi_uv)).r;
+        var heat  = heatmap_color(dist / SDF_HEATMAP_RANGE);
+        if dist <= 0.0 {
+            heat *= 0.5;
+        }
+        var scene = in_floor_diffuse.rgb;
+        scene     = mix(scene, in_walls_diffuse.rgb, in_walls_diffuse.a);
+        scene     = mix(scene, in_objects_diffuse.rgb, in_objects_diffuse.a);
+        return vec4<f32>(mix(lin_to_srgb(scene), heat, 0.6), 1.0);
+    }
+    if debug_view == DEBUG_VIEW_PROBES_ONLY {
+        let probes = load_irradiance(in_probes_texture, in_probes_sampler, gi_uv);
+        return vec4<f32>(lin_to_srgb(probes), 1.0);
+    }
 
     // Calculate object irradiance.
     // TODO: parametrize this filter.
     // TODO: we don't really need to do this per pixel.
-    var object_irradiance = in_irradiance;
+    var object_irradiance = in_irradiance_indirect;
     let k_size = 3;
     let k_width = 28;
 
@@ -47,13 +278,9 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
         for (var j = -k_size; j < 0; j++) {
 
             let offset = vec2<f32>(f32(i * k_width), f32(j * k_width));
-            let irradiance_uv = coords_to_viewport_uv(position.xy - offset, view.viewport);
+            let irradiance_uv = to_gi_uv(coords_to_viewport_uv(position.xy - offset, view.viewport));
 
-            let sample_irradiance = textureSample(
-                in_irradiance_texture,
-                in_irradiance_texture_sampler,
-                irradiance_uv
-            ).xyz;
+            let sample_irradiance = load_composited_irradiance(irradiance_uv);
 
             // TODO: Might also need a visibility check here.
             if any(irradiance_uv < vec2<f32>(0.0)) || any(irradiance_uv > vec2<f32>(1.0)) {
@@ -64,16 +291,51 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
         }
     }
 
-    let floor_irradiance_srgb   = lin_to_srgb(in_irradiance);
-    let objects_irradiance_srgb = lin_to_srgb(object_irradiance);
+    let floor_irradiance_srgb   = lin_to_srgb(scale_indirect(in_irradiance_indirect, layer_indirect.x) * exposure);
+    let walls_irradiance_srgb   = lin_to_srgb(scale_indirect(in_irradiance_indirect, layer_indirect.y) * exposure);
+    let objects_irradiance      = scale_indirect(object_irradiance, layer_indirect.z);
+    // Lower color bleed lights objects with less tint and more brightness.
+    let object_luminance        = dot(objects_irradiance, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let object_bleed_irradiance = mix(vec3<f32>(object_luminance), objects_irradiance, color_bleed);
+    let objects_irradiance_srgb = lin_to_srgb(object_bleed_irradiance * exposure);
 
-    let final_floor   = in_floor_diffuse.xyz   * floor_irradiance_srgb;
-    let final_walls   = in_walls_diffuse.xyz   * floor_irradiance_srgb;
+    // Reflections are lit by the floor they appear on.
+    let reflection    = floor_reflection(uv, gi_uv);
+    let floor_diffuse = mix(in_floor_diffuse.xyz, reflection.rgb, reflection.a);
+    let final_floor   = floor_diffuse          * floor_irradiance_srgb;
+    let final_walls   = in_walls_diffuse.xyz   * walls_irradiance_srgb;
     let final_objects = in_objects_diffuse.xyz * objects_irradiance_srgb;
 
+    // The composite is premultiplied while the layers are drawn. Without a
+    // transparent background the floor is opaque and the coverage stays 1.
     var out = vec4<f32>(final_floor, 1.0);
-        out = vec4<f32>(mix(out.xyz, final_walls.xyz, in_walls_diffuse.w), 1.0);
-        out = vec4<f32>(mix(out.xyz, final_objects.xyz, in_objects_diffuse.w), 1.0);
+    if transparent_background != 0u {
+        out = vec4<f32>(composite_layer(vec3<f32>(0.0), final_floor, in_floor_diffuse.w), in_floor_diffuse.w);
+    }
+    out = vec4<f32>(composite_layer(out.xyz, final_walls.xyz, in_walls_diffuse.w), composite_alpha(out.w, in_walls_diffuse.w));
+    out = vec4<f32>(composite_layer(out.xyz, final_objects.xyz, in_objects_diffuse.w), composite_alpha(out.w, in_objects_diffuse.w));
+
+    // Extra lit layers are lit like objects and drawn on top, in order.
+    let in_extra_diffuse = array<vec4<f32>, 4>(
+        textureSample(in_extra_texture_0, in_extra_sampler_0, uv),
+        textureSample(in_extra_texture_1, in_extra_sampler_1, uv),
+        textureSample(in_extra_texture_2, in_extra_sampler_2, uv),
+        textureSample(in_extra_texture_3, in_extra_sampler_3, uv),
+    );
+    for (var i = 0u; i < min(extra_layer_count, 4u); i++) {
+        let final_extra = in_extra_diffuse[i].xyz * objects_irradiance_srgb;
+        out = vec4<f32>(composite_layer(out.xyz, final_extra, in_extra_diffuse[i].w), composite_alpha(out.w, in_extra_diffuse[i].w));
+    }
+
+    // Unlit sprites keep their own color, on top of every lit layer.
+    let in_unlit_diffuse = textureSample(in_unlit_texture, in_unlit_sampler, uv);
+    out = vec4<f32>(composite_layer(out.xyz, in_unlit_diffuse.xyz, in_unlit_diffuse.w), composite_alpha(out.w, in_unlit_diffuse.w));
+
+    // The outline glows over transparent areas too.
+    let glow = occluder_outline_glow(gi_uv);
+    out = vec4<f32>(out.xyz + glow, max(out.w, max(glow.x, max(glow.y, glow.z))));
 
-    return out;
+    // Graded and output with straight alpha, the material blends it.
+    let color = out.xyz / max(out.w, 1e-4);
+    return vec4<f32>(apply_vignette(color_grade(color), uv), out.w);
 }
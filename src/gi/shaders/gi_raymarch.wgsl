@@ -183,4 +183,92 @@ fn raymarch_bounce(
     }
 
     return RayMarchResult(0, max_steps, h);
+}
+
+// Marches from the origin along a fixed direction. Leaving the SDF,
+// passing `max_distance` or running out of steps counts as unoccluded.
+fn raymarch_directional(
+    ray_origin:    vec2<f32>,
+    ray_direction: vec2<f32>,
+    max_steps:     i32,
+    max_distance:  f32,
+    sdf:           texture_2d<f32>,
+    sdf_sampler:   sampler,
+    camera_params: CameraParams,
+) -> RayMarchResult {
+
+    var ray_progress: f32 = 0.0;
+    var h                 = ray_origin;
+    let min_sdf           = 1e-4;
+
+    for (var i: i32 = 0; i < max_steps; i++) {
+
+        if ray_progress > max_distance {
+            return RayMarchResult(1, i, h);
+        }
+
+        h = ray_origin + ray_progress * ray_direction;
+
+        let uv = world_to_sdf_uv(h, camera_params.view_proj, camera_params.inv_sdf_scale);
+        if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+            return RayMarchResult(1, i, h);
+        }
+
+        let scene_dist = bilinear_sample_r(sdf, sdf_sampler, uv);
+        if scene_dist <= min_sdf {
+            return RayMarchResult(0, i, h);
+        }
+
+        ray_progress += max(scene_dist, 0.5);
+    }
+
+    return RayMarchResult(1, max_steps, h);
+}
+
+// Soft shadow visibility from the origin towards a light, `0.0` in the
+// umbra and `1.0` unoccluded. `tan_angle` is the light's angular radius as
+// seen from the origin. The penumbra at distance `t` along the ray widens
+// with `t`, so shadows are sharp where the occluder meets the receiver, and
+// by `light_distance / (light_distance - t)` as it nears a light at a finite
+// distance.
+fn raymarch_penumbra(
+    ray_origin:     vec2<f32>,
+    ray_direction:  vec2<f32>,
+    max_distance:   f32,
+    light_distance: f32,
+    tan_angle:      f32,
+    max_steps:      i32,
+    sdf:            texture_2d<f32>,
+    sdf_sampler:    sampler,
+    camera_params:  CameraParams,
+) -> f32 {
+
+    var ray_progress: f32 = 0.0;
+    var visibility        = 1.0;
+    let min_sdf           = 1e-4;
+
+    for (var i: i32 = 0; i < max_steps; i++) {
+
+        if ray_progress >= max_distance {
+            break;
+        }
+
+        let h  = ray_origin + ray_progress * ray_direction;
+        let uv = world_to_sdf_uv(h, camera_params.view_proj, camera_params.inv_sdf_scale);
+        if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+            break;
+        }
+
+        let scene_dist = bilinear_sample_r(sdf, sdf_sampler, uv);
+        if scene_dist <= min_sdf {
+            return 0.0;
+        }
+
+        let width  = ray_progress * tan_angle * light_distance / max(light_distance - ray_progress, 1e-3);
+        visibility = min(visibility, scene_dist / max(width, min_sdf));
+
+        ray_progress += max(scene_dist, 0.5);
+    }
+
+    return smoothstep(0.0, 1.0, saturate(visibility));
 }
\ No newline at end of file
@@ -1,19 +1,79 @@
-#import bevy_magic_light_2d::gi_types::LightOccluderBuffer
-#import bevy_magic_light_2d::gi_math::quat_mul
+#import bevy_magic_light_2d::gi_types::{LightOccluderBuffer, TileOccluderGrid}
+#import bevy_magic_light_2d::gi_math::sdf_occluder
 #import bevy_magic_light_2d::gi_camera::{CameraParams, sdf_uv_to_world}
 
 @group(0) @binding(0) var<uniform> camera_params:         CameraParams;
 @group(0) @binding(1) var<storage> light_occluder_buffer: LightOccluderBuffer;
+#ifdef GI_SDF_R32F
+@group(0) @binding(2) var          sdf_out:               texture_storage_2d<r32float, read_write>;
+#else
 @group(0) @binding(2) var          sdf_out:               texture_storage_2d<r16float, read_write>;
+#endif
+@group(0) @binding(3) var<storage> tile_grid:             TileOccluderGrid;
+
+// Rings of cells searched around a point, beyond them the distance is only
+// a conservative bound.
+const TILE_GRID_MAX_RING: i32 = 8;
 
 fn sdf_aabb_occluder(p: vec2<f32>, occluder_i: i32) -> f32 {
-    let occluder = light_occluder_buffer.data[occluder_i];
-    let local_p = quat_mul(occluder.rotation, vec3<f32>(occluder.center - p, 0.0)).xy;
-    let d        = abs(local_p) - occluder.h_extent;
-    let d_max    = max(d, vec2<f32>(0.0));
-    let d_o      = length(d_max);
-    let d_i      = min(max(d.x, d.y), 0.0);
-    return d_o + d_i;
+    return sdf_occluder(p, light_occluder_buffer.data[occluder_i]);
+}
+
+fn sdf_box(p: vec2<f32>, center: vec2<f32>, half_size: vec2<f32>) -> f32 {
+    let d = abs(p - center) - half_size;
+    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0);
+}
+
+fn tile_grid_solid(cell: vec2<i32>) -> bool {
+    let size = vec2<i32>(tile_grid.size);
+    if any(cell < vec2<i32>(0)) || any(cell >= size) {
+        return false;
+    }
+    let i = u32(cell.y * size.x + cell.x);
+    return ((tile_grid.bits[i / 32u] >> (i % 32u)) & 1u) != 0u;
+}
+
+// Distance to the nearest solid cell, searched ring by ring outwards from
+// the cell containing `p`.
+fn sdf_tile_grid(p: vec2<f32>) -> f32 {
+    if tile_grid.size.x == 0u || tile_grid.size.y == 0u {
+        return 1e+10;
+    }
+
+    let cell_size = tile_grid.cell_size;
+    let half_cell = cell_size * 0.5;
+    let min_cell  = min(cell_size.x, cell_size.y);
+    let center    = vec2<i32>(floor((p - tile_grid.origin) / cell_size));
+
+    var best = 1e+10;
+    for (var k: i32 = 0; k <= TILE_GRID_MAX_RING; k++) {
+        // Cells of ring k are at least k - 1 cells away.
+        if best <= f32(k - 1) * min_cell {
+            break;
+        }
+        for (var y: i32 = -k; y <= k; y++) {
+            // Only the outline of the ring, inner cells were searched before.
+            let step = select(2 * k, 1, abs(y) == k || k == 0);
+            for (var x: i32 = -k; x <= k; x += step) {
+                let cell = center + vec2<i32>(x, y);
+                if !tile_grid_solid(cell) {
+                    continue;
+                }
+                let cell_center = tile_grid.origin + (vec2<f32>(cell) + 0.5) * cell_size;
+                best = min(best, sdf_box(p, cell_center, half_cell));
+            }
+        }
+    }
+
+    // Nothing within the searched rings: anything solid is further than the
+    // rings reach, and outside the grid at least as far as its bounds.
+    let searched = f32(TILE_GRID_MAX_RING) * min_cell;
+    if best > searched {
+        let grid_half_size = vec2<f32>(tile_grid.size) * half_cell;
+        let grid_dist = sdf_box(p, tile_grid.origin + grid_half_size, grid_half_size);
+        best = min(best, max(searched, grid_dist));
+    }
+    return best;
 }
 
 fn round_merge(s1: f32, s2: f32, r: f32) -> f32 {
@@ -36,14 +96,15 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         camera_params.sdf_scale);
     let r = 1.2;
 
-     var sdf_merged   = round_merge(
-        1e+10,
-        sdf_aabb_occluder(world_pose.xy, 0),
-        r,
-     );
-     for (var i: i32 = 1; i < i32(light_occluder_buffer.count); i++) {
+     // Occluders without any group never block light.
+     var sdf_merged = 1e+10;
+     for (var i: i32 = 0; i < i32(light_occluder_buffer.count); i++) {
+        if light_occluder_buffer.data[i].group == 0u {
+            continue;
+        }
         sdf_merged = round_merge(sdf_merged, sdf_aabb_occluder(world_pose.xy, i), r);
      }
+     sdf_merged = min(sdf_merged, sdf_tile_grid(world_pose.xy));
 
     textureStore(sdf_out, texel_pos, vec4<f32>(sdf_merged, 0.0, 0.0, 0.0));
 }
\ No newline at end of file
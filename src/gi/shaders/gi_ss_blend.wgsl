@@ -1,6 +1,6 @@
-#import bevy_magic_light_2d::gi_types::{LightOccluderBuffer, LightPassParams, ProbeDataBuffer}
+#import bevy_magic_light_2d::gi_types::{LightOccluderBuffer, LightPassParams, ProbeData, ProbeDataBuffer}
 #import bevy_magic_light_2d::gi_math
-#import bevy_magic_light_2d::gi_camera::{CameraParams, screen_to_world, world_to_ndc, ndc_to_screen}
+#import bevy_magic_light_2d::gi_camera::{CameraParams, screen_to_world, world_to_ndc, ndc_to_screen, screen_offset_to_world}
 #import bevy_magic_light_2d::gi_halton
 #import bevy_magic_light_2d::gi_attenuation
 
@@ -9,11 +9,17 @@
 @group(0) @binding(2) var<storage> probes:            ProbeDataBuffer;
 @group(0) @binding(3) var          sdf_in:            texture_2d<f32>;
 @group(0) @binding(4) var          sdf_in_sampler:    sampler;
+#ifdef GI_TARGETS_RGBA16F
+@group(0) @binding(5) var          ss_bounce_in:      texture_storage_2d<rgba16float, read>;
+@group(0) @binding(6) var          ss_blend_out:      texture_storage_2d<rgba16float, write>;
+#else
 @group(0) @binding(5) var          ss_bounce_in:      texture_storage_2d<rgba32float, read>;
 @group(0) @binding(6) var          ss_blend_out:      texture_storage_2d<rgba32float, write>;
+#endif
 
+// Irradiance in `xyz`, indirect luminance in `w`.
 struct ProbeVal {
-    val:       vec3<f32>,
+    val:       vec4<f32>,
     pose:      vec2<f32>,
 }
 
@@ -21,9 +27,9 @@ fn read_probe(
     probe_tile_origin: vec2<i32>,
     probe_tile_pose:   vec2<i32>,
     probe_offset:      vec2<i32>,
-    motion_offset:     vec2<f32>,
+    probe_camera:      ProbeData,
     tile_size:         vec2<i32>,
-    probe_size_f32:    f32) -> ProbeVal {
+    probe_size_f32:    vec2<f32>) -> ProbeVal {
 
     let clamped_offset = clamp(probe_tile_pose + probe_offset, vec2<i32>(0), tile_size - vec2<i32>(1));
 
@@ -33,15 +39,23 @@ fn read_probe(
 
     //
     let data        = textureLoad(ss_bounce_in, probe_atlas_pose);
-    var val         = data.xyz;
-
-    let halton_offset  = unpack2x16float(bitcast<u32>(data.w)) * probe_size_f32 * 1.0;
+    var val         = data;
+
+    // The jitter isn't stored, recompute it from the tile.
+    let probe_id       = (probe_tile_origin.y / cfg.probe_atlas_rows) * cfg.probe_size.x
+                       + probe_tile_origin.x / cfg.probe_atlas_cols;
+    let halton         = gi_halton::hammersley2d(probe_id, i32(cfg.reservoir_size));
+    let halton_offset  = screen_offset_to_world(
+        halton * probe_size_f32,
+        probe_camera.inverse_view_proj,
+        camera_params.screen_size_inv,
+    );
     let probe_pose     = screen_to_world(
         probe_screen_pose,
         camera_params.screen_size,
-        camera_params.inverse_view_proj,
+        probe_camera.inverse_view_proj,
         camera_params.screen_size_inv,
-    ) + halton_offset - motion_offset;
+    ) + halton_offset;
 
     return ProbeVal(
         val,
@@ -50,7 +64,7 @@ fn read_probe(
 }
 
 struct SampleResult {
-    val:    vec3<f32>,
+    val:    vec4<f32>,
     weight: f32,
 }
 
@@ -58,12 +72,12 @@ fn get_probe_tile_origin(
     probe_id:       i32,
     rows:           i32,
     cols:           i32,
-    probe_size:     i32) -> vec2<i32> {
+    probe_size:     vec2<i32>) -> vec2<i32> {
 
     return vec2<i32>(
         cols,
         rows,
-    ) * vec2<i32>(probe_id % probe_size, probe_id / probe_size);
+    ) * vec2<i32>(probe_id % probe_size.x, probe_id / probe_size.x);
 }
 
 fn gauss(x: f32) -> f32 {
@@ -80,13 +94,20 @@ fn estimate_probes_at(
     sample_pose:         vec2<f32>,
     screen_pose:         vec2<i32>,
     probe_id:            i32,
-    probe_camera_motion: vec2<f32>,
+    probe_camera:        ProbeData,
     tile_size:           vec2<i32>,
-    probe_size_f32:      f32) -> SampleResult {
+    probe_size_f32:      vec2<f32>) -> SampleResult {
+
+    // Reproject sample world pose with the camera of the probe's frame, so
+    // that panning, zooming and rotating all keep history registered.
+    let reproj_ndc             = world_to_ndc(sample_pose, probe_camera.view_proj);
 
-    // Reproject sample world pose to previous frame world pose.
-    let reproj_sample_pose     = sample_pose + probe_camera_motion;
-    let reproj_ndc             = world_to_ndc(reproj_sample_pose, camera_params.view_proj);
+    // History is only valid where the sample was on screen in the probe's
+    // frame. Otherwise the lookup would be clamped to an edge probe that
+    // lit a different part of the scene, smearing it in while panning.
+    if any(reproj_ndc <= vec2<f32>(-1.0)) || any(reproj_ndc >= vec2<f32>(1.0)) {
+        return SampleResult(vec4<f32>(0.0), 0.0);
+    }
 
     // Probe pose in the screen.
     let reproj_screen_pose     = ndc_to_screen(reproj_ndc.xy, camera_params.screen_size);
@@ -107,14 +128,14 @@ fn estimate_probes_at(
         curr_probe_origin,
         reproj_tile_probe_pose,
         base_offset,
-        probe_camera_motion,
+        probe_camera,
         tile_size,
         probe_size_f32);
 
     // Discard if offscreen.
     let base_ndc = world_to_ndc(base_probe.pose, camera_params.view_proj);
     if any(base_ndc <= vec2<f32>(-1.0)) || any(base_ndc >= vec2<f32>(1.0)) {
-        return SampleResult(vec3<f32>(0.0), 0.0);
+        return SampleResult(vec4<f32>(0.0), 0.0);
     }
 
     // Compute bilateral filter with gauss function
@@ -125,12 +146,54 @@ fn estimate_probes_at(
     var total_w = g;
 
     return SampleResult(
-        clamp(total_q, vec3<f32>(0.0), vec3<f32>(1e+4)),
+        clamp(total_q, vec4<f32>(0.0), vec4<f32>(1e+4)),
         clamp(total_w, 0.0, 1e+4),
     );
 }
 
 
+fn luminance(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Blinn-Phong highlight for a surface facing the viewer. The dominant light
+// direction is estimated from the irradiance gradient of neighbouring probes
+// in the current frame. Assuming an inverse-square falloff, the distance to
+// the light is `2 * I / |grad I|`, lights are assumed to sit slightly above
+// the plane.
+fn specular_highlight(tile_xy: vec2<i32>, tile_size: vec2<i32>, irradiance: vec3<f32>) -> vec3<f32> {
+    let tile_origin = get_probe_tile_origin(
+        cfg.frame_counter % i32(cfg.reservoir_size),
+        cfg.probe_atlas_rows,
+        cfg.probe_atlas_cols,
+        cfg.probe_size,
+    );
+    let max_tile = tile_size - vec2<i32>(1);
+
+    let l = luminance(textureLoad(ss_bounce_in, tile_origin + clamp(tile_xy - vec2<i32>(1, 0), vec2<i32>(0), max_tile)).xyz);
+    let r = luminance(textureLoad(ss_bounce_in, tile_origin + clamp(tile_xy + vec2<i32>(1, 0), vec2<i32>(0), max_tile)).xyz);
+    let t = luminance(textureLoad(ss_bounce_in, tile_origin + clamp(tile_xy - vec2<i32>(0, 1), vec2<i32>(0), max_tile)).xyz);
+    let b = luminance(textureLoad(ss_bounce_in, tile_origin + clamp(tile_xy + vec2<i32>(0, 1), vec2<i32>(0), max_tile)).xyz);
+
+    // Screen y points down, world y points up. Central differences span two
+    // probes.
+    let gradient = vec2<f32>(r - l, t - b) / (2.0 * vec2<f32>(cfg.probe_size));
+    let grad_len = length(gradient);
+    if grad_len <= 1e-5 {
+        return vec3<f32>(0.0);
+    }
+
+    let light_height   = 32.0;
+    let light_distance = 2.0 * luminance(irradiance) / grad_len;
+    let n = vec3<f32>(0.0, 0.0, 1.0);
+    let v = vec3<f32>(0.0, 0.0, 1.0);
+    let l_dir = normalize(vec3<f32>(gradient / grad_len * light_distance, light_height));
+    let h = normalize(l_dir + v);
+
+    let spec = pow(max(dot(n, h), 0.0), cfg.shininess);
+    return irradiance * spec * cfg.specular_strength;
+}
+
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let screen_pose  = vec2<i32>(invocation_id.xy) * cfg.probe_size + cfg.probe_size / 2;
@@ -144,19 +207,21 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let reservoir_size     = i32(cfg.reservoir_size);
     let curr_probe_id      = cfg.frame_counter % reservoir_size;
 
-    let camera_buffer_size = cfg.probe_size * cfg.probe_size;
+    let camera_buffer_size = cfg.frame_cycle;
     let camera_buffer_id   = cfg.frame_counter;
-    let curr_camera_pose   = probes.data[camera_buffer_id].pose;
-    let probe_size_f32     = f32(cfg.probe_size);
+    let probe_size_f32     = vec2<f32>(cfg.probe_size);
 
-    let tile_size          = vec2<i32>(camera_params.screen_size / (f32(cfg.probe_size) - 0.001));
+    let tile_size          = vec2<i32>(camera_params.screen_size / (probe_size_f32 - 0.001));
     let min_irradiance     = vec3<f32>(0.0);
     let max_irradiance     = vec3<f32>(1e+4);
     var total_irradiance   = min_irradiance;
+    var total_indirect     = 0.0;
     var total_weight       = 0.0;
 
-    // Sample radiance from previous frames.
-    for (var i = 0; i < reservoir_size; i++) {
+    // Sample radiance from previous frames, frames before a temporal reset
+    // are skipped.
+    let history_length     = clamp(cfg.history_length, 1, reservoir_size);
+    for (var i = 0; i < history_length; i++) {
 
         // Get index of probe tile of previous frame.
         var probe_id = curr_probe_id - i;
@@ -170,16 +235,12 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
             probe_camera_buffer_id = camera_buffer_size + probe_camera_buffer_id;
         }
 
-        // Compute position change.
-        let probe_camera_pose   = probes.data[probe_camera_buffer_id].pose;
-        let probe_camera_motion = curr_camera_pose - probe_camera_pose;
-
         // Get sample probe value.
         let r = estimate_probes_at(
             sample_pose,
             screen_pose,
             probe_id,
-            probe_camera_motion,
+            probes.data[probe_camera_buffer_id],
             tile_size,
             probe_size_f32,
         );
@@ -187,21 +248,30 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
         // If probe is active, accumulate irradiance and weight.
         if r.weight > 0.0 {
-            total_irradiance += clamp(r.val, min_irradiance, max_irradiance);
+            total_irradiance += clamp(r.val.xyz, min_irradiance, max_irradiance);
+            total_indirect   += r.val.w;
             total_weight     += r.weight;
         }
     }
 
 
-    // Normalize and clamp.
-    total_irradiance = total_irradiance / total_weight;
+    // Normalize and clamp. Without valid history the probe stays dark for
+    // a frame rather than turning NaN.
+    total_irradiance = total_irradiance / max(total_weight, 1e-6);
     total_irradiance = clamp(total_irradiance, min_irradiance, max_irradiance);
+    total_indirect   = total_indirect / max(total_weight, 1e-6);
+
+    if cfg.specular_strength > 0.0 {
+        total_irradiance += specular_highlight(vec2<i32>(invocation_id.xy), tile_size, total_irradiance);
+        total_irradiance  = clamp(total_irradiance, min_irradiance, max_irradiance);
+    }
 
     var l = vec3<f32>(0.001 + dot(total_irradiance, vec3<f32>(1.0/3.0)));
         l = clamp(vec3<f32>(1.0) - l, vec3<f32>(0.0), vec3<f32>(1.0)) * .15;
 
     // total_irradiance = log(vec3<f32>(1.0) + total_irradiance + total_irradiance * l);
     total_irradiance = total_irradiance + total_irradiance * l;
+    total_indirect   = total_indirect + total_indirect * l.x;
 
-    textureStore(ss_blend_out, vec2<i32>(invocation_id.xy), vec4<f32>(total_irradiance, total_weight));
+    textureStore(ss_blend_out, vec2<i32>(invocation_id.xy), vec4<f32>(total_irradiance, total_indirect));
 }
\ No newline at end of file
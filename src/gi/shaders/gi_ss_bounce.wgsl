@@ -1,16 +1,113 @@
-#import bevy_magic_light_2d::gi_types::LightPassParams
-#import bevy_magic_light_2d::gi_math::fast_normalize_2d
-#import bevy_magic_light_2d::gi_camera::{CameraParams, world_to_sdf_uv, bilinear_sample_rgba, screen_to_world, world_to_screen, world_to_ndc}
-#import bevy_magic_light_2d::gi_halton::radical_inverse_vdc
+#import bevy_magic_light_2d::gi_types::{LightPassParams, LightSourceBuffer, RectLightSourceBuffer}
+#import bevy_magic_light_2d::gi_math::{clamp_luminance, fast_normalize_2d}
+#import bevy_magic_light_2d::gi_camera::{CameraParams, world_to_sdf_uv, bilinear_sample_rgba, screen_to_world, world_to_screen, world_to_ndc, ndc_to_screen_uv, screen_offset_to_world}
+#import bevy_magic_light_2d::gi_halton::{hammersley2d, radical_inverse_vdc}
 #import bevy_magic_light_2d::gi_attenuation
-#import bevy_magic_light_2d::gi_raymarch::raymarch_bounce
+#import bevy_magic_light_2d::gi_raymarch::{raymarch_bounce, raymarch_primary}
 
 @group(0) @binding(0) var<uniform> camera_params:     CameraParams;
 @group(0) @binding(1) var<uniform> cfg:               LightPassParams;
 @group(0) @binding(2) var          sdf_in:            texture_2d<f32>;
 @group(0) @binding(3) var          sdf_in_sampler:    sampler;
+#ifdef GI_TARGETS_RGBA16F
 @group(0) @binding(4) var          ss_probe_in:       texture_storage_2d<rgba16float, read>;
+@group(0) @binding(5) var          ss_bounce_out:     texture_storage_2d<rgba16float, write>;
+#else
+@group(0) @binding(4) var          ss_probe_in:       texture_storage_2d<rgba32float, read>;
 @group(0) @binding(5) var          ss_bounce_out:     texture_storage_2d<rgba32float, write>;
+#endif
+@group(0) @binding(6) var          floor_in:          texture_2d<f32>;
+@group(0) @binding(7) var          floor_in_sampler:  sampler;
+@group(0) @binding(8) var          objects_in:        texture_2d<f32>;
+@group(0) @binding(9) var          objects_in_sampler: sampler;
+@group(0) @binding(10) var         blue_noise_in:     texture_2d<f32>;
+@group(0) @binding(11) var<storage> lights_source_buffer: LightSourceBuffer;
+@group(0) @binding(12) var<storage> rect_lights_buffer:   RectLightSourceBuffer;
+
+// Share of the indirect rays a probe traces: the highest indirect quality
+// of the lights reaching it. Rect lights, skylight and sun always get full
+// quality, as do probes out of reach of every light, which are lit only by
+// the bounce.
+fn indirect_quality(probe_center_world: vec2<f32>) -> f32 {
+    if any(cfg.skylight_color > vec3<f32>(0.0)) || any(cfg.sun_color > vec3<f32>(0.0)) {
+        return 1.0;
+    }
+    for (var i: i32 = 0; i < i32(rect_lights_buffer.count); i++) {
+        let light = rect_lights_buffer.data[i];
+        let delta = probe_center_world - light.center;
+        if dot(delta, delta) <= light.radius * light.radius {
+            return 1.0;
+        }
+    }
+
+    var quality = 0.0;
+    var in_reach = false;
+    for (var i: i32 = 0; i < i32(lights_source_buffer.count); i++) {
+        let light = lights_source_buffer.data[i];
+        let delta = probe_center_world - light.center;
+        if light.direct_only == 0u && light.subtractive == 0u && dot(delta, delta) <= light.radius * light.radius {
+            quality  = max(quality, light.indirect_quality);
+            in_reach = true;
+            if quality >= 1.0 {
+                break;
+            }
+        }
+    }
+    return select(1.0, quality, in_reach);
+}
+
+// Direct light of `direct_only` lights at a bounce sample, removed from the
+// sampled irradiance so they don't bounce. Occluder masks, partial
+// occluders and fog are ignored, so the estimate can differ slightly from
+// what the probe pass added.
+fn direct_only_irradiance(sample_world: vec2<f32>) -> vec3<f32> {
+    var irradiance = vec3<f32>(0.0);
+    for (var i: i32 = 0; i < i32(lights_source_buffer.count); i++) {
+        let light = lights_source_buffer.data[i];
+        if light.direct_only == 0u || light.subtractive != 0u {
+            continue;
+        }
+        let delta = sample_world - light.center;
+        if dot(delta, delta) > light.radius * light.radius {
+            continue;
+        }
+
+        let light_distance = length(delta);
+        let ray_end        = light.center + fast_normalize_2d(delta) * min(light.shadow_bias, light_distance);
+        let ray_result     = raymarch_primary(
+            sample_world,
+            ray_end,
+            cfg.raymarch_max_steps,
+            sdf_in,
+            sdf_in_sampler,
+            camera_params,
+            0.0
+        );
+        if ray_result.success <= 0 {
+            continue;
+        }
+
+        let att = gi_attenuation::light_attenuation_at_dist_r_two(
+            max(light_distance, light.min_distance),
+            light.falloff.x,
+            light.falloff.y,
+            light.falloff.z,
+        );
+        irradiance += light.color * att * light.intensity;
+    }
+    return irradiance;
+}
+
+// Surface color at the bounce origin. Objects are drawn on top of the floor,
+// empty (transparent) areas reflect light without tinting it.
+fn surface_albedo(ndc: vec2<f32>) -> vec3<f32> {
+    let uv      = ndc_to_screen_uv(ndc * camera_params.display_scale);
+    let floor   = textureSampleLevel(floor_in,   floor_in_sampler,   uv, 0.0);
+    let objects = textureSampleLevel(objects_in, objects_in_sampler, uv, 0.0);
+
+    let floor_albedo = mix(vec3<f32>(1.0), floor.rgb, floor.a);
+    return mix(floor_albedo, objects.rgb, objects.a);
+}
 
 
 @compute @workgroup_size(8, 8, 1)
@@ -21,8 +118,8 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let reservoir_size           = i32(cfg.reservoir_size);
     let frame_index              = cfg.frame_counter % reservoir_size;
 
-    let atlas_row = frame_index / cfg.probe_size;
-    let atlas_col = frame_index % cfg.probe_size;
+    let atlas_row = frame_index / cfg.probe_size.x;
+    let atlas_col = frame_index % cfg.probe_size.x;
 
     let out_atlas_tile_offset = vec2<i32>(
         cfg.probe_atlas_cols * atlas_col,
@@ -34,11 +131,15 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let probe             = textureLoad(ss_probe_in, out_atlas_tile_pose);
     let direct_irradiance = probe.xyz;
     var total_irradiance  = direct_irradiance;
-    let probe_size_f32    = f32(cfg.probe_size);
-    let halton            = unpack2x16float(bitcast<u32>(probe.w));
+    let probe_size_f32    = vec2<f32>(cfg.probe_size);
+    let halton            = hammersley2d(frame_index, reservoir_size);
     let probe_tile_origin_screen = tile_xy * cfg.probe_size;
 
-    let probe_offset_world  = halton * probe_size_f32;
+    let probe_offset_world  = screen_offset_to_world(
+        halton * probe_size_f32,
+        camera_params.inverse_view_proj,
+        camera_params.screen_size_inv,
+    );
     let probe_center_world  = screen_to_world(
         probe_tile_origin_screen,
         camera_params.screen_size,
@@ -52,12 +153,18 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let pi2                  = pi * 2.0;
     var indirect_irradiance  = vec3<f32>(0.0);
     var total_rays           = 0;
-    var rays_per_sample      = cfg.indirect_rays_per_sample;
+    var rays_per_sample      = i32(ceil(f32(cfg.indirect_rays_per_sample) * indirect_quality(probe_center_world)));
     let golden_angle         = pi * mm;
 
     var r_bias = 4.0;
     var r_step = 16.0;
     var hh = radical_inverse_vdc(frame_index) / f32(reservoir_size);
+    if cfg.indirect_blue_noise != 0u {
+        // Per-probe blue noise, animated over frames with the golden ratio.
+        let noise_size = vec2<i32>(textureDimensions(blue_noise_in));
+        let noise      = textureLoad(blue_noise_in, tile_xy % noise_size, 0).r;
+        hh = fract(noise + 0.618034 * f32(frame_index)) / f32(reservoir_size);
+    }
 
     {
         r_step *= mm;
@@ -89,7 +196,7 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
             var raymarch_sample_to_probe = raymarch_bounce(
                 probe_center_world,
                 sample_world,
-                32,
+                cfg.raymarch_max_steps,
                 sdf_in,
                 sdf_in_sampler,
                 camera_params,
@@ -112,8 +219,13 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
             let sample_probe   = textureLoad(ss_probe_in, sample_atlas_pose);
             let sample_xyz     = sample_probe.xyz;
 
-            let sample_halton       = unpack2x16float(bitcast<u32>(sample_probe.w));
-            let sample_offset_world = sample_halton * probe_size_f32;
+            // Samples come from the same atlas tile, so they share the jitter.
+            let sample_halton       = halton;
+            let sample_offset_world = screen_offset_to_world(
+                sample_halton * probe_size_f32,
+                camera_params.inverse_view_proj,
+                camera_params.screen_size_inv,
+            );
 
             sample_world           += sample_offset_world;
 
@@ -123,14 +235,21 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
                 continue;
             }
 
-            let sample_irradiance = sample_xyz;
+            let bounced_xyz       = max(sample_xyz - direct_only_irradiance(sample_world), vec3<f32>(0.0));
+            let sample_irradiance = clamp_luminance(
+                bounced_xyz * surface_albedo(sample_ndc),
+                cfg.max_luminance_clamp,
+            );
             indirect_irradiance  += sample_irradiance * 0.6; // 0.4 is absorbed by surface.
         }
     }
 
-    indirect_irradiance = indirect_irradiance / f32(total_rays / k_max);
+    indirect_irradiance = indirect_irradiance / f32(max(total_rays / k_max, 1));
     total_irradiance  = cfg.indirect_light_contrib * indirect_irradiance
                       + cfg.direct_light_contrib   * direct_irradiance;
 
-    textureStore(ss_bounce_out, out_atlas_tile_pose, vec4(total_irradiance, probe.w));
+    // Alpha carries the indirect luminance through the blend and filter
+    // passes, so the composite can scale indirect light per layer.
+    let indirect_luminance = dot(cfg.indirect_light_contrib * indirect_irradiance, vec3<f32>(0.2126, 0.7152, 0.0722));
+    textureStore(ss_bounce_out, out_atlas_tile_pose, vec4(total_irradiance, indirect_luminance));
 }
@@ -10,9 +10,27 @@
 @group(0) @binding(2) var<storage> probes:            ProbeDataBuffer;
 @group(0) @binding(3) var          sdf_in:            texture_2d<f32>;
 @group(0) @binding(4) var          sdf_in_sampler:    sampler;
+#ifdef GI_TARGETS_RGBA16F
+@group(0) @binding(5) var          ss_blend_in:       texture_storage_2d<rgba16float, read>;
+@group(0) @binding(6) var          ss_filter_out:     texture_storage_2d<rgba16float, write>;
+#else
 @group(0) @binding(5) var          ss_blend_in:       texture_storage_2d<rgba32float, read>;
 @group(0) @binding(6) var          ss_filter_out:     texture_storage_2d<rgba32float, write>;
+#endif
+#ifdef GI_POSE_RGBA16F
+@group(0) @binding(7) var          ss_pose_out:      texture_storage_2d<rgba16float, write>;
+#else
 @group(0) @binding(7) var          ss_pose_out:      texture_storage_2d<rg32float, write>;
+#endif
+
+// Distance field difference, in world units, at which an edge-aware sample's
+// weight falls to 1/e.
+const FILTER_SDF_SIGMA: f32 = 16.0;
+
+fn sdf_at(world_pose: vec2<f32>) -> f32 {
+    let uv = world_to_sdf_uv(world_pose, camera_params.view_proj, camera_params.inv_sdf_scale);
+    return bilinear_sample_r(sdf_in, sdf_in_sampler, uv);
+}
 
 fn gauss(x: f32) -> f32 {
     let a = 4.0;
@@ -37,7 +55,10 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     let base_probe_screen_pose = screen_pose;
     let base_probe_grid_pose   = screen_pose / cfg.probe_size;
-    let base_probe_sample      = textureLoad(ss_blend_in, base_probe_screen_pose).xyz;
+    let base_probe_sample      = gi_math::clamp_luminance(
+        textureLoad(ss_blend_in, base_probe_screen_pose).xyz,
+        cfg.max_luminance_clamp,
+    );
     let base_probe_world_pose  = screen_to_world(
         base_probe_screen_pose,
         camera_params.screen_size,
@@ -45,11 +66,18 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         camera_params.screen_size_inv,
     );
 
+    let edge_aware = cfg.filter_edge_aware != 0u;
+    var base_sdf   = 0.0;
+    if edge_aware {
+        base_sdf = sdf_at(sample_world_pose);
+    }
+
     let kernel_hl = i32(cfg.smooth_kernel_size_w);
     let kernel_hr = i32(cfg.smooth_kernel_size_h);
 
     var total_w = 0.0;
     var total_q = vec3<f32>(0.0);
+    var total_i = 0.0;
     var total_samples = 0;
 
     for (var i = -kernel_hl; i <= kernel_hr; i++) {
@@ -73,7 +101,11 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
                 camera_params.screen_size_inv,
             );
 
-            let p_sample = textureLoad(ss_blend_in, p_grid_pose).xyz;
+            let p_data   = textureLoad(ss_blend_in, p_grid_pose);
+            let p_sample = gi_math::clamp_luminance(
+                p_data.xyz,
+                cfg.max_luminance_clamp,
+            );
 
             // Discard occluded probes.
             if raymarch_primary(sample_world_pose, p_world_pose,
@@ -87,20 +119,29 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
             let d = distance(p_world_pose, sample_world_pose);
             let x = distance(p_sample, base_probe_sample);
-            let g = gauss(x) * gauss(d);
+            var g = gauss(x) * gauss(d);
+
+            // Probes across an occluder edge see a different distance field,
+            // down-weight them so light doesn't leak through thin walls.
+            if edge_aware {
+                g *= exp(-abs(sdf_at(p_world_pose) - base_sdf) / FILTER_SDF_SIGMA);
+            }
 
             total_q += p_sample * g;
+            total_i += min(p_data.w, cfg.max_luminance_clamp) * g;
             total_w += g;
         }
     }
 
     var irradiance = vec3<f32>(0.0);
+    var indirect   = 0.0;
     if (total_w > 0.0) {
         irradiance = total_q / total_w;
+        indirect   = total_i / total_w;
     }
 
     let sdf_uv = world_to_sdf_uv(sample_world_pose, camera_params.view_proj, camera_params.inv_sdf_scale);
 
-    textureStore(ss_filter_out, screen_pose, vec4<f32>(irradiance.xyz, 1.0));
+    textureStore(ss_filter_out, screen_pose, vec4<f32>(irradiance.xyz, indirect));
     textureStore(ss_pose_out, screen_pose, vec4<f32>(sdf_uv, 0.0,  0.0));
 }
@@ -1,9 +1,9 @@
-#import bevy_magic_light_2d::gi_types::{LightPassParams, ProbeDataBuffer, SkylightMaskBuffer, LightSourceBuffer}
-#import bevy_magic_light_2d::gi_math
-#import bevy_magic_light_2d::gi_camera::{CameraParams, world_to_sdf_uv, bilinear_sample_rgba, screen_to_world, world_to_ndc, ndc_to_screen, bilinear_sample_r}
-#import bevy_magic_light_2d::gi_attenuation::light_attenuation_r_two
+#import bevy_magic_light_2d::gi_types::{LightPassParams, ProbeDataBuffer, SkylightMaskBuffer, LightSource, LightSourceBuffer, LightOccluderBuffer, RectLightSource, RectLightSourceBuffer, TileOccluderGrid}
+#import bevy_magic_light_2d::gi_math::{sdf_occluder, occluder_ray_exit, distance_squared}
+#import bevy_magic_light_2d::gi_camera::{CameraParams, world_to_sdf_uv, bilinear_sample_rgba, screen_to_world, world_to_ndc, ndc_to_screen, ndc_to_screen_uv, bilinear_sample_r, screen_offset_to_world}
+#import bevy_magic_light_2d::gi_attenuation::{light_attenuation_at_dist_r_two, light_attenuation_at_dist_r}
 #import bevy_magic_light_2d::gi_halton::hammersley2d
-#import bevy_magic_light_2d::gi_raymarch::{raymarch_bounce, raymarch_primary}
+#import bevy_magic_light_2d::gi_raymarch::{raymarch_bounce, raymarch_primary, raymarch_directional, raymarch_penumbra, RayMarchResult}
 
 @group(0) @binding(0) var<uniform> camera_params:         CameraParams;
 @group(0) @binding(1) var<uniform> cfg:                   LightPassParams;
@@ -12,8 +12,468 @@
 @group(0) @binding(4) var<storage> lights_source_buffer:  LightSourceBuffer;
 @group(0) @binding(5) var          sdf_in:                texture_2d<f32>;
 @group(0) @binding(6) var          sdf_in_sampler:        sampler;
+#ifdef GI_TARGETS_RGBA16F
 @group(0) @binding(7) var          ss_probe_out:          texture_storage_2d<rgba16float, write>;
+#else
+@group(0) @binding(7) var          ss_probe_out:          texture_storage_2d<rgba32float, write>;
+#endif
+@group(0) @binding(8) var<storage> light_occluder_buffer: LightOccluderBuffer;
+@group(0) @binding(9) var<storage> rect_lights_buffer:    RectLightSourceBuffer;
+@group(0) @binding(10) var         height_map:            texture_2d<f32>;
+@group(0) @binding(11) var         height_map_sampler:    sampler;
+@group(0) @binding(12) var<storage> tile_grid:            TileOccluderGrid;
 
+const OCCLUDER_MASK_ALL: u32 = 0xFFFFFFFFu;
+const MAX_OCCLUDER_HOPS: i32 = 3;
+// `LightAccumulation::Max`.
+const LIGHT_ACCUMULATION_MAX: u32 = 1u;
+// Share of a light's radius after which its fog glow fades out.
+const FOG_FADE_START: f32 = 0.5;
+
+// Distance to the closest occluder whose group intersects the mask.
+fn sdf_masked(p: vec2<f32>, occluder_mask: u32) -> f32 {
+    var d = 1e+10;
+    for (var i: i32 = 0; i < i32(light_occluder_buffer.count); i++) {
+        let occluder = light_occluder_buffer.data[i];
+        if (occluder.group & occluder_mask) == 0u {
+            continue;
+        }
+        d = min(d, sdf_occluder(p, occluder));
+    }
+    return d;
+}
+
+// Same as raymarch_primary, but evaluates occluders analytically so that
+// only occluders matching the light's mask block the ray.
+fn raymarch_primary_masked(
+    ray_origin:    vec2<f32>,
+    ray_target:    vec2<f32>,
+    max_steps:     i32,
+    occluder_mask: u32,
+) -> RayMarchResult {
+    let ray_direction = normalize(ray_target - ray_origin);
+    let stop_at       = distance_squared(ray_origin, ray_target);
+    let min_sdf       = 1e-4;
+
+    var ray_progress  = 0.0;
+    var h             = ray_origin;
+    var h_prev        = h;
+
+    for (var i: i32 = 0; i < max_steps; i++) {
+        h_prev = h;
+        h = ray_origin + ray_progress * ray_direction;
+
+        if ray_progress * ray_progress >= stop_at {
+            return RayMarchResult(1, i, h_prev);
+        }
+
+        let scene_dist = sdf_masked(h, occluder_mask);
+        if scene_dist <= min_sdf {
+            return RayMarchResult(0, i, h);
+        }
+
+        ray_progress += scene_dist;
+    }
+
+    return RayMarchResult(0, max_steps, h);
+}
+
+// Index of the occluder closest to `p` among those matching the mask.
+fn occluder_at(p: vec2<f32>, occluder_mask: u32) -> i32 {
+    var closest      = -1;
+    var closest_dist = 1e+10;
+    for (var i: i32 = 0; i < i32(light_occluder_buffer.count); i++) {
+        let occluder = light_occluder_buffer.data[i];
+        if (occluder.group & occluder_mask) == 0u {
+            continue;
+        }
+        let d = sdf_occluder(p, occluder);
+        if d < closest_dist {
+            closest      = i;
+            closest_dist = d;
+        }
+    }
+    return closest;
+}
+
+fn tile_grid_solid(cell: vec2<i32>) -> bool {
+    let size = vec2<i32>(tile_grid.size);
+    if any(cell < vec2<i32>(0)) || any(cell >= size) {
+        return false;
+    }
+    let i = u32(cell.y * size.x + cell.x);
+    return ((tile_grid.bits[i / 32u] >> (i % 32u)) & 1u) != 0u;
+}
+
+// Whether a solid tile grid cell is on the segment from `from` to `to`.
+// Walks the cells the segment crosses (DDA), so cells block exactly like the
+// full height walls they stand for.
+fn tile_grid_blocks(from: vec2<f32>, to: vec2<f32>) -> bool {
+    let size = vec2<i32>(tile_grid.size);
+    if any(size <= vec2<i32>(0)) || any(tile_grid.cell_size <= vec2<f32>(0.0)) {
+        return false;
+    }
+
+    // Segment in cell units, clipped to the grid.
+    let start    = (from - tile_grid.origin) / tile_grid.cell_size;
+    let delta    = (to - tile_grid.origin) / tile_grid.cell_size - start;
+    let moving   = abs(delta) > vec2<f32>(0.0);
+    let safe_d   = select(vec2<f32>(1.0), delta, moving);
+    let t0       = -start / safe_d;
+    let t1       = (vec2<f32>(size) - start) / safe_d;
+    let inside   = (start >= vec2<f32>(0.0)) & (start < vec2<f32>(size));
+    if any(!moving & !inside) {
+        return false;
+    }
+    let t_near   = select(vec2<f32>(0.0), min(t0, t1), moving);
+    let t_far    = select(vec2<f32>(1.0), max(t0, t1), moving);
+    let t_enter  = max(max(t_near.x, t_near.y), 0.0);
+    let t_exit   = min(min(t_far.x, t_far.y), 1.0);
+    if t_enter > t_exit {
+        return false;
+    }
+
+    var cell     = clamp(vec2<i32>(floor(start + delta * t_enter)), vec2<i32>(0), size - 1);
+    let step     = vec2<i32>(sign(delta));
+    let t_step   = select(vec2<f32>(1e+10), 1.0 / abs(safe_d), moving);
+    let boundary = vec2<f32>(cell) + select(vec2<f32>(0.0), vec2<f32>(1.0), delta > vec2<f32>(0.0));
+    var t_next   = select(vec2<f32>(1e+10), (boundary - start) / safe_d, moving);
+
+    // A segment crosses at most one cell per step along either axis.
+    for (var i: i32 = 0; i <= size.x + size.y; i++) {
+        if tile_grid_solid(cell) {
+            return true;
+        }
+        if t_next.x < t_next.y {
+            if t_next.x > t_exit {
+                return false;
+            }
+            cell.x   += step.x;
+            t_next.x += t_step.x;
+        } else {
+            if t_next.y > t_exit {
+                return false;
+            }
+            cell.y   += step.y;
+            t_next.y += t_step.y;
+        }
+        if any(cell < vec2<i32>(0)) || any(cell >= size) {
+            return false;
+        }
+    }
+    return false;
+}
+
+// Distance to the solid cells around the one containing `p`, only valid
+// within a cell of `p`. Tells tile grid hits of the SDF march from boxes.
+fn tile_grid_distance_near(p: vec2<f32>) -> f32 {
+    if tile_grid.size.x == 0u || tile_grid.size.y == 0u {
+        return 1e+10;
+    }
+    let half_cell = tile_grid.cell_size * 0.5;
+    let center    = vec2<i32>(floor((p - tile_grid.origin) / tile_grid.cell_size));
+    var best      = 1e+10;
+    for (var y: i32 = -1; y <= 1; y++) {
+        for (var x: i32 = -1; x <= 1; x++) {
+            let cell = center + vec2<i32>(x, y);
+            if !tile_grid_solid(cell) {
+                continue;
+            }
+            let cell_center = tile_grid.origin + (vec2<f32>(cell) + 0.5) * tile_grid.cell_size;
+            let d = abs(p - cell_center) - half_cell;
+            best = min(best, length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0));
+        }
+    }
+    return best;
+}
+
+const HEIGHT_MAP_STEPS: i32 = 16;
+
+// Scene height at a world position. The height map is rendered by the scene
+// cameras, so it is addressed with the display view rather than the GI one.
+fn scene_height(world_pose: vec2<f32>) -> f32 {
+    let ndc = world_to_ndc(world_pose, camera_params.view_proj) * camera_params.display_scale;
+    if any(abs(ndc) > vec2<f32>(1.0)) {
+        return 0.0;
+    }
+    let uv = ndc_to_screen_uv(ndc);
+    return textureSampleLevel(height_map, height_map_sampler, uv, 0.0).r * cfg.height_scale;
+}
+
+// Whether the segment from a probe to a light clears the height map. The ray
+// starts on the scene surface at the probe and ends `light_height` above the
+// surface under the light.
+fn height_map_visibility(from: vec2<f32>, to: vec2<f32>) -> f32 {
+    if cfg.height_map_enabled == 0u {
+        return 1.0;
+    }
+
+    let from_height = scene_height(from);
+    let to_height   = scene_height(to) + cfg.light_height;
+    for (var i = 1; i < HEIGHT_MAP_STEPS; i++) {
+        let t = f32(i) / f32(HEIGHT_MAP_STEPS);
+        if scene_height(mix(from, to, t)) > mix(from_height, to_height, t) + 0.5 {
+            return 0.0;
+        }
+    }
+    return 1.0;
+}
+
+// Irradiance from a single light, attenuated by the occluders in between.
+fn direct_light(light: LightSource, probe_center_world: vec2<f32>) -> vec3<f32> {
+    // Skip lights whose contribution is negligible at this probe.
+    let light_delta = probe_center_world - light.center;
+    if dot(light_delta, light_delta) > light.radius * light.radius {
+        return vec3<f32>(0.0);
+    }
+
+    // Occluders lower than a full wall let part of the light through, the
+    // ray then continues behind them. Rays stop `shadow_bias` short of the
+    // light, so occluders the light sits in don't shadow it.
+    let light_distance = distance(light.center, probe_center_world);
+    let ray_direction  = normalize(light.center - probe_center_world);
+    let ray_end        = light.center - ray_direction * min(light.shadow_bias, light_distance);
+    // Occlusion is only tested up to `raymarch_max_distance` from the probe.
+    let shadow_end     = probe_center_world + ray_direction
+        * min(distance(probe_center_world, ray_end), cfg.raymarch_max_distance);
+    // Tile grid cells are opaque full height walls, tested exactly. Lights
+    // with a custom mask ignore them.
+    if light.occluder_mask == OCCLUDER_MASK_ALL && tile_grid_blocks(probe_center_world, shadow_end) {
+        return vec3<f32>(0.0);
+    }
+
+    var ray_origin     = probe_center_world;
+    var transmission   = 1.0;
+    var reached_light  = false;
+    var passed_through = false;
+
+    for (var hop = 0; hop < MAX_OCCLUDER_HOPS; hop++) {
+        var ray_result: RayMarchResult;
+        if light.occluder_mask == OCCLUDER_MASK_ALL {
+            ray_result = raymarch_primary(
+                ray_origin,
+                shadow_end,
+                cfg.raymarch_max_steps,
+                sdf_in,
+                sdf_in_sampler,
+                camera_params,
+                0.3
+            );
+        } else {
+            ray_result = raymarch_primary_masked(
+                ray_origin,
+                shadow_end,
+                cfg.raymarch_max_steps,
+                light.occluder_mask,
+            );
+        }
+
+        if ray_result.success > 0 {
+            reached_light = true;
+            break;
+        }
+
+        // The SDF also holds the tile grid. The segment clears it, so a stop
+        // closer to a cell than to any box grazes the cell and the march
+        // continues past it.
+        let occluder_index = occluder_at(ray_result.pose, light.occluder_mask);
+        let grid_distance  = select(1e+10, tile_grid_distance_near(ray_result.pose), light.occluder_mask == OCCLUDER_MASK_ALL);
+        let box_distance   = select(1e+10, sdf_occluder(ray_result.pose, light_occluder_buffer.data[max(occluder_index, 0)]), occluder_index >= 0);
+        if grid_distance < box_distance {
+            ray_origin = ray_result.pose + ray_direction * min(tile_grid.cell_size.x, tile_grid.cell_size.y);
+            if dot(shadow_end - ray_origin, ray_direction) <= 0.0 {
+                reached_light = true;
+                break;
+            }
+            continue;
+        }
+        if occluder_index < 0 {
+            break;
+        }
+        let occluder = light_occluder_buffer.data[occluder_index];
+        transmission *= 1.0 - clamp(occluder.height, 0.0, 1.0);
+        if transmission <= 0.0 {
+            break;
+        }
+
+        passed_through = true;
+        let exit    = occluder_ray_exit(ray_result.pose, ray_direction, occluder);
+        ray_origin  = ray_result.pose + ray_direction * (exit + 1.0);
+        if dot(shadow_end - ray_origin, ray_direction) <= 0.0 {
+            reached_light = true;
+            break;
+        }
+    }
+
+    transmission *= height_map_visibility(probe_center_world, ray_end);
+    if !reached_light || transmission <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+
+    // Lights with a body soften the shadow edges around the hard shadow.
+    // The march sees every occluder at full height, so it is skipped for
+    // masked lights and rays that passed through low occluders.
+    if light.source_radius > 0.0 && light.occluder_mask == OCCLUDER_MASK_ALL && !passed_through {
+        transmission *= raymarch_penumbra(
+            probe_center_world,
+            ray_direction,
+            distance(probe_center_world, shadow_end),
+            light_distance,
+            light.source_radius / max(light_distance, 1e-3),
+            cfg.raymarch_max_steps,
+            sdf_in,
+            sdf_in_sampler,
+            camera_params,
+        );
+    }
+
+    // Clamping the distance keeps lights touching a surface finite.
+    let falloff_distance = max(light_distance, light.min_distance);
+    let att = light_attenuation_at_dist_r_two(
+        falloff_distance,
+        light.falloff.x,
+        light.falloff.y,
+        light.falloff.z,
+    );
+
+    // Lights with an edge color shift towards it over their radius.
+    let color  = mix(light.color, light.edge_color, saturate(light_distance / max(light.radius, 1e-4)));
+    let direct = color * att * light.intensity * transmission;
+    if cfg.fog_density <= 0.0 {
+        return direct;
+    }
+
+    // Fog dims light over the path and scatters what it takes out into a
+    // glow. The glow falls off linearly rather than quadratically, so light
+    // shafts reach further than the pool of direct light. It is still
+    // faded out towards the light's radius, where the light is culled, so
+    // the glow doesn't end in a hard edge.
+    let fog_transmittance = exp(-cfg.fog_density * light_distance);
+    let scatter_att = light_attenuation_at_dist_r(
+        falloff_distance,
+        light.falloff.x,
+        light.falloff.y,
+        light.falloff.z,
+    );
+    let scatter_fade = 1.0 - smoothstep(FOG_FADE_START, 1.0, light_distance / max(light.radius, 1e-4));
+    let in_scatter = cfg.fog_color * color * light.intensity * transmission
+                   * scatter_att * scatter_fade * (1.0 - fog_transmittance);
+
+    return direct * fog_transmittance + in_scatter;
+}
+
+// Rect lights are approximated by point lights spread over their surface.
+// Sample positions are jittered per probe and frame, so the temporal
+// reservoir integrates the whole area.
+fn rect_light(light: RectLightSource, probe_center_world: vec2<f32>, tile_xy: vec2<i32>) -> vec3<f32> {
+    let light_delta = probe_center_world - light.center;
+    if dot(light_delta, light_delta) > light.radius * light.radius {
+        return vec3<f32>(0.0);
+    }
+
+    let sample_count = i32(cfg.rect_light_samples);
+    let jitter       = vec2<f32>(
+        hash_probe(tile_xy,    cfg.frame_counter),
+        hash_probe(tile_xy.yx, cfg.frame_counter),
+    );
+
+    var irradiance = vec3<f32>(0.0);
+    for (var i: i32 = 0; i < sample_count; i++) {
+        let uv = fract(hammersley2d(i, sample_count) + jitter) * 2.0 - 1.0;
+        let sample_light = LightSource(
+            light.center + light.axis_x * uv.x + light.axis_y * uv.y,
+            light.intensity / f32(sample_count),
+            light.color,
+            light.falloff,
+            light.occluder_mask,
+            light.radius,
+            1.0,
+            1.0,
+            1.0,
+            0u,
+            light.color,
+            0u,
+            0.0,
+        );
+        irradiance += direct_light(sample_light, probe_center_world);
+    }
+    return irradiance;
+}
+
+// Cheap estimate of a light's contribution used as its sampling weight.
+fn light_weight(light: LightSource, probe_center_world: vec2<f32>) -> f32 {
+    let d2 = distance_squared(probe_center_world, light.center);
+    if d2 > light.radius * light.radius {
+        return 0.0;
+    }
+    let peak = light.intensity * max(light.color.x, max(light.color.y, light.color.z));
+    return max(peak, 0.0) / max(d2, 1.0);
+}
+
+fn hash_probe(tile_xy: vec2<i32>, frame: i32) -> f32 {
+    var h = u32(tile_xy.x) * 1973u + u32(tile_xy.y) * 9277u + u32(frame) * 26699u;
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    h =  h ^ (h >> 16u);
+    return f32(h) * 2.3283064365386963e-10;
+}
+
+// Combines the direct light of two sources, see `LightAccumulation`.
+fn accumulate_light(total: vec3<f32>, light: vec3<f32>) -> vec3<f32> {
+    if cfg.light_accumulation == LIGHT_ACCUMULATION_MAX {
+        return max(total, light);
+    }
+    return total + light;
+}
+
+// Importance samples `light_sample_budget` lights proportionally to their
+// estimated contribution. Lights are picked by systematic sampling of the
+// weight CDF and each pick is divided by its probability, so the estimate
+// stays unbiased and converges through the temporal reservoir. Subtractive
+// lights are not sampled, they are always shaded by the caller.
+fn sample_direct_lights(probe_center_world: vec2<f32>, tile_xy: vec2<i32>) -> vec3<f32> {
+    let light_count = i32(lights_source_buffer.count);
+
+    var total_weight = 0.0;
+    for (var i: i32 = 0; i < light_count; i++) {
+        if lights_source_buffer.data[i].subtractive == 0u {
+            total_weight += light_weight(lights_source_buffer.data[i], probe_center_world);
+        }
+    }
+    if total_weight <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+
+    let sample_count = f32(cfg.light_sample_budget);
+    let step         = total_weight / sample_count;
+    var u            = hash_probe(tile_xy, cfg.frame_counter) * step;
+    var cumulative   = 0.0;
+    var irradiance   = vec3<f32>(0.0);
+
+    for (var i: i32 = 0; i < light_count; i++) {
+        let light  = lights_source_buffer.data[i];
+        if light.subtractive != 0u {
+            continue;
+        }
+        let weight = light_weight(light, probe_center_world);
+        cumulative += weight;
+
+        var picks = 0.0;
+        while u < cumulative {
+            picks += 1.0;
+            u     += step;
+        }
+
+        if picks > 0.0 {
+            irradiance = accumulate_light(
+                irradiance,
+                direct_light(light, probe_center_world) * picks * step / weight,
+            );
+        }
+    }
+
+    return irradiance;
+}
 
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
@@ -21,14 +481,19 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     // Screen-space position of the probe.
     let reservoir_size           = i32(cfg.reservoir_size);
-    let probe_size_f32           = f32(cfg.probe_size);
-    let frames_max               = cfg.probe_size * cfg.probe_size;
+    let probe_size_f32           = vec2<f32>(cfg.probe_size);
+    // frame_cycle is a multiple of reservoir_size, so the halton index
+    // wraps together with the frame counter.
     let frame_index              = cfg.frame_counter % reservoir_size;
     let halton_jitter            = hammersley2d(frame_index, reservoir_size);
     let probe_tile_origin_screen = tile_xy * cfg.probe_size;
 
     // Get current frame.
-    let probe_offset_world  = halton_jitter * probe_size_f32;
+    let probe_offset_world  = screen_offset_to_world(
+                                  halton_jitter * probe_size_f32,
+                                  camera_params.inverse_view_proj,
+                                  camera_params.screen_size_inv,
+                              );
     let probe_center_world_unbiased = screen_to_world(
                                               probe_tile_origin_screen,
                                               camera_params.screen_size,
@@ -39,17 +504,20 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     let probe_ndc    = world_to_ndc(probe_center_world, camera_params.view_proj);
     let probe_screen = ndc_to_screen(probe_ndc, camera_params.screen_size);
-    var is_masked    = 1.0;
+    var skylight_tint = vec3<f32>(1.0);
 
-    // Check if the probe is masked from skylight.
+    // Tint skylight by every mask covering the probe, opaque masks have a
+    // black tint.
     for (var i: i32 = 0; i < i32(skylight_masks_buffer.count); i++) {
         let mask = skylight_masks_buffer.data[i];
         if probe_center_world.x > mask.center.x - mask.h_extent.x &&
            probe_center_world.x < mask.center.x + mask.h_extent.x &&
            probe_center_world.y > mask.center.y - mask.h_extent.y &&
            probe_center_world.y < mask.center.y + mask.h_extent.y {
-            is_masked = 0.0;
-            break;
+            skylight_tint *= mask.tint;
+            if all(skylight_tint == vec3<f32>(0.0)) {
+                break;
+            }
         }
     }
 
@@ -59,42 +527,71 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let dist = bilinear_sample_r( sdf_in, sdf_in_sampler, uv);
     if dist > 0.0 {
 
-        let skylight = cfg.skylight_color * is_masked;;
+        let skylight = cfg.skylight_color * skylight_tint;
 
         // Compute direct irradiance from lights in the current frame.
-        probe_irradiance = vec3<f32>(skylight);
-        for (var i: i32 = 0; i < i32(lights_source_buffer.count); i++) {
-
+        var direct = vec3<f32>(0.0);
+        // Light removed by subtractive lights, always summed.
+        var darkness = vec3<f32>(0.0);
+        let light_count = i32(lights_source_buffer.count);
+        let sample_lights = cfg.light_sample_budget != 0u && light_count > i32(cfg.light_sample_budget);
+        for (var i: i32 = 0; i < light_count; i++) {
             let light = lights_source_buffer.data[i];
+            if light.subtractive != 0u {
+                darkness += direct_light(light, probe_center_world);
+            } else if !sample_lights {
+                direct = accumulate_light(direct, direct_light(light, probe_center_world));
+            }
+        }
+        if sample_lights {
+            direct = sample_direct_lights(probe_center_world, tile_xy);
+        }
+        for (var i: i32 = 0; i < i32(rect_lights_buffer.count); i++) {
+            direct = accumulate_light(direct, rect_light(rect_lights_buffer.data[i], probe_center_world, tile_xy));
+        }
 
-            let ray_result = raymarch_primary(
+        // Directional light, occlusion is tested towards the light.
+        if any(cfg.sun_color > vec3<f32>(0.0)) && cfg.sun_penumbra > 0.0 {
+            // The sun is infinitely far, its penumbra only widens with the
+            // distance to the occluder.
+            let sun_visibility = raymarch_penumbra(
                 probe_center_world,
-                light.center,
-                32,
+                -cfg.sun_direction,
+                cfg.raymarch_max_distance,
+                1e30,
+                cfg.sun_penumbra,
+                cfg.raymarch_max_steps,
                 sdf_in,
                 sdf_in_sampler,
                 camera_params,
-                0.3
             );
-
-            let att = light_attenuation_r_two(
+            direct = accumulate_light(direct, cfg.sun_color * sun_visibility);
+        } else if any(cfg.sun_color > vec3<f32>(0.0)) {
+            let sun_result = raymarch_directional(
                 probe_center_world,
-                light.center,
-                light.falloff.x,
-                light.falloff.y,
-                light.falloff.z,
+                -cfg.sun_direction,
+                cfg.raymarch_max_steps,
+                cfg.raymarch_max_distance,
+                sdf_in,
+                sdf_in_sampler,
+                camera_params,
             );
 
-            if (ray_result.success > 0) {
-                probe_irradiance += light.color * att * light.intensity;
+            if (sun_result.success > 0) {
+                direct = accumulate_light(direct, cfg.sun_color);
             }
         }
 
+        probe_irradiance = max(skylight + direct - darkness, vec3<f32>(0.0));
+
     }
 
+    // Ambient floor, applied regardless of occlusion.
+    probe_irradiance += cfg.minimum_ambient;
+
     // Coordinates of the screen-space cache output tile.
-    let atlas_row  = frame_index / cfg.probe_size;
-    let atlas_col  = frame_index % cfg.probe_size;
+    let atlas_row  = frame_index / cfg.probe_size.x;
+    let atlas_col  = frame_index % cfg.probe_size.x;
 
     let out_atlas_tile_offset = vec2<i32>(
         cfg.probe_atlas_cols * atlas_col,
@@ -102,8 +599,9 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     );
 
     let out_atlas_tile_pose = out_atlas_tile_offset + tile_xy;
-    let out_halton_jitter   = pack2x16float(halton_jitter);
-    let out_color           = vec4<f32>(probe_irradiance, bitcast<f32>(out_halton_jitter));
+    // Readers recompute the jitter from the frame index. Alpha holds the
+    // indirect luminance, which the bounce pass fills in.
+    let out_color           = vec4<f32>(probe_irradiance, 0.0);
 
     textureStore(ss_probe_out, out_atlas_tile_pose, out_color);
 }
\ No newline at end of file
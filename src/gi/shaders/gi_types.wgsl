@@ -1,10 +1,19 @@
 #define_import_path bevy_magic_light_2d::gi_types
 
 struct LightSource {
-    center:    vec2<f32>,
-    intensity: f32,
-    color:     vec3<f32>,
-    falloff:   vec3<f32>,
+    center:           vec2<f32>,
+    intensity:        f32,
+    color:            vec3<f32>,
+    falloff:          vec3<f32>,
+    occluder_mask:    u32,
+    radius:           f32,
+    shadow_bias:      f32,
+    min_distance:     f32,
+    indirect_quality: f32,
+    direct_only:      u32,
+    edge_color:       vec3<f32>,
+    subtractive:      u32,
+    source_radius:    f32,
 }
 
 struct LightSourceBuffer {
@@ -12,6 +21,22 @@ struct LightSourceBuffer {
     data:  array<LightSource>,
 }
 
+struct RectLightSource {
+    center:        vec2<f32>,
+    axis_x:        vec2<f32>,
+    axis_y:        vec2<f32>,
+    intensity:     f32,
+    color:         vec3<f32>,
+    falloff:       vec3<f32>,
+    occluder_mask: u32,
+    radius:        f32,
+}
+
+struct RectLightSourceBuffer {
+    count: u32,
+    data:  array<RectLightSource>,
+}
+
 struct Quaternion {
     data: vec4<f32>,
 }
@@ -20,6 +45,9 @@ struct LightOccluder {
     center: vec2<f32>,
     rotation: Quaternion,
     h_extent: vec2<f32>,
+    group: u32,
+    height: f32,
+    corner_radius: f32,
 }
 
 struct LightOccluderBuffer {
@@ -28,7 +56,9 @@ struct LightOccluderBuffer {
 }
 
 struct ProbeData {
-    pose: vec2<f32>,
+    pose:              vec2<f32>,
+    view_proj:         mat4x4<f32>,
+    inverse_view_proj: mat4x4<f32>,
 }
 
 struct ProbeDataBuffer {
@@ -38,9 +68,10 @@ struct ProbeDataBuffer {
 
 struct LightPassParams {
     frame_counter:          i32,
-    probe_size:             i32,
+    probe_size:             vec2<i32>,
     probe_atlas_cols:       i32,
     probe_atlas_rows:       i32,
+    frame_cycle:            i32,
     skylight_color:         vec3<f32>,
 
     reservoir_size:         u32,
@@ -51,14 +82,42 @@ struct LightPassParams {
 
     indirect_rays_per_sample:    i32,
     indirect_rays_radius_factor: f32,
+    minimum_ambient:             vec3<f32>,
+    sun_direction:               vec2<f32>,
+    sun_color:                   vec3<f32>,
+    specular_strength:           f32,
+    shininess:                   f32,
+    light_sample_budget:         u32,
+    indirect_blue_noise:         u32,
+    rect_light_samples:          u32,
+    max_luminance_clamp:         f32,
+    filter_edge_aware:           u32,
+    height_map_enabled:          u32,
+    height_scale:                f32,
+    light_height:                f32,
+    fog_density:                 f32,
+    fog_color:                   vec3<f32>,
+    raymarch_max_steps:          i32,
+    raymarch_max_distance:       f32,
+    history_length:              i32,
+    light_accumulation:          u32,
+    sun_penumbra:                f32,
 }
 
 struct SkylightMask {
     center:   vec2<f32>,
     h_extent: vec2<f32>,
+    tint:     vec3<f32>,
 }
 
 struct SkylightMaskBuffer {
     count: u32,
     data:  array<SkylightMask>,
 }
+
+struct TileOccluderGrid {
+    origin:    vec2<f32>,
+    cell_size: vec2<f32>,
+    size:      vec2<u32>,
+    bits:      array<u32>,
+}
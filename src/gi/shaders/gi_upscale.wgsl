@@ -0,0 +1,57 @@
+#import bevy_pbr::{
+    mesh_view_bindings::view,
+    forward_io::VertexOutput,
+    utils::coords_to_viewport_uv,
+}
+
+@group(2) @binding(0) var in_source_texture:       texture_2d<f32>;
+@group(2) @binding(1) var in_source_sampler:       sampler;
+@group(2) @binding(2) var<uniform> upscale_filter: u32;
+@group(2) @binding(3) var<uniform> transparent:    u32;
+
+// Mirrors `UpscaleFilter`.
+const UPSCALE_NEAREST:  u32 = 0u;
+const UPSCALE_BILINEAR: u32 = 1u;
+
+// Weight of the unsharp mask of `UpscaleFilter::Sharpen`.
+const SHARPEN_STRENGTH: f32 = 0.5;
+
+// A transparent composite was blended onto a cleared source, so the source
+// is premultiplied. It is filtered that way and output with straight alpha.
+fn present(color: vec4<f32>) -> vec4<f32> {
+    if transparent == 0u {
+        return color;
+    }
+    return vec4<f32>(color.xyz / max(color.w, 1e-4), color.w);
+}
+
+fn sample_source(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(in_source_texture, in_source_sampler, uv, 0.0);
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv          = coords_to_viewport_uv(in.position.xy, view.viewport);
+    let source_size = vec2<f32>(textureDimensions(in_source_texture));
+
+    if upscale_filter == UPSCALE_NEAREST {
+        let texel = clamp(vec2<i32>(uv * source_size), vec2<i32>(0), vec2<i32>(source_size) - 1);
+        return present(textureLoad(in_source_texture, texel, 0));
+    }
+
+    let color = sample_source(uv);
+    if upscale_filter == UPSCALE_BILINEAR {
+        return present(color);
+    }
+
+    // Unsharp mask against the average of the neighbouring source texels.
+    let texel_size = 1.0 / source_size;
+    let blurred = (
+        sample_source(uv + vec2<f32>(texel_size.x, 0.0)) +
+        sample_source(uv - vec2<f32>(texel_size.x, 0.0)) +
+        sample_source(uv + vec2<f32>(0.0, texel_size.y)) +
+        sample_source(uv - vec2<f32>(0.0, texel_size.y))
+    ) * 0.25;
+    let sharpened = color.xyz + (color.xyz - blurred.xyz) * SHARPEN_STRENGTH;
+    return present(vec4<f32>(max(sharpened, vec3<f32>(0.0)), color.w));
+}
@@ -1,8 +1,14 @@
 use bevy::prelude::*;
 use bevy::camera::visibility::VisibilityClass;
 
+/// Occluder group assigned to occluders by default.
+pub const OCCLUDER_GROUP_DEFAULT: u32 = 0b01;
+
+/// Light occluder mask that is blocked by every occluder group.
+pub const OCCLUDER_MASK_ALL: u32 = u32::MAX;
+
 #[rustfmt::skip]
-#[derive(Reflect, Component, Clone, Copy, Default)]
+#[derive(Reflect, Component, Clone, Copy)]
 #[require(VisibilityClass)]
 #[component(on_add = bevy::camera::visibility::add_visibility_class::<OmniLightSource2D>)]
 #[reflect(Component)]
@@ -12,23 +18,440 @@ pub struct OmniLightSource2D {
     pub falloff:            Vec3,
     pub jitter_intensity:   f32,
     pub jitter_translation: f32,
+    /// Bitmask of occluder groups that block this light. Occluders whose
+    /// `group` does not intersect the mask are ignored for direct light.
+    pub occluder_mask:      u32,
+    /// Distance from the light at which shadow rays stop, in world units.
+    /// Prevents self-shadowing of lights placed inside or against an
+    /// occluder, e.g. a lamp on a wall. The default of `1.0` is about a
+    /// pixel, raise it to the occluder's thickness for embedded lights.
+    pub shadow_bias:        f32,
+    /// Distances below this are clamped in the falloff, so a light right on
+    /// a surface gives a bright but finite hotspot. In world units, the
+    /// default of `1e-3` only guards against a zero distance.
+    pub min_distance:       f32,
+    /// Share of the indirect rays spent on areas this light reaches, in
+    /// `0..=1`. Lower it for minor fill lights: areas lit only by them trace
+    /// fewer bounce rays, none at `0.0`.
+    pub indirect_quality:   f32,
+    /// Light only shades surfaces directly and is left out of the bounce,
+    /// e.g. for crisp highlights and markers that shouldn't tint nearby
+    /// walls.
+    pub direct_only:        bool,
+    /// Color the light shifts to towards the edge of its effective radius,
+    /// e.g. a white-hot core fading to orange for a fire. `None` keeps
+    /// `color` everywhere.
+    pub edge_color:         Option<LinearRgba>,
+    /// Removes its light from the probes instead of adding it, e.g. for a
+    /// shadow orb carving a dark region out of a lit room. Subtracts from
+    /// lights and skylight, the result is clamped at zero so radiance never
+    /// goes negative. `LightPassParams::minimum_ambient` is still added on
+    /// top.
+    pub subtractive:        bool,
+    /// Radius of the emitting body, in world units. Shadows get a penumbra
+    /// that widens with the distance from the occluder to the lit surface
+    /// and narrows towards the light. `0.0` casts hard shadows. Only applies
+    /// to lights with `occluder_mask` set to `OCCLUDER_MASK_ALL` and to
+    /// shadows of full height occluders.
+    pub source_radius:      f32,
+    /// Disabled lights contribute no light, without touching the
+    /// visibility of the entity or its children.
+    pub enabled:            bool,
 }
 
+impl OmniLightSource2D
+{
+    /// Distance at which the light's contribution drops below `epsilon`.
+    ///
+    /// Lights with a zero quadratic falloff term never fade out and have an
+    /// infinite radius.
+    pub fn effective_radius(&self, epsilon: f32) -> f32
+    {
+        let peak = self.peak_intensity();
+        let (a, b, c) = (self.falloff.x, self.falloff.y, self.falloff.z);
+
+        if c <= 0.0 || epsilon <= 0.0 {
+            return f32::INFINITY;
+        }
+
+        // Solve `peak * a / (b + c * d^2) = epsilon` for `d`.
+        let d_squared = (peak * a / epsilon - b) / c;
+        d_squared.max(0.0).sqrt()
+    }
+
+    /// Contribution of the brightest color channel at `distance`, ignoring
+    /// occlusion. Mirrors `light_attenuation_at_dist_r_two` in the probe pass.
+    pub fn intensity_at(&self, distance: f32) -> f32
+    {
+        let (a, b, c) = (self.falloff.x, self.falloff.y, self.falloff.z);
+        let distance = distance.max(self.min_distance);
+        let attenuation = (a / (b + c * distance * distance)).clamp(0.0, 1000.0);
+        self.peak_intensity() * attenuation
+    }
+
+    fn peak_intensity(&self) -> f32
+    {
+        let srgba = self.color.to_srgba();
+        self.intensity * srgba.red.max(srgba.green).max(srgba.blue)
+    }
+
+    /// Light with the color of a blackbody at `kelvin`, clamped to
+    /// `1000..=12000` K. E.g. ~3200 K for tungsten, ~6500 K for daylight.
+    /// Other fields are left at their defaults, so `falloff` still needs to
+    /// be set.
+    pub fn from_temperature(kelvin: f32, intensity: f32) -> Self
+    {
+        Self {
+            intensity,
+            color: kelvin_to_color(kelvin),
+            ..default()
+        }
+    }
+
+    /// Replaces the color with the blackbody color at `kelvin`, see
+    /// [`Self::from_temperature`].
+    pub fn set_temperature(&mut self, kelvin: f32)
+    {
+        self.color = kelvin_to_color(kelvin);
+    }
+}
+
+/// Blackbody color approximation by Tanner Helland, fitted to the CIE 1964
+/// 10° color matching functions. Produces sRGB, white at ~6600 K.
+fn kelvin_to_color(kelvin: f32) -> Color
+{
+    let t = kelvin.clamp(1000.0, 12000.0) / 100.0;
+
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let green = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_17 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    Color::srgb(
+        (red / 255.0).clamp(0.0, 1.0),
+        (green / 255.0).clamp(0.0, 1.0),
+        (blue / 255.0).clamp(0.0, 1.0),
+    )
+}
+
+impl Default for OmniLightSource2D
+{
+    fn default() -> Self
+    {
+        Self {
+            intensity:          0.0,
+            color:              Color::default(),
+            falloff:            Vec3::ZERO,
+            jitter_intensity:   0.0,
+            jitter_translation: 0.0,
+            occluder_mask:      OCCLUDER_MASK_ALL,
+            shadow_bias:        1.0,
+            min_distance:       1e-3,
+            indirect_quality:   1.0,
+            direct_only:        false,
+            edge_color:         None,
+            subtractive:        false,
+            source_radius:      0.0,
+            enabled:            true,
+        }
+    }
+}
+
+/// Rectangular area light, e.g. a fluorescent tube or a window. It is
+/// sampled at several points of its surface, see
+/// [`LightPassParams::rect_light_samples`](crate::gi::resource::LightPassParams::rect_light_samples),
+/// which casts soft shadows. Rotates and scales with its transform.
 #[rustfmt::skip]
-#[derive(Reflect, Component, Default, Clone, Copy)]
+#[derive(Reflect, Component, Clone, Copy)]
+#[require(VisibilityClass)]
+#[component(on_add = bevy::camera::visibility::add_visibility_class::<RectLightSource2D>)]
+#[reflect(Component)]
+pub struct RectLightSource2D {
+    pub half_size:     Vec2,
+    pub color:         Color,
+    /// Total intensity, split between the sample points.
+    pub intensity:     f32,
+    /// Falloff of each sample point, see [`OmniLightSource2D::falloff`].
+    pub falloff:       Vec3,
+    /// See [`OmniLightSource2D::occluder_mask`].
+    pub occluder_mask: u32,
+    /// See [`OmniLightSource2D::enabled`].
+    pub enabled:       bool,
+}
+
+impl RectLightSource2D
+{
+    /// Distance from the center at which the light's contribution drops
+    /// below `epsilon`, see [`OmniLightSource2D::effective_radius`].
+    pub fn effective_radius(&self, epsilon: f32) -> f32
+    {
+        let point = OmniLightSource2D {
+            intensity: self.intensity,
+            color: self.color,
+            falloff: self.falloff,
+            ..default()
+        };
+        point.effective_radius(epsilon) + self.half_size.length()
+    }
+}
+
+impl Default for RectLightSource2D
+{
+    fn default() -> Self
+    {
+        Self {
+            half_size:     Vec2::ZERO,
+            color:         Color::default(),
+            intensity:     0.0,
+            falloff:       Vec3::ZERO,
+            occluder_mask: OCCLUDER_MASK_ALL,
+            enabled:       true,
+        }
+    }
+}
+
+#[rustfmt::skip]
+#[derive(Reflect, Component, Clone, Copy)]
 #[require(VisibilityClass)]
 #[component(on_add = bevy::camera::visibility::add_visibility_class::<LightOccluder2D>)]
 #[reflect(Component)]
 pub struct LightOccluder2D {
-    pub h_size: Vec2,
+    pub h_size:        Vec2,
+    /// Bitmask of groups this occluder belongs to, see
+    /// [`OmniLightSource2D::occluder_mask`].
+    pub group:         u32,
+    /// Height relative to a full wall. `1.0` blocks light completely,
+    /// lower values cast proportionally lighter shadows.
+    pub height:        f32,
+    /// Radius of the rounded corners in world units, clamped to the smaller
+    /// half extent. `0.0` keeps sharp corners. Rounded corners give softer,
+    /// more natural shadow corners. Line of sight queries on the CPU still
+    /// use the sharp box.
+    pub corner_radius: f32,
+}
+
+impl Default for LightOccluder2D
+{
+    fn default() -> Self
+    {
+        Self {
+            h_size:        Vec2::ZERO,
+            group:         OCCLUDER_GROUP_DEFAULT,
+            height:        1.0,
+            corner_radius: 0.0,
+        }
+    }
+}
+
+/// Scales the intensity of the [`OmniLightSource2D`] or [`RectLightSource2D`]
+/// on the same entity by the [`LightModulator`](crate::gi::resource::LightModulator)
+/// level, e.g. to pulse lights on the beat. The light's own `intensity` is
+/// ignored while this is present.
+#[rustfmt::skip]
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ModulatedLight {
+    /// Intensity at a modulator level of `1.0`.
+    pub base_intensity: f32,
+    /// How much the level affects the light, `0.0` keeps `base_intensity`
+    /// and `1.0` scales it by the level.
+    pub amount:         f32,
+}
+
+impl ModulatedLight
+{
+    pub fn intensity(&self, level: f32) -> f32
+    {
+        (self.base_intensity * (1.0 + self.amount * (level - 1.0))).max(0.0)
+    }
+}
+
+/// Solid cells of a tile grid, e.g. a level's collision map, occluding light
+/// like one [`LightOccluder2D`] per cell at a fraction of the cost.
+///
+/// Cells are stored as a bitgrid and uploaded only when the component
+/// changes. The grid is placed by `origin` rather than the entity's
+/// transform, and only the first grid in the scene is used. Cells belong to
+/// no occluder group: they block every light with the default
+/// [`OCCLUDER_MASK_ALL`] mask, lights with a custom mask ignore them. The
+/// same holds for line-of-sight queries on
+/// [`LightVisibility`](crate::gi::line_of_sight::LightVisibility).
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct TileOccluderGrid
+{
+    /// World position of the bottom-left corner of cell `(0, 0)`.
+    pub origin:    Vec2,
+    pub cell_size: Vec2,
+    size:          UVec2,
+    bits:          Vec<u32>,
+}
+
+impl TileOccluderGrid
+{
+    /// An empty grid of `size` cells.
+    pub fn new(size: UVec2, cell_size: Vec2, origin: Vec2) -> Self
+    {
+        let cells = (size.x * size.y) as usize;
+        Self {
+            origin,
+            cell_size,
+            size,
+            bits: vec![0; cells.div_ceil(32)],
+        }
+    }
+
+    pub fn size(&self) -> UVec2
+    {
+        self.size
+    }
+
+    /// Whether the cell is solid, cells outside the grid are empty.
+    pub fn is_solid(&self, cell: UVec2) -> bool
+    {
+        self.bit_index(cell)
+            .is_some_and(|i| self.bits[i / 32] & (1 << (i % 32)) != 0)
+    }
+
+    /// Cells outside the grid are ignored.
+    pub fn set_solid(&mut self, cell: UVec2, solid: bool)
+    {
+        let Some(i) = self.bit_index(cell) else {
+            return;
+        };
+        if solid {
+            self.bits[i / 32] |= 1 << (i % 32);
+        } else {
+            self.bits[i / 32] &= !(1 << (i % 32));
+        }
+    }
+
+    /// Bitgrid in row-major order, bit `i % 32` of word `i / 32` is cell
+    /// `(i % size.x, i / size.x)`.
+    pub fn bits(&self) -> &[u32]
+    {
+        &self.bits
+    }
+
+    /// World space center of the cell.
+    pub fn cell_center(&self, cell: UVec2) -> Vec2
+    {
+        self.origin + (cell.as_vec2() + 0.5) * self.cell_size
+    }
+
+    /// First solid cell on the segment from `a` to `b` and the fraction of
+    /// the segment at which it enters it, `0.0` if `a` is in a solid cell.
+    /// Walks the cells the segment crosses (DDA).
+    pub fn first_solid_cell(&self, a: Vec2, b: Vec2) -> Option<(UVec2, f32)>
+    {
+        if self.size.x == 0 || self.size.y == 0 || self.cell_size.cmple(Vec2::ZERO).any() {
+            return None;
+        }
+        let start = (a - self.origin) / self.cell_size;
+        let delta = (b - self.origin) / self.cell_size - start;
+        let size = self.size.as_vec2();
+
+        // Clip the segment to the grid (slab method in cell units).
+        let mut t_enter = 0.0_f32;
+        let mut t_exit = 1.0_f32;
+        for axis in 0..2 {
+            let (o, d) = (start[axis], delta[axis]);
+            if d == 0.0 {
+                if o < 0.0 || o >= size[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = -o / d;
+            let t1 = (size[axis] - o) / d;
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+
+        let max_cell = self.size.as_ivec2() - 1;
+        let mut cell = (start + delta * t_enter).floor().as_ivec2().clamp(IVec2::ZERO, max_cell);
+        let step = IVec2::new(delta.x.signum() as i32, delta.y.signum() as i32);
+        let mut t = t_enter;
+        let mut t_next = Vec2::INFINITY;
+        let mut t_step = Vec2::INFINITY;
+        for axis in 0..2 {
+            let d = delta[axis];
+            if d != 0.0 {
+                let boundary = cell[axis] as f32 + if d > 0.0 { 1.0 } else { 0.0 };
+                t_next[axis] = (boundary - start[axis]) / d;
+                t_step[axis] = 1.0 / d.abs();
+            }
+        }
+
+        loop {
+            if self.is_solid(cell.as_uvec2()) {
+                return Some((cell.as_uvec2(), t));
+            }
+            let axis = if t_next.x < t_next.y { 0 } else { 1 };
+            t = t_next[axis];
+            if t > t_exit {
+                return None;
+            }
+            cell[axis] += step[axis];
+            if cell[axis] < 0 || cell[axis] > max_cell[axis] {
+                return None;
+            }
+            t_next[axis] += t_step[axis];
+        }
+    }
+
+    fn bit_index(&self, cell: UVec2) -> Option<usize>
+    {
+        (cell.x < self.size.x && cell.y < self.size.y)
+            .then(|| (cell.y * self.size.x + cell.x) as usize)
+    }
 }
 
+/// Keeps the [`LightOccluder2D`] on the same entity sized to its [`Sprite`].
+///
+/// The occluder's `h_size` follows the sprite's `custom_size`, atlas rect or
+/// image size and the entity's global scale, `group` and `height` are left as
+/// set. Sprites whose image isn't loaded yet keep their current size.
+#[derive(Reflect, Component, Clone, Copy, Default)]
+#[require(LightOccluder2D)]
+#[reflect(Component)]
+pub struct ShadowCaster;
+
+/// Draws the sprite on the same entity at full brightness, on top of the lit
+/// layers, e.g. for glowing orbs or world-space HUD elements.
+///
+/// The plugin moves the entity to the unlit render layer, see
+/// [`MagicLightLayers::unlit`](crate::gi::render_layer::MagicLightLayers::unlit),
+/// which a camera following the `ObjectsCamera` renders into
+/// `CameraTargets::unlit_target`. Unlit sprites are drawn over every lit
+/// layer, regardless of their z order.
+#[derive(Reflect, Component, Clone, Copy, Default)]
+#[reflect(Component)]
+pub struct Unlit;
+
 impl From<(f32, f32)> for LightOccluder2D
 {
     fn from(value: (f32, f32)) -> Self
     {
         LightOccluder2D {
             h_size: value.into(),
+            ..default()
         }
     }
 }
@@ -37,7 +460,10 @@ impl From<Vec2> for LightOccluder2D
 {
     fn from(value: Vec2) -> Self
     {
-        LightOccluder2D { h_size: value }
+        LightOccluder2D {
+            h_size: value,
+            ..default()
+        }
     }
 }
 
@@ -46,6 +472,36 @@ impl From<Vec2> for LightOccluder2D
 #[reflect(Component)]
 pub struct SkylightMask2D {
     pub h_size: Vec2,
+    /// Color skylight is multiplied by inside the mask, e.g. for stained
+    /// glass. `None` blocks skylight completely.
+    pub tint:   Option<LinearRgba>,
+}
+
+/// Keeps a light, spawned as a child of a moving entity, at `offset` from its
+/// parent. With a non-zero `smoothing` the light trails behind the parent,
+/// e.g. a lantern swaying behind the player.
+#[rustfmt::skip]
+#[derive(Reflect, Component, Clone, Copy, Default)]
+#[reflect(Component)]
+pub struct AttachedLight {
+    /// Offset from the parent, in the parent's local space.
+    pub offset:    Vec2,
+    /// Time in seconds the light takes to close most of the gap to its
+    /// target position. `0.0` attaches it rigidly.
+    pub smoothing: f32,
+}
+
+/// Animates the [`SkylightMask2D`] on the same entity towards a target size,
+/// e.g. a retractable roof. The probe pass reads mask sizes every frame, so
+/// the lit area follows the animation within the temporal reservoir.
+#[rustfmt::skip]
+#[derive(Reflect, Component, Clone, Copy, Default)]
+#[require(SkylightMask2D)]
+#[reflect(Component)]
+pub struct SkylightMaskAnimation {
+    pub target_h_size: Vec2,
+    /// Change of `h_size` per second, in world units.
+    pub speed:         f32,
 }
 
 #[rustfmt::skip]
@@ -55,3 +511,88 @@ pub struct SkylightLight2D {
     pub color:     Color,
     pub intensity: f32,
 }
+
+/// Directional light (e.g. sunlight) casting parallel shadows across the
+/// whole scene. Only the first visible one with a non-zero `direction` is
+/// used.
+#[rustfmt::skip]
+#[derive(Reflect, Component, Clone, Copy, Default)]
+#[reflect(Component)]
+pub struct DirectionalLight2D {
+    /// Direction the light travels in, in world space.
+    pub direction:      Vec2,
+    pub color:          Color,
+    pub intensity:      f32,
+    /// Angular radius of the light's disc in radians, e.g. about `0.005`
+    /// for the sun. Shadows are sharp where the occluder meets the ground
+    /// and soften with the distance from it. `0.0` casts hard shadows.
+    pub angular_radius: f32,
+}
+
+pub(crate) fn system_animate_skylight_masks(
+    mut query: Query<(&mut SkylightMask2D, &SkylightMaskAnimation)>,
+    time: Res<Time>,
+)
+{
+    for (mut mask, animation) in query.iter_mut() {
+        if mask.h_size == animation.target_h_size {
+            continue;
+        }
+        let max_delta = animation.speed.max(0.0) * time.delta_secs();
+        let step = (animation.target_h_size - mask.h_size).clamp(Vec2::splat(-max_delta), Vec2::splat(max_delta));
+        mask.h_size += step;
+    }
+}
+
+pub(crate) fn system_sync_shadow_casters(
+    mut query: Query<(&mut LightOccluder2D, &Sprite, &GlobalTransform), With<ShadowCaster>>,
+    images: Res<Assets<Image>>,
+    atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+)
+{
+    for (mut occluder, sprite, transform) in query.iter_mut() {
+        let size = sprite.custom_size.or_else(|| {
+            if let Some(rect) = sprite.rect {
+                return Some(rect.size());
+            }
+            if let Some(atlas) = &sprite.texture_atlas {
+                return atlas.texture_rect(&atlas_layouts).map(|rect| rect.size().as_vec2());
+            }
+            images.get(&sprite.image).map(|image| image.size_f32())
+        });
+        let Some(size) = size else {
+            continue;
+        };
+
+        let h_size = size * 0.5 * transform.scale().truncate().abs();
+        if occluder.h_size != h_size {
+            occluder.h_size = h_size;
+        }
+    }
+}
+
+pub(crate) fn system_follow_attached_lights(
+    mut query: Query<(Ref<AttachedLight>, &ChildOf, &mut Transform, &GlobalTransform)>,
+    query_parents: Query<&GlobalTransform>,
+    time: Res<Time>,
+)
+{
+    for (attached, child_of, mut transform, global_transform) in query.iter_mut() {
+        let depth = transform.translation.z;
+        if attached.smoothing <= 0.0 || attached.is_added() {
+            transform.translation = attached.offset.extend(depth);
+            continue;
+        }
+        let Ok(parent) = query_parents.get(child_of.parent()) else {
+            continue;
+        };
+
+        // Global transforms are from the last propagation, so the light
+        // eases from where it was drawn towards where it should be.
+        let target = parent.transform_point(attached.offset.extend(depth));
+        let t = 1.0 - (-time.delta_secs() / attached.smoothing).exp();
+        let position = global_transform.translation().lerp(target, t);
+        let local = parent.affine().inverse().transform_point3(position);
+        transform.translation = local.truncate().extend(depth);
+    }
+}
@@ -2,27 +2,46 @@ use bevy::prelude::*;
 use bevy::render::render_resource::ShaderType;
 
 use crate::gi::constants::GI_SCREEN_PROBE_SIZE;
-use crate::gi::types::OmniLightSource2D;
+use crate::gi::types::{OmniLightSource2D, RectLightSource2D};
 
 #[rustfmt::skip]
 #[derive(Default, Clone, ShaderType)]
 pub struct GpuOmniLightSource {
-    pub center:    Vec2,
-    pub intensity: f32,
-    pub color:     Vec3,
-    pub falloff:   Vec3,
+    pub center:           Vec2,
+    pub intensity:        f32,
+    pub color:            Vec3,
+    pub falloff:          Vec3,
+    pub occluder_mask:    u32,
+    pub radius:           f32,
+    pub shadow_bias:      f32,
+    pub min_distance:     f32,
+    pub indirect_quality: f32,
+    pub direct_only:      u32,
+    pub edge_color:       Vec3,
+    pub subtractive:      u32,
+    pub source_radius:    f32,
 }
 
 impl GpuOmniLightSource
 {
-    pub fn new(light: OmniLightSource2D, center: Vec2) -> Self
+    pub fn new(light: OmniLightSource2D, center: Vec2, radius: f32) -> Self
     {
         let color: Srgba = light.color.into();
+        let edge_color = light.edge_color.map_or(color, Srgba::from);
         Self {
             center,
             intensity: light.intensity,
             color: color.to_vec3(),
             falloff: light.falloff,
+            occluder_mask: light.occluder_mask,
+            radius,
+            shadow_bias: light.shadow_bias.max(0.0),
+            min_distance: light.min_distance.max(0.0),
+            indirect_quality: light.indirect_quality.clamp(0.0, 1.0),
+            direct_only: light.direct_only as u32,
+            edge_color: edge_color.to_vec3(),
+            subtractive: light.subtractive as u32,
+            source_radius: light.source_radius.max(0.0),
         }
     }
 }
@@ -35,12 +54,56 @@ pub struct GpuLightSourceBuffer {
     pub data:  Vec<GpuOmniLightSource>,
 }
 
+#[rustfmt::skip]
+#[derive(Default, Clone, ShaderType)]
+pub struct GpuRectLightSource {
+    pub center:        Vec2,
+    /// Half extent along the light's local x axis, in world space.
+    pub axis_x:        Vec2,
+    /// Half extent along the light's local y axis, in world space.
+    pub axis_y:        Vec2,
+    pub intensity:     f32,
+    pub color:         Vec3,
+    pub falloff:       Vec3,
+    pub occluder_mask: u32,
+    pub radius:        f32,
+}
+
+impl GpuRectLightSource
+{
+    pub fn new(light: &RectLightSource2D, center: Vec2, axis_x: Vec2, axis_y: Vec2, radius: f32) -> Self
+    {
+        let color: Srgba = light.color.into();
+        Self {
+            center,
+            axis_x,
+            axis_y,
+            intensity: light.intensity,
+            color: color.to_vec3(),
+            falloff: light.falloff,
+            occluder_mask: light.occluder_mask,
+            radius,
+        }
+    }
+}
+
+#[rustfmt::skip]
+#[derive(Default, Clone, ShaderType)]
+pub struct GpuRectLightSourceBuffer {
+    pub count: u32,
+    #[size(runtime)]
+    pub data:  Vec<GpuRectLightSource>,
+}
+
 #[rustfmt::skip]
 #[derive(Default, Clone, ShaderType)]
 pub struct GpuLightOccluder2D {
     pub center: Vec2,
     pub rotation: Vec4,
     pub h_extent: Vec2,
+    pub group: u32,
+    pub height: f32,
+    pub corner_radius: f32,
 }
 
 #[rustfmt::skip]
@@ -58,17 +121,21 @@ pub struct GpuCameraParams {
     pub screen_size_inv:   Vec2,
     pub view_proj:         Mat4,
     pub inverse_view_proj: Mat4,
+    /// Area covered by the SDF relative to the view, not its resolution.
     pub sdf_scale:         Vec2,
     pub inv_sdf_scale:     Vec2,
+    /// Scale from GI camera NDC to displayed camera NDC.
+    pub display_scale:     f32,
 }
 
 #[rustfmt::skip]
 #[derive(Clone, ShaderType, Debug)]
 pub struct GpuLightPassParams {
     pub frame_counter:          i32,
-    pub probe_size:             i32,
+    pub probe_size:             IVec2,
     pub probe_atlas_cols:       i32,
     pub probe_atlas_rows:       i32,
+    pub frame_cycle:            i32,
     pub skylight_color:         Vec3,
 
     pub reservoir_size:              u32,
@@ -78,6 +145,27 @@ pub struct GpuLightPassParams {
     pub indirect_light_contrib:      f32,
     pub indirect_rays_per_sample:    i32,
     pub indirect_rays_radius_factor: f32,
+    pub minimum_ambient:             Vec3,
+    pub sun_direction:               Vec2,
+    pub sun_color:                   Vec3,
+    pub specular_strength:           f32,
+    pub shininess:                   f32,
+    pub light_sample_budget:         u32,
+    pub indirect_blue_noise:         u32,
+    pub rect_light_samples:          u32,
+    pub max_luminance_clamp:         f32,
+    pub filter_edge_aware:           u32,
+    pub height_map_enabled:          u32,
+    pub height_scale:                f32,
+    pub light_height:                f32,
+    pub fog_density:                 f32,
+    pub fog_color:                   Vec3,
+    pub raymarch_max_steps:          i32,
+    pub raymarch_max_distance:       f32,
+    pub history_length:              i32,
+    pub light_accumulation:          u32,
+    /// Tangent of the directional light's angular radius.
+    pub sun_penumbra:                f32,
 }
 
 impl Default for GpuLightPassParams
@@ -86,9 +174,10 @@ impl Default for GpuLightPassParams
     {
         Self {
             frame_counter:    0,
-            probe_size:       0,
+            probe_size:       IVec2::ZERO,
             probe_atlas_cols: 0,
             probe_atlas_rows: 0,
+            frame_cycle:      1,
             skylight_color:   Vec3::new(0.003, 0.0078, 0.058) / 100.0,
 
             reservoir_size:         16,
@@ -99,16 +188,47 @@ impl Default for GpuLightPassParams
 
             indirect_rays_per_sample:    64,
             indirect_rays_radius_factor: 3.0,
+            minimum_ambient:             Vec3::ZERO,
+            sun_direction:               Vec2::ZERO,
+            sun_color:                   Vec3::ZERO,
+            specular_strength:           0.0,
+            shininess:                   32.0,
+            light_sample_budget:         0,
+            indirect_blue_noise:         0,
+            rect_light_samples:          4,
+            max_luminance_clamp:         1000.0,
+            filter_edge_aware:           1,
+            height_map_enabled:          0,
+            height_scale:                64.0,
+            light_height:                32.0,
+            fog_density:                 0.0,
+            fog_color:                   Vec3::ONE,
+            raymarch_max_steps:          32,
+            raymarch_max_distance:       10000.0,
+            history_length:              16,
+            light_accumulation:          0,
+            sun_penumbra:                0.0,
         }
     }
 }
 
+/// Camera of one probe atlas tile, mirrors `ProbeData` in
+/// `bevy_magic_light_2d::gi_types`.
 #[rustfmt::skip]
 #[derive(Clone, ShaderType, Default)]
 pub struct GpuProbeData {
-    pub camera_pose: Vec2,
+    /// World position of the camera.
+    pub camera_pose:       Vec2,
+    /// Camera the probe tile was traced with, used to reproject it when the
+    /// camera zooms or rotates.
+    pub view_proj:         Mat4,
+    pub inverse_view_proj: Mat4,
 }
 
+/// One [`GpuProbeData`] per probe atlas tile, i.e. per frame of the temporal
+/// cycle. The entry of the current frame is at `LightPassParams::frame_counter`
+/// and tile `i` of the atlas holds the probes traced in frame `i`. Mirrors
+/// `ProbeDataBuffer` in `bevy_magic_light_2d::gi_types`.
 #[rustfmt::skip]
 #[derive(Clone, ShaderType)]
 pub struct GpuProbeDataBuffer {
@@ -125,9 +245,7 @@ impl Default for GpuProbeDataBuffer
         Self {
             count: MAX_PROBES,
             data:  vec![
-                GpuProbeData {
-                    camera_pose: Vec2::ZERO,
-                };
+                GpuProbeData::default();
                 MAX_PROBES as usize
             ],
         }
@@ -139,13 +257,15 @@ impl Default for GpuProbeDataBuffer
 pub struct GpuSkylightMaskData {
     pub center:   Vec2,
     pub h_extent: Vec2,
+    pub tint:     Vec3,
 }
 
 impl GpuSkylightMaskData
 {
-    pub fn new(center: Vec2, h_extent: Vec2) -> Self
+    pub fn new(center: Vec2, h_extent: Vec2, tint: Option<LinearRgba>) -> Self
     {
-        Self { center, h_extent }
+        let tint = tint.map_or(Vec3::ZERO, |tint| tint.to_vec3());
+        Self { center, h_extent, tint }
     }
 }
 
@@ -156,3 +276,29 @@ pub struct GpuSkylightMaskBuffer {
     #[size(runtime)]
     pub data: Vec<GpuSkylightMaskData>,
 }
+
+/// [`TileOccluderGrid`](crate::gi::types::TileOccluderGrid) as read by the SDF
+/// pass. `size` is zero when there is no grid, `bits` always holds at least
+/// one word so the binding is never empty.
+#[rustfmt::skip]
+#[derive(Clone, ShaderType)]
+pub struct GpuTileOccluderGrid {
+    pub origin:    Vec2,
+    pub cell_size: Vec2,
+    pub size:      UVec2,
+    #[size(runtime)]
+    pub bits:      Vec<u32>,
+}
+
+impl Default for GpuTileOccluderGrid
+{
+    fn default() -> Self
+    {
+        Self {
+            origin:    Vec2::ZERO,
+            cell_size: Vec2::ONE,
+            size:      UVec2::ZERO,
+            bits:      vec![0],
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use bevy::asset::AssetPath;
-use bevy::math::IVec2;
+use bevy::math::{IVec2, Vec3};
 
 use crate::gi::WORKGROUP_SIZE;
 
@@ -25,20 +25,109 @@ impl AssetUtil
     }
 }
 
+/// Rounds `size` up to the next multiple of `WORKGROUP_SIZE` on both axes, so
+/// that dispatching `size / WORKGROUP_SIZE` workgroups covers every texel.
+/// Sizes that are already aligned are returned unchanged, negative sizes are
+/// treated as zero.
 pub fn align_to_work_group_grid(size: IVec2) -> IVec2
 {
     let wg_size = WORKGROUP_SIZE as i32;
-    // Only add padding if necessary
-    IVec2::new(
-        if size.x % wg_size == 0 {
-            size.x
-        } else {
-            size.x + wg_size - size.x % wg_size
-        },
-        if size.y % wg_size == 0 {
-            size.y
-        } else {
-            size.y + wg_size - size.y % wg_size
-        },
-    )
+    let align = |v: i32| {
+        let v = v.max(0);
+        // Only add padding if necessary
+        match v % wg_size {
+            0 => v,
+            rem => v + wg_size - rem,
+        }
+    };
+    IVec2::new(align(size.x), align(size.y))
+}
+
+/// Compresses the part of `color` above `knee` so it rolls off towards
+/// `knee + 1.0` instead of growing without bound. The brightest channel
+/// drives the compression and the others are scaled with it, so the hue is
+/// kept. Below the knee the color is unchanged.
+pub fn soft_knee(color: Vec3, knee: f32) -> Vec3
+{
+    let knee = knee.max(0.0);
+    let peak = color.max_element();
+    if peak <= knee {
+        return color;
+    }
+    let excess = peak - knee;
+    let compressed = knee + excess / (1.0 + excess);
+    color * (compressed / peak)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const WG: i32 = WORKGROUP_SIZE as i32;
+
+    #[test]
+    fn aligned_sizes_are_unchanged()
+    {
+        for size in [IVec2::ZERO, IVec2::splat(WG), IVec2::new(1024, 768), IVec2::new(WG * 3, WG * 7)] {
+            assert_eq!(align_to_work_group_grid(size), size);
+        }
+    }
+
+    #[test]
+    fn unaligned_sizes_round_up()
+    {
+        assert_eq!(align_to_work_group_grid(IVec2::new(1, 1)), IVec2::splat(WG));
+        assert_eq!(align_to_work_group_grid(IVec2::new(WG + 1, WG - 1)), IVec2::new(WG * 2, WG));
+        assert_eq!(align_to_work_group_grid(IVec2::new(1000, 1000)), IVec2::splat(1000_i32.div_ceil(WG) * WG));
+    }
+
+    #[test]
+    fn negative_sizes_are_zero()
+    {
+        assert_eq!(align_to_work_group_grid(IVec2::new(-3, -WG)), IVec2::ZERO);
+    }
+
+    #[test]
+    fn dispatch_covers_every_texel()
+    {
+        for width in 0..=3 * WG {
+            for height in [1, 767, 768, 769, 1000] {
+                let aligned = align_to_work_group_grid(IVec2::new(width, height));
+                let groups = aligned / WG;
+
+                assert_eq!(aligned % WG, IVec2::ZERO);
+                assert!(groups.x * WG >= width && groups.y * WG >= height);
+                // No more than one partially used workgroup per axis.
+                assert!(aligned.x - width < WG && aligned.y - height < WG);
+            }
+        }
+    }
+
+    #[test]
+    fn soft_knee_keeps_colors_below_knee()
+    {
+        let color = Vec3::new(0.2, 0.5, 0.8);
+        assert_eq!(soft_knee(color, 0.8), color);
+    }
+
+    #[test]
+    fn soft_knee_rolls_off_above_knee()
+    {
+        let knee = 0.8;
+        let mut last = 0.0;
+        for intensity in [1.0, 2.0, 4.0, 16.0, 1000.0] {
+            let peak = soft_knee(Vec3::new(0.5, 1.0, 0.25) * intensity, knee).max_element();
+            assert!(peak > last && peak < knee + 1.0);
+            last = peak;
+        }
+    }
+
+    #[test]
+    fn soft_knee_keeps_hue()
+    {
+        let color = soft_knee(Vec3::new(1.0, 2.0, 4.0) * 3.0, 0.5);
+        assert!((color.y / color.z - 0.5).abs() < 1e-5);
+        assert!((color.x / color.z - 0.25).abs() < 1e-5);
+    }
 }
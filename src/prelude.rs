@@ -1,7 +1,61 @@
+pub use crate::gi::adaptive_rays::{AdaptiveRayParams, GiAdaptiveRays};
+pub use crate::gi::bake::{LightBake, LightBakeState};
 pub use crate::gi::camera_viewer::{CameraViewerPlugin, CameraType, CameraViewerState};
-pub use crate::gi::compositing::{setup_post_processing_camera, CameraTargets};
-pub use crate::gi::render_layer::{CAMERA_LAYER_FLOOR, CAMERA_LAYER_OBJECTS, CAMERA_LAYER_WALLS};
-pub use crate::gi::resource::{BevyMagicLight2DSettings, LightPassParams};
-pub use crate::gi::types::{LightOccluder2D, OmniLightSource2D, SkylightLight2D, SkylightMask2D};
-pub use crate::gi::BevyMagicLight2DPlugin;
+pub use crate::gi::compositing::{setup_post_processing_camera, CameraTargets, GiTargetTransition};
+pub use crate::gi::diagnostics::{GiConvergence, GiDiagnostics, GiPass};
+pub use crate::gi::exposure::{AutoExposureParams, GiExposure};
+#[cfg(feature = "egui")]
+pub use crate::gi::inspector::LightInspectorPlugin;
+pub use crate::gi::light_ray_debug::{LightRayDebug, LightRayGizmos};
+pub use crate::gi::line_of_sight::LightVisibility;
+#[cfg(feature = "hot_reload_shaders")]
+pub use crate::gi::pipeline_assets::HotReloadShadersPlugin;
+pub use crate::gi::render_layer::{
+    MagicLightLayers,
+    CAMERA_LAYER_FLOOR,
+    CAMERA_LAYER_OBJECTS,
+    CAMERA_LAYER_UNLIT,
+    CAMERA_LAYER_WALLS,
+    MAX_EXTRA_LIT_LAYERS,
+};
+pub use crate::gi::resource::{
+    ActiveLight,
+    ActiveLightsSnapshot,
+    BevyMagicLight2DSettings,
+    DebugView,
+    FrameStepControl,
+    GiActiveLights,
+    GiPipelineReady,
+    GiPipelineStatus,
+    GiStatus,
+    GiWarmup,
+    IrradianceFilter,
+    LayerAlpha,
+    LayerIndirect,
+    LightAccumulation,
+    LightModulator,
+    LightPassParams,
+    OccluderOutline,
+    PostProcessingCameraParams,
+    UpscaleFilter,
+    Vignette,
+};
+pub use crate::gi::scene_gizmos::{SceneGizmoParams, SceneGizmos};
+pub use crate::gi::types::{
+    AttachedLight,
+    DirectionalLight2D,
+    LightOccluder2D,
+    ModulatedLight,
+    OmniLightSource2D,
+    RectLightSource2D,
+    ShadowCaster,
+    SkylightLight2D,
+    SkylightMask2D,
+    SkylightMaskAnimation,
+    TileOccluderGrid,
+    Unlit,
+    OCCLUDER_GROUP_DEFAULT,
+    OCCLUDER_MASK_ALL,
+};
+pub use crate::gi::{BevyMagicLight2DPlugin, GiExtractSet, MAX_LIGHTS, MAX_OCCLUDERS};
 pub use crate::{FloorCamera, ObjectsCamera, SpriteCamera, WallsCamera};
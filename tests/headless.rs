@@ -0,0 +1,86 @@
+//! End to end check that the GI pipeline initializes in a headless app.
+//!
+//! Winit is disabled, so the primary window has no surface and nothing is
+//! presented, but the render world still runs every frame. A GPU adapter is
+//! required, so the test is ignored by default. Run it with
+//! `cargo test --test headless -- --ignored`, on CI a software adapter such
+//! as lavapipe works.
+
+use bevy::camera::RenderTarget;
+use bevy::prelude::*;
+use bevy::render::RenderApp;
+use bevy::winit::WinitPlugin;
+use bevy_magic_light_2d::gi::pipeline::LightPassPipelineBindGroups;
+use bevy_magic_light_2d::prelude::*;
+
+/// Frames to wait for the pipelines to compile before giving up.
+const MAX_FRAMES: usize = 200;
+
+fn setup(mut commands: Commands, camera_targets: Res<CameraTargets>)
+{
+    commands.spawn((
+        Transform::from_translation(Vec3::new(-64.0, 0.0, 0.0)),
+        Visibility::default(),
+        OmniLightSource2D {
+            intensity: 1.0,
+            falloff: Vec3::new(1.5, 10.0, 0.005),
+            ..default()
+        },
+    ));
+    commands.spawn((
+        Transform::default(),
+        Visibility::default(),
+        LightOccluder2D {
+            h_size: Vec2::new(20.0, 20.0),
+            ..default()
+        },
+    ));
+    commands.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(camera_targets.floor_render_target()),
+            ..default()
+        },
+        FloorCamera,
+    ));
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn pipeline_creates_bind_groups()
+{
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: (256u32, 256u32).into(),
+                    ..default()
+                }),
+                exit_condition: bevy::window::ExitCondition::DontExit,
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+        BevyMagicLight2DPlugin,
+    ))
+    .add_systems(Startup, setup.after(setup_post_processing_camera));
+
+    // `App::run` would do this, the app is updated by hand instead.
+    app.finish();
+    app.cleanup();
+
+    let mut frames = 0;
+    while !app.world().resource::<GiPipelineStatus>().is_ready() {
+        assert!(frames < MAX_FRAMES, "GI pipeline not ready after {MAX_FRAMES} frames");
+        app.update();
+        frames += 1;
+    }
+
+    // A few more frames with the passes actually dispatching.
+    for _ in 0..5 {
+        app.update();
+    }
+
+    let render_world = app.sub_app(RenderApp).world();
+    assert!(render_world.contains_resource::<LightPassPipelineBindGroups>());
+}